@@ -5,7 +5,8 @@ export interface MobileRouteStep extends GeoJSON.Feature<GeoJSON.LineString> {
   properties: {
     distance: number; // meters for this step
     way_name: string; // street name
-    labels: [number, number, boolean]; // [cycleway, road, salmon]
+    route_name?: string; // signed bike route(s) followed, e.g. "Hudson River Greenway"
+    labels: [number, number, boolean, number, number, number]; // [cycleway, road, salmon, bike network, surface, LTS]
   };
 }
 
@@ -17,6 +18,7 @@ export interface MobileRoute {
 export interface RouteMeta {
   total_distance: number; // meters
   total_time_estimate: number; // seconds
+  signals: number;
 }
 
 export type CorridorData = GeoJSON.FeatureCollection<GeoJSON.LineString>;
@@ -53,4 +55,21 @@ export interface MobileCostModel {
   hill_penalty: number;    // 0 = ignore, 1 = avoid, 2 = strongly avoid
   salmon_penalty: number;  // 0 = ignore, 1 = avoid, 2 = strongly avoid
   avoid_major_roads: boolean;
+  surface_tolerance?: number; // 0 = stick to pavement, 1 = tolerate some (default), 2 = ride anything
+  max_lts?: number;           // 1-4: most stressful streets to ride; any when unset
+  rules?: CostRule[];         // custom multipliers, applied on top of the controls above
+}
+
+/// Scales the cost of every Way meeting all of the given conditions.
+export interface CostRule {
+  if: {
+    cycleway?: string | string[];
+    road?: string | string[];
+    salmon?: boolean;
+    bike_network?: string | string[];
+    surface?: string | string[];
+    lts?: number | number[];
+    name?: string;              // contained in the street name, ignoring case
+  };
+  multiply_by: number;
 }
@@ -9,11 +9,15 @@ fn run_a_long_route_lots_of_times() -> Result<(), anyhow::Error> {
 
     for _i in 0..20 {
         graph.calculate_route(
-            Point::new(-73.98949, 40.75376),
-            Point::new(-73.92632, 40.64338),
+            &[
+                Point::new(-73.98949, 40.75376),
+                Point::new(-73.92632, 40.64338),
+            ],
             true,
             None,
             None,
+            None,
+            None,
         )?;
     }
     println!("Routing took: {}", now.elapsed().as_millis());
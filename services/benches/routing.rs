@@ -1,6 +1,6 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 use geo::Point;
-use rusty_router::graph::Graph;
+use rusty_router::graph::{Graph, SearchMode};
 
 /// Cross-city route from the performance issue:
 /// Upper West Side → Flatbush, Brooklyn (~10km)
@@ -12,11 +12,37 @@ fn bench_cross_city_route(c: &mut Criterion) {
     c.bench_function("cross_city_route", |b| {
         b.iter(|| {
             graph
-                .calculate_route(start, end, false, None, Some(0.75))
+                .calculate_route(&[start, end], false, None, Some(0.75), None, None)
                 .unwrap()
         })
     });
 }
 
-criterion_group!(benches, bench_cross_city_route);
+/// Same route, searching from both ends at once
+fn bench_cross_city_route_bidirectional(c: &mut Criterion) {
+    let graph = Graph::new().unwrap();
+    let start = Point::new(-73.98002, 40.7751769);
+    let end = Point::new(-73.9091492, 40.6270216);
+
+    c.bench_function("cross_city_route_bidirectional", |b| {
+        b.iter(|| {
+            graph
+                .calculate_route(
+                    &[start, end],
+                    false,
+                    None,
+                    Some(0.75),
+                    Some(SearchMode::Bidirectional),
+                    None,
+                )
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_cross_city_route,
+    bench_cross_city_route_bidirectional
+);
 criterion_main!(benches);
@@ -29,6 +29,12 @@ curl --request POST \
         ["highway"!="platform"]
         ["highway"!="proposed"]
         ["bicycle"!="no"]
-        ;
-      out geom;
+        ->.ways;
+      .ways out geom;
+      // along with the turn restrictions between them, and the bike routes along them
+      (
+        rel(bw.ways)["type"="restriction"];
+        rel(bw.ways)["route"="bicycle"];
+      );
+      out body;
     ' > out.geom.json
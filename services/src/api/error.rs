@@ -0,0 +1,52 @@
+/// Middleware for reporting failed requests as JSON
+use crate::api::geojson::serialize_route_geom;
+use crate::graph::RoutingError;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Serialize)]
+struct ErrorBody {
+    code: &'static str,
+    message: String,
+    /// for searches which gave up, the path to wherever they got closest to the end
+    #[serde(skip_serializing_if = "Option::is_none")]
+    partial_route: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct ErrorResponse {
+    error: ErrorBody,
+}
+
+/// The HTTP status and JSON body to report a failed request with. RoutingErrors are the
+/// request's fault and map to 4xx statuses (or 503, for searches that gave up); anything else
+/// is a server bug, and is reported as an opaque 500.
+pub fn serialize_error(e: &anyhow::Error) -> Result<(u16, Value), anyhow::Error> {
+    let (status, body) = match e.downcast_ref::<RoutingError>() {
+        Some(routing_error) => {
+            let partial_route = match routing_error {
+                RoutingError::BudgetExceeded {
+                    partial: Some(partial),
+                    ..
+                } => serialize_route_geom(partial).ok(),
+                _ => None,
+            };
+            let body = ErrorBody {
+                code: routing_error.code(),
+                message: routing_error.to_string(),
+                partial_route,
+            };
+            (routing_error.status(), body)
+        }
+        None => {
+            let body = ErrorBody {
+                code: "internal",
+                message: "Internal server error".to_owned(),
+                partial_route: None,
+            };
+            (500, body)
+        }
+    };
+
+    Ok((status, serde_json::to_value(ErrorResponse { error: body })?))
+}
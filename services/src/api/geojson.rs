@@ -1,5 +1,5 @@
 /// Middleware for formatting Graph structures into Geojson
-use crate::graph::{Depth, TraversalSegment};
+use crate::graph::{Depth, Isochrone, TraversalSegment};
 use crate::osm::{Distance, NodeId, WayId, WayLabels};
 use geo::{Coord, LineString};
 use geojson::ser::serialize_geometry;
@@ -12,6 +12,7 @@ pub struct Route {
 
     len: usize,
     last_step_way: WayId,
+    last_step_leg: usize,
 }
 
 impl Route {
@@ -21,21 +22,23 @@ impl Route {
             steps: vec![init_step],
             len: 1,
             last_step_way: segment.way,
+            last_step_leg: segment.leg,
         }
     }
 
     /// extends this route with the specified TraversalSegment
-    /// attempts to add to the last RouteStep (if on the same Way).
+    /// attempts to add to the last RouteStep (if on the same Way and leg).
     /// otherwise, inits a new RouteStep
     pub fn extend_with(&mut self, segment: &TraversalSegment) {
         // if still on the same way, extend the existing step
-        if self.last_step_way == segment.way {
+        if self.last_step_way == segment.way && self.last_step_leg == segment.leg {
             let last_step = self.steps.get_mut(self.len - 1).unwrap();
             last_step.extend_with(segment);
         } else {
             // otherwise, create and append a new step
             self.len += 1;
             self.last_step_way = segment.way;
+            self.last_step_leg = segment.leg;
             self.steps.push(RouteStep::new(segment, self.len));
         }
     }
@@ -68,6 +71,7 @@ pub struct RouteStep {
     pub elevation_loss: i32,
     pub depth: Depth,
     pub labels: WayLabels,
+    pub leg: usize,
     pub idx: usize,
 }
 
@@ -84,6 +88,7 @@ impl RouteStep {
             way: segment.way,
             depth: segment.depth,
             labels: segment.labels,
+            leg: segment.leg,
             idx,
         }
     }
@@ -114,6 +119,12 @@ pub fn serialize_traversal_geoms(traversal: &[TraversalSegment]) -> Result<Value
     )?)
 }
 
+pub fn serialize_isochrones(isochrones: &[Isochrone]) -> Result<Value, anyhow::Error> {
+    Ok(serde_json::from_str(
+        &geojson::ser::to_feature_collection_string(isochrones)?,
+    )?)
+}
+
 pub fn serialize_route_geom(segments: &[TraversalSegment]) -> Result<Value, anyhow::Error> {
     let route: Route = segments.into();
     Ok(serde_json::from_str(
@@ -1,4 +1,5 @@
 pub mod compression;
 pub mod corridor;
+pub mod error;
 pub mod geojson;
 pub mod navigation;
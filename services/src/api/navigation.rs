@@ -1,6 +1,6 @@
 /// Lean response serialization for the /navigate endpoint (mobile-optimized).
-/// Drops from/to/way IDs, includes street names from DB.
-use crate::graph::TraversalSegment;
+/// Drops from/to/way IDs, includes street and bike route names from DB.
+use crate::graph::{TraversalSegment, AVG_CYCLING_SPEED_MPS};
 use crate::osm::{Distance, WayId, WayLabels};
 use geo::{Coord, LineString};
 use geojson::ser::serialize_geometry;
@@ -8,9 +8,6 @@ use serde::{Serialize, Serializer};
 use serde_json::Value;
 use std::collections::HashMap;
 
-/// Estimated average cycling speed in m/s (~15 km/h)
-const AVG_CYCLING_SPEED_MPS: f64 = 4.2;
-
 #[derive(Serialize, Clone, Debug)]
 pub struct NavigationStep {
     #[serde(serialize_with = "serialize_nav_step_geom")]
@@ -18,16 +15,26 @@ pub struct NavigationStep {
 
     pub distance: Distance,
     pub way_name: String,
+    /// the signed bike routes this step follows, e.g. "Hudson River Greenway"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub route_name: Option<String>,
     pub labels: WayLabels,
+    pub leg: usize,
 }
 
 impl NavigationStep {
-    pub fn new(segment: &TraversalSegment, way_names: &HashMap<WayId, String>) -> Self {
+    pub fn new(
+        segment: &TraversalSegment,
+        way_names: &HashMap<WayId, String>,
+        route_names: &HashMap<WayId, String>,
+    ) -> Self {
         Self {
             geometry: vec![segment.geometry.start, segment.geometry.end],
             distance: segment.length,
             way_name: way_names.get(&segment.way).cloned().unwrap_or_default(),
+            route_name: route_names.get(&segment.way).cloned(),
             labels: segment.labels,
+            leg: segment.leg,
         }
     }
 
@@ -49,6 +56,8 @@ where
 pub struct NavigationMeta {
     pub total_distance: Distance,
     pub total_time_estimate: u32, // seconds
+    /// traffic signals along the route
+    pub signals: usize,
 }
 
 #[derive(Serialize, Debug)]
@@ -60,22 +69,25 @@ pub struct NavigationResponse {
 }
 
 /// Build lean navigation steps from route segments, merging consecutive segments
-/// on the same way into a single step (same logic as Route in geojson.rs).
+/// on the same way and leg into a single step (same logic as Route in geojson.rs).
 pub fn build_navigation_steps(
     segments: &[TraversalSegment],
     way_names: &HashMap<WayId, String>,
+    route_names: &HashMap<WayId, String>,
 ) -> Vec<NavigationStep> {
     let mut iter = segments.iter();
     let first = iter.next().unwrap();
-    let mut steps = vec![NavigationStep::new(first, way_names)];
+    let mut steps = vec![NavigationStep::new(first, way_names, route_names)];
     let mut last_way = first.way;
+    let mut last_leg = first.leg;
 
     for segment in iter {
-        if segment.way == last_way {
+        if segment.way == last_way && segment.leg == last_leg {
             steps.last_mut().unwrap().extend_with(segment);
         } else {
             last_way = segment.way;
-            steps.push(NavigationStep::new(segment, way_names));
+            last_leg = segment.leg;
+            steps.push(NavigationStep::new(segment, way_names, route_names));
         }
     }
 
@@ -86,9 +98,11 @@ pub fn build_navigation_steps(
 pub fn serialize_navigation(
     segments: &[TraversalSegment],
     way_names: &HashMap<WayId, String>,
+    route_names: &HashMap<WayId, String>,
+    signals: usize,
     corridor: Option<Value>,
 ) -> Result<NavigationResponse, anyhow::Error> {
-    let steps = build_navigation_steps(segments, way_names);
+    let steps = build_navigation_steps(segments, way_names, route_names);
 
     let total_distance: Distance = steps.iter().map(|s| s.distance).sum();
     let total_time_estimate = (total_distance as f64 / AVG_CYCLING_SPEED_MPS).round() as u32;
@@ -100,6 +114,7 @@ pub fn serialize_navigation(
         meta: NavigationMeta {
             total_distance,
             total_time_estimate,
+            signals,
         },
         corridor,
     })
@@ -0,0 +1,19 @@
+use std::env;
+use std::path::Path;
+use std::process;
+
+use rusty_router::db;
+
+fn main() -> Result<(), anyhow::Error> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        eprintln!("must supply a path to an OSM change (.osc or .osc.gz) file");
+        process::exit(1);
+    }
+
+    let summary = db::apply_changes(Path::new(&args[1]))?;
+    println!("{summary:#?}");
+    println!("contraction hierarchies and landmarks were dropped; rerun contract-graph and build-landmarks");
+
+    Ok(())
+}
@@ -6,11 +6,15 @@ fn main() -> Result<(), anyhow::Error> {
 
     for _i in 0..20 {
         graph.calculate_route(
-            Point::new(-73.98949, 40.75376),
-            Point::new(-73.92632, 40.64338),
+            &[
+                Point::new(-73.98949, 40.75376),
+                Point::new(-73.92632, 40.64338),
+            ],
             true,
             None,
             None,
+            None,
+            None,
         )?;
     }
 
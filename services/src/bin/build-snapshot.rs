@@ -0,0 +1,26 @@
+use anyhow::anyhow;
+use rusty_router::graph::{write_snapshot, InMemoryGraphRepository};
+use std::env;
+use std::path::Path;
+use std::time::Instant;
+
+/// Writes the graph in the DB out to a snapshot for the router to memory-map, at the path given or
+/// else `GRAPH_PATH`
+fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt()
+        .compact()
+        .with_target(false)
+        .init();
+
+    let path = env::args()
+        .nth(1)
+        .or_else(|| env::var("GRAPH_PATH").ok())
+        .ok_or_else(|| anyhow!("must supply a path to write the snapshot to"))?;
+
+    let now = Instant::now();
+    let graph = InMemoryGraphRepository::new()?;
+    let summary = write_snapshot(&graph, Path::new(&path))?;
+    println!("{summary:?}: written in {}s", now.elapsed().as_secs());
+
+    Ok(())
+}
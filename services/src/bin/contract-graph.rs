@@ -0,0 +1,22 @@
+use rusty_router::db;
+use rusty_router::graph::ContractionHierarchy;
+use std::time::Instant;
+
+fn main() -> Result<(), anyhow::Error> {
+    tracing_subscriber::fmt()
+        .compact()
+        .with_target(false)
+        .init();
+
+    let mut conn = db::get_conn()?;
+    db::init_contraction_tables(&conn)?;
+
+    for (name, cost_model) in ContractionHierarchy::profiles() {
+        let now = Instant::now();
+        let hierarchy = ContractionHierarchy::build(&conn, name, cost_model)?;
+        hierarchy.save(&mut conn)?;
+        println!("{name}: built and saved in {}s", now.elapsed().as_secs());
+    }
+
+    Ok(())
+}
@@ -1,24 +1,37 @@
 use anyhow::anyhow;
+use chrono::NaiveDateTime;
 use geo::Point;
 use itertools::Itertools;
 use lambda_http::{
     run, service_fn, Body, Error as LambdaError, Request, RequestExt, RequestPayloadExt, Response,
 };
 use rusty_router::api::compression::Encoding;
-use rusty_router::osm::Location;
+use rusty_router::osm::{Distance, Location};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::error;
 
-use rusty_router::api::{compression, corridor, geojson, navigation};
+use rusty_router::api::{compression, corridor, error, geojson, navigation};
 use rusty_router::graph::{
-    CostModel, Graph, MobileCostModel, RouteMetadata, TraversalSegment, Weight,
+    Cost, CostModel, Graph, MobileCostModel, Profile, ProfileRegistry, RouteMetadata, RoutingError,
+    SearchBudget, SearchMode, TraversalMetric, TraversalSegment, Weight,
 };
 use std::collections::HashMap;
+use std::time::Duration;
+
+/// how long any one search may run before giving up, well within the Lambda's own timeout
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// the most alternative routes a request may ask for, each of which takes several searches
+const MAX_ALTERNATIVES: usize = 5;
 
 // create a singleton of the Graph struct on lambda boot
 thread_local! {
-    static GRAPH: Graph = Graph::new().unwrap();
+    static GRAPH: Graph = Graph::new()
+        .unwrap()
+        .with_budget(SearchBudget::default().with_timeout(SEARCH_TIMEOUT));
+    static PROFILES: ProfileRegistry = ProfileRegistry::load().unwrap();
 }
 
 #[tokio::main]
@@ -47,20 +60,25 @@ async fn handler(event: Request) -> Result<Response<Body>, LambdaError> {
         return Ok(Response::builder().status(403).body(Body::Empty)?);
     }
 
-    let body = GRAPH.with(|graph| match event.raw_http_path() {
-        "/traverse" => traverse_handler(graph, &event),
-        "/route" => route_handler(graph, &event),
-        "/navigate" => navigate_handler(graph, &event),
-        "/ping" => ping_handler(graph),
-        _ => Err(anyhow!("invalid path")),
-    })?;
+    let body = GRAPH.with(|graph| {
+        PROFILES.with(|profiles| match event.raw_http_path() {
+            "/traverse" => traverse_handler(graph, profiles, &event),
+            "/route" => route_handler(graph, profiles, &event),
+            "/navigate" => navigate_handler(graph, profiles, &event),
+            "/alternatives" => alternatives_handler(graph, &event),
+            "/isochrone" => isochrone_handler(graph, &event),
+            "/loop" => loop_handler(graph, &event),
+            "/profiles" => profiles_handler(profiles),
+            "/ping" => ping_handler(graph),
+            _ => Err(RoutingError::invalid_request("invalid path").into()),
+        })
+    });
+    let body = match body {
+        Ok(body) => body,
+        Err(e) => return error_response(&e, &origin),
+    };
 
-    let mut response = Response::builder()
-        .status(200)
-        .header("content-type", "application/json")
-        .header("Access-Control-Allow-Headers", "Content-Type")
-        .header("Access-Control-Allow-Origin", &origin)
-        .header("Access-Control-Allow-Methods", "GET,POST");
+    let mut response = response_builder(200, &origin);
 
     // perform compression, if specified
     if let Some(accept_encoding) = event.headers().get("Accept-Encoding") {
@@ -84,6 +102,33 @@ async fn handler(event: Request) -> Result<Response<Body>, LambdaError> {
     Ok(response.body(body.into())?)
 }
 
+fn response_builder(status: u16, origin: &str) -> lambda_http::http::response::Builder {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .header("Access-Control-Allow-Headers", "Content-Type")
+        .header("Access-Control-Allow-Origin", origin)
+        .header("Access-Control-Allow-Methods", "GET,POST")
+}
+
+/// Reports a failed request as JSON with a machine-readable code, logging anything that isn't
+/// the request's fault
+fn error_response(e: &anyhow::Error, origin: &str) -> Result<Response<Body>, LambdaError> {
+    if e.downcast_ref::<RoutingError>().is_none() {
+        error!("Internal Error: {e:#}");
+    }
+    let (status, body) = error::serialize_error(e)?;
+    Ok(response_builder(status, origin).body(body.to_string().into())?)
+}
+
+/// Parses the JSON request body, treating a missing or malformed one as the client's error
+fn parse_params<T: DeserializeOwned>(event: &Request, name: &str) -> Result<T, anyhow::Error> {
+    event
+        .payload::<T>()
+        .map_err(|e| RoutingError::invalid_request(format!("Could not parse {name} params: {e}")))?
+        .ok_or_else(|| RoutingError::invalid_request(format!("Missing {name} params")).into())
+}
+
 /// handler for waking up the lambda
 /// ensures that the Graph singleton is instantiated and traversable
 fn ping_handler(graph: &Graph) -> Result<String, anyhow::Error> {
@@ -97,11 +142,51 @@ fn ping_handler(graph: &Graph) -> Result<String, anyhow::Error> {
     Ok("ok!".to_owned())
 }
 
+#[derive(Serialize)]
+struct ProfilesResponse<'a> {
+    profiles: &'a [Profile],
+}
+
+/// handler listing the routing profiles requests may ask for by name
+fn profiles_handler(profiles: &ProfileRegistry) -> Result<String, anyhow::Error> {
+    let response = ProfilesResponse {
+        profiles: profiles.profiles(),
+    };
+    Ok(serde_json::to_string(&response)?)
+}
+
+/// The cost model and heuristic weight to search with: those of the named profile, if the request
+/// asks for one, with any heuristic weight it gives taking precedence. A profile can't be combined
+/// with a cost model of the request's own.
+fn apply_profile(
+    profiles: &ProfileRegistry,
+    profile: Option<&str>,
+    cost_model: Option<CostModel>,
+    heuristic_weight: Option<Weight>,
+) -> Result<(Option<CostModel>, Option<Weight>), anyhow::Error> {
+    match profile {
+        None => Ok((cost_model, heuristic_weight)),
+        Some(_) if cost_model.is_some() => Err(RoutingError::invalid_request(
+            "A request can give a profile or a cost model, but not both",
+        )
+        .into()),
+        Some(id) => {
+            let profile = profiles.get(id)?;
+            Ok((
+                Some(profile.cost_model.clone()),
+                heuristic_weight.or(profile.heuristic_weight),
+            ))
+        }
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct TraversalParams {
     lat: f64,
     lon: f64,
     depth: usize,
+    /// a server-side profile to route with, as "name@version" or just "name" for its latest
+    profile: Option<String>,
     cost_model: Option<CostModel>,
     heuristic_weight: Option<Weight>,
 }
@@ -111,20 +196,23 @@ struct TraversalResponse {
     traversal: Value,
 }
 
-fn traverse_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Error> {
-    let params = event
-        .payload::<TraversalParams>()?
-        .ok_or_else(|| anyhow!("Missing traversal params"))?;
+fn traverse_handler(
+    graph: &Graph,
+    profiles: &ProfileRegistry,
+    event: &Request,
+) -> Result<String, anyhow::Error> {
+    let params = parse_params::<TraversalParams>(event, "traversal")?;
 
     let starting_coord = Point::new(params.lon, params.lat);
+    let (cost_model, heuristic_weight) = apply_profile(
+        profiles,
+        params.profile.as_deref(),
+        params.cost_model,
+        params.heuristic_weight,
+    )?;
 
     let traversal = graph
-        .calculate_traversal(
-            starting_coord,
-            params.depth,
-            params.cost_model,
-            params.heuristic_weight,
-        )
+        .calculate_traversal(starting_coord, params.depth, cost_model, heuristic_weight)
         .map_err(|e| {
             error!("Routing Error: {e}");
             e
@@ -140,13 +228,58 @@ fn traverse_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Er
     Ok(serde_json::to_string(&response)?)
 }
 
+#[derive(Debug, Deserialize)]
+struct IsochroneParams {
+    start: Location,
+    metric: TraversalMetric,
+    /// thresholds of `metric` to outline, e.g. [300, 600, 900] seconds
+    bands: Vec<f64>,
+    cost_model: Option<CostModel>,
+}
+
+#[derive(Serialize)]
+struct IsochroneResponse {
+    isochrones: Value,
+}
+
+fn isochrone_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Error> {
+    let params = parse_params::<IsochroneParams>(event, "isochrone")?;
+
+    let isochrones = graph
+        .calculate_isochrones(
+            params.start.into(),
+            params.metric,
+            &params.bands,
+            params.cost_model,
+        )
+        .map_err(|e| {
+            error!("Routing Error: {e}");
+            e
+        })?;
+    let isochrones = geojson::serialize_isochrones(&isochrones).map_err(|e| {
+        error!("Serialization Error: {e}");
+        e
+    })?;
+
+    let response = IsochroneResponse { isochrones };
+
+    Ok(serde_json::to_string(&response)?)
+}
+
 #[derive(Debug, Deserialize)]
 struct RouteParams {
     start: Location,
     end: Location,
+    /// intermediate points to pass through, in order, between start and end
+    waypoints: Option<Vec<Location>>,
     with_traversal: Option<bool>,
+    /// a server-side profile to route with, as "name@version" or just "name" for its latest
+    profile: Option<String>,
     cost_model: Option<CostModel>,
     heuristic_weight: Option<Weight>,
+    search_mode: Option<SearchMode>,
+    /// local time of setting off, e.g. "2024-06-01T22:30:00", to route around timed closures
+    departure: Option<NaiveDateTime>,
 }
 
 #[derive(Serialize)]
@@ -156,20 +289,30 @@ struct RouteResponse {
     meta: RouteMetadata,
 }
 
-fn route_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Error> {
-    let params = event
-        .payload::<RouteParams>()?
-        .ok_or_else(|| anyhow!("Missing route params"))?;
+fn route_handler(
+    graph: &Graph,
+    profiles: &ProfileRegistry,
+    event: &Request,
+) -> Result<String, anyhow::Error> {
+    let params = parse_params::<RouteParams>(event, "route")?;
 
     let with_traversal = params.with_traversal.unwrap_or(false);
+    let points = route_points(params.start, params.waypoints, params.end);
+    let (cost_model, heuristic_weight) = apply_profile(
+        profiles,
+        params.profile.as_deref(),
+        params.cost_model,
+        params.heuristic_weight,
+    )?;
 
     let (route, traversal, meta) = graph
         .calculate_route(
-            params.start.into(),
-            params.end.into(),
+            &points,
             with_traversal,
-            params.cost_model,
-            params.heuristic_weight,
+            cost_model,
+            heuristic_weight,
+            params.search_mode,
+            params.departure,
         )
         .map_err(|e| {
             error!("Routing Error: {e}");
@@ -199,50 +342,210 @@ fn route_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Error
     Ok(serde_json::to_string(&response)?)
 }
 
+#[derive(Debug, Deserialize)]
+struct AlternativesParams {
+    start: Location,
+    end: Location,
+    /// maximum number of routes to return, defaulting to 3
+    k: Option<usize>,
+    cost_model: Option<CostModel>,
+    heuristic_weight: Option<Weight>,
+}
+
+#[derive(Serialize)]
+struct AlternativeResponse {
+    route: Value,
+    meta: RouteMetadata,
+    cost: Cost,
+    overlap: f32,
+}
+
+#[derive(Serialize)]
+struct AlternativesResponse {
+    alternatives: Vec<AlternativeResponse>,
+}
+
+fn alternatives_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Error> {
+    let params = parse_params::<AlternativesParams>(event, "alternatives")?;
+    let k = params.k.unwrap_or(3);
+    if !(1..=MAX_ALTERNATIVES).contains(&k) {
+        return Err(RoutingError::invalid_request(format!(
+            "k must be between 1 and {MAX_ALTERNATIVES}, got {k}"
+        ))
+        .into());
+    }
+
+    let alternatives = graph
+        .calculate_alternatives(
+            params.start.into(),
+            params.end.into(),
+            k,
+            params.cost_model,
+            params.heuristic_weight,
+        )
+        .map_err(|e| {
+            error!("Routing Error: {e}");
+            e
+        })?;
+
+    let alternatives = alternatives
+        .into_iter()
+        .map(|alternative| {
+            let route = geojson::serialize_route_geom(&alternative.route).map_err(|e| {
+                error!("Serialization Error: {e}");
+                e
+            })?;
+            Ok(AlternativeResponse {
+                route,
+                meta: alternative.meta,
+                cost: alternative.cost,
+                overlap: alternative.overlap,
+            })
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    let response = AlternativesResponse { alternatives };
+
+    Ok(serde_json::to_string(&response)?)
+}
+
+#[derive(Debug, Deserialize)]
+struct LoopParams {
+    start: Location,
+    /// target loop length, in meters
+    distance: Distance,
+    /// maximum number of candidate loops to return, defaulting to 3
+    count: Option<usize>,
+    mobile_cost_model: Option<MobileCostModel>,
+    cost_model: Option<CostModel>,
+}
+
+#[derive(Serialize)]
+struct LoopResponse {
+    route: Value,
+    meta: RouteMetadata,
+    distance: Distance,
+    avg_cost_factor: Cost,
+    overlap: f32,
+}
+
+#[derive(Serialize)]
+struct LoopsResponse {
+    loops: Vec<LoopResponse>,
+}
+
+fn loop_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Error> {
+    let params = parse_params::<LoopParams>(event, "loop")?;
+
+    let cost_model = params
+        .mobile_cost_model
+        .map(|m| m.resolve())
+        .or(params.cost_model);
+
+    let loops = graph
+        .calculate_loops(
+            params.start.into(),
+            params.distance,
+            params.count.unwrap_or(3),
+            cost_model,
+        )
+        .map_err(|e| {
+            error!("Routing Error: {e}");
+            e
+        })?;
+
+    let loops = loops
+        .into_iter()
+        .map(|l| {
+            let route = geojson::serialize_route_geom(&l.route).map_err(|e| {
+                error!("Serialization Error: {e}");
+                e
+            })?;
+            Ok(LoopResponse {
+                route,
+                meta: l.meta,
+                distance: l.distance,
+                avg_cost_factor: l.avg_cost_factor,
+                overlap: l.overlap,
+            })
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    let response = LoopsResponse { loops };
+
+    Ok(serde_json::to_string(&response)?)
+}
+
+/// Orders the requested start, via points, and end into the full list of points to route through
+fn route_points(start: Location, waypoints: Option<Vec<Location>>, end: Location) -> Vec<Point> {
+    std::iter::once(start)
+        .chain(waypoints.unwrap_or_default())
+        .chain(std::iter::once(end))
+        .map(Point::from)
+        .collect()
+}
+
 /// Mobile-optimized /navigate endpoint: lean response (no from/to/way IDs),
-/// merged steps per way, total_distance + total_time_estimate in meta.
+/// merged steps per way, total_distance + total_time_estimate + signals in meta.
 #[derive(Debug, Deserialize)]
 struct NavigateParams {
     start: Location,
     end: Location,
+    /// intermediate points to pass through, in order, between start and end
+    waypoints: Option<Vec<Location>>,
+    /// a server-side profile to route with, as "name@version" or just "name" for its latest
+    profile: Option<String>,
     /// High-level mobile cost model (preferred). Resolved to CostModel internally.
     mobile_cost_model: Option<MobileCostModel>,
     /// Raw cost model (desktop-style, backward compat). Used if mobile_cost_model is absent.
     cost_model: Option<CostModel>,
     heuristic_weight: Option<Weight>,
+    search_mode: Option<SearchMode>,
+    /// local time of setting off, to route around timed closures
+    departure: Option<NaiveDateTime>,
     with_corridor: Option<bool>,
 }
 
-fn navigate_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Error> {
-    let params = event
-        .payload::<NavigateParams>()?
-        .ok_or_else(|| anyhow!("Missing navigate params"))?;
+fn navigate_handler(
+    graph: &Graph,
+    profiles: &ProfileRegistry,
+    event: &Request,
+) -> Result<String, anyhow::Error> {
+    let params = parse_params::<NavigateParams>(event, "navigate")?;
 
     let with_corridor = params.with_corridor.unwrap_or(false);
     let start_point = Point::new(params.start.lon, params.start.lat);
-    let end_point: Point = params.end.into();
+    let points = route_points(params.start, params.waypoints, params.end);
 
-    // Prefer mobile_cost_model (high-level) → resolve to CostModel.
-    // Fall back to raw cost_model for backward compat, then Default.
+    // mobile_cost_model (high-level) resolves to a CostModel, and takes precedence over a raw
+    // cost_model. A named profile stands in for either, but can't be combined with one (400);
+    // with none of the three, the default CostModel is used.
     let cost_model = params
         .mobile_cost_model
         .map(|m| m.resolve())
         .or(params.cost_model);
-
-    let (route_segments, traversal, _) = graph
+    let (cost_model, heuristic_weight) = apply_profile(
+        profiles,
+        params.profile.as_deref(),
+        cost_model,
+        params.heuristic_weight,
+    )?;
+
+    let (route_segments, traversal, meta) = graph
         .calculate_route(
-            start_point,
-            end_point,
+            &points,
             with_corridor, // request traversal when corridor needed
             cost_model,
-            params.heuristic_weight,
+            heuristic_weight,
+            params.search_mode,
+            params.departure,
         )
         .map_err(|e| {
             error!("Routing Error: {e}");
             e
         })?;
 
-    // Collect unique way IDs and look up street names
+    // Collect unique way IDs and look up street and bike route names
     let way_ids = route_segments
         .iter()
         .map(|s| s.way)
@@ -253,6 +556,10 @@ fn navigate_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Er
         error!("Way names lookup error: {e}");
         e
     })?;
+    let route_names = graph.get_route_names(&way_ids).map_err(|e| {
+        error!("Route names lookup error: {e}");
+        e
+    })?;
 
     // Extract corridor from traversal if requested
     let corridor_value = if with_corridor {
@@ -269,7 +576,7 @@ fn navigate_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Er
             start_point,
             exploration_depth,
             None, // Use default cost model for exploration
-            params.heuristic_weight,
+            heuristic_weight,
         ) {
             // Merge deep traversal with route traversal (keep cheapest path to each node)
             for segment in deep_traversal {
@@ -284,14 +591,8 @@ fn navigate_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Er
             }
         }
 
-        // Second-to-last segment has the real accumulated cost;
-        // the last segment is the virtual END_NODE with cost=0
-        let optimal_cost = route_segments
-            .iter()
-            .rev()
-            .find(|s| s.cost > 0.0)
-            .map(|s| s.cost)
-            .unwrap_or(0.0);
+        // The last segment, the partial edge into the virtual END_NODE, has the full accumulated cost
+        let optimal_cost = route_segments.last().map(|s| s.cost).unwrap_or(0.0);
 
         let merged_vec: Vec<TraversalSegment> = merged_traversal.values().cloned().collect();
         let corridor_segments =
@@ -306,11 +607,17 @@ fn navigate_handler(graph: &Graph, event: &Request) -> Result<String, anyhow::Er
         None
     };
 
-    let response = navigation::serialize_navigation(&route_segments, &way_names, corridor_value)
-        .map_err(|e| {
-            error!("Serialization Error: {e}");
-            e
-        })?;
+    let response = navigation::serialize_navigation(
+        &route_segments,
+        &way_names,
+        &route_names,
+        meta.signals(),
+        corridor_value,
+    )
+    .map_err(|e| {
+        error!("Serialization Error: {e}");
+        e
+    })?;
 
     Ok(serde_json::to_string(&response)?)
 }
@@ -1,19 +1,28 @@
 use std::env;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::Path;
 use std::process;
 
-use rusty_router::db::Output;
+use rusty_router::db::{self, Output};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() < 2 {
-        eprintln!("must supply a path to an OSM JSON file");
+        eprintln!("must supply a path to an OSM JSON, PBF or XML file");
         process::exit(1);
     }
 
-    // Open the file in read-only mode with buffer.
     let filename = &args[1];
+
+    // raw extracts need their geometry resolved, which the Overpass JSON comes with
+    if !filename.ends_with(".json") {
+        let count = db::populate_from_extract(Path::new(filename)).unwrap();
+        println!("inserted {count} ways and relations");
+        return;
+    }
+
+    // Open the file in read-only mode with buffer.
     let file = File::open(filename).unwrap();
     let reader = BufReader::new(file);
 
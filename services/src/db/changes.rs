@@ -0,0 +1,283 @@
+/// Governs incremental updates to the DB from OSM change files (osmChange, `.osc`), so that an
+/// edit to the map needn't wait on a full rebuild
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::anyhow;
+use rusqlite::{OptionalExtension, Transaction};
+
+use super::{xml, Bounds, Element};
+use crate::osm::{Location, NodeId, Way, WayId};
+
+/// What an osmChange file does to an element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeAction {
+    Create,
+    Modify,
+    Delete,
+}
+
+/// Tallies of what applying an osmChange file did to the DB
+#[derive(Debug, Default)]
+pub struct ChangeSummary {
+    /// Ways created or modified that are routable
+    pub ways_upserted: usize,
+    /// Ways deleted, or modified to no longer be routable
+    pub ways_deleted: usize,
+    /// routable Ways whose Nodes were missing from both the DB and the change file, as happens
+    /// when a Way first becomes routable, and so which need a full rebuild to pick up
+    pub ways_unresolved: usize,
+    /// unmodified Ways whose Segments were recomputed as their Nodes moved
+    pub ways_moved: usize,
+    pub nodes_moved: usize,
+    pub nodes_deleted: usize,
+    /// Relations are left to a full rebuild
+    pub relations_skipped: usize,
+}
+
+/// Applies an osmChange file to the DB in one transaction. Only the elements routing keeps are
+/// updated: Nodes' coordinates, and Ways' geometry, Segments and labels, recomputed from scratch
+/// for every Way the file creates, modifies or deletes, or whose Nodes it moves. A Way keeps the
+/// bike routes it's on, which are Relations, through a modification.
+///
+/// Any contraction hierarchies and landmarks are dropped, as they no longer match the graph, and
+/// should be rebuilt.
+pub fn apply_changes(path: &Path) -> Result<ChangeSummary, anyhow::Error> {
+    let mut summary = ChangeSummary::default();
+
+    // later changes to an element supersede earlier ones; None is a deletion
+    let mut nodes: HashMap<NodeId, Option<Location>> = HashMap::new();
+    // the Nodes created or modified, for their traffic control devices
+    let mut node_elements: HashMap<NodeId, Element> = HashMap::new();
+    let mut ways: HashMap<WayId, Option<Element>> = HashMap::new();
+    xml::read_osc(path, |action, element| {
+        match (element.r#type.as_str(), action) {
+            ("node", ChangeAction::Delete) => {
+                nodes.insert(element.id, None);
+                node_elements.remove(&element.id);
+            }
+            ("node", _) => {
+                let (Some(lat), Some(lon)) = (element.lat, element.lon) else {
+                    return Err(anyhow!("Node {} is missing its coordinates", element.id));
+                };
+                nodes.insert(element.id, Some(Location { lat, lon }));
+                node_elements.insert(element.id, element);
+            }
+            ("way", ChangeAction::Delete) => {
+                ways.insert(element.id, None);
+            }
+            ("way", _) => {
+                ways.insert(element.id, Some(element));
+            }
+            _ => summary.relations_skipped += 1,
+        }
+        Ok(())
+    })?;
+
+    let mut conn = super::get_conn()?;
+    let tx = conn.transaction()?;
+
+    #[cfg(feature = "elevation")]
+    let elevation = std::env::var("ELEVATION_PATH")
+        .ok()
+        .map(|p| super::elevation::ElevationLookup::new(&p))
+        .transpose()?;
+
+    // Nodes are only kept for routable Ways, so only those already in the DB can have moved
+    let mut moved: Vec<NodeId> = vec![];
+    let mut move_stmt = tx.prepare_cached(
+        "UPDATE Nodes SET lon = ?2, lat = ?3 WHERE id = ?1 AND (lon != ?2 OR lat != ?3)",
+    )?;
+    for (id, location) in &nodes {
+        if let Some(location) = location {
+            if move_stmt.execute((id, location.lon, location.lat))? > 0 {
+                moved.push(*id);
+            }
+        }
+    }
+    drop(move_stmt);
+    summary.nodes_moved = moved.len();
+
+    // Ways left as they were but for their Nodes' coordinates need only their geometry redone,
+    // once the rest are applied
+    let mut moved_ways: HashSet<WayId> = HashSet::new();
+    let mut stmt = tx.prepare_cached("SELECT way FROM WayNodes WHERE node = ?1")?;
+    for id in &moved {
+        for way in stmt.query_map([id], |row| row.get(0))? {
+            let way = way?;
+            if !ways.contains_key(&way) {
+                moved_ways.insert(way);
+            }
+        }
+    }
+    drop(stmt);
+
+    // Nodes that may no longer belong to any Way once the changes are applied
+    let mut orphan_candidates: HashSet<NodeId> = nodes
+        .iter()
+        .filter(|(_, location)| location.is_none())
+        .map(|(id, _)| *id)
+        .collect();
+
+    for (id, element) in ways {
+        let routes = get_routes(&tx, id)?;
+        let existed = !routes.is_empty();
+        orphan_candidates.extend(super::delete_way(&tx, id)?);
+
+        let way = element
+            .filter(Element::is_routable_way)
+            .map(|element| resolve_changed_way(&tx, element, &nodes))
+            .transpose()?;
+        match way {
+            Some(Some(way)) => {
+                super::insert_way_element(
+                    &tx,
+                    way,
+                    #[cfg(feature = "elevation")]
+                    elevation.as_ref(),
+                )?;
+                restore_routes(&tx, &routes)?;
+                summary.ways_upserted += 1;
+            }
+            Some(None) => {
+                eprintln!("{id}: nodes missing from the DB and the change file");
+                summary.ways_unresolved += 1;
+            }
+            None => {
+                // a Way that's gone can't be turned from or onto
+                tx.prepare_cached("DELETE FROM TurnRestrictions WHERE ?1 IN (from_way, to_way)")?
+                    .execute([id])?;
+                if existed {
+                    summary.ways_deleted += 1;
+                }
+            }
+        }
+    }
+
+    for id in &moved_ways {
+        let node_ids = super::delete_way_geometry(&tx, *id)?;
+        let node_coords = get_node_locations(&tx, &node_ids)?;
+        let bounds = Bounds::from_geometry(&node_coords);
+        let way = Way {
+            id: *id,
+            min_lat: bounds.minlat,
+            max_lat: bounds.maxlat,
+            min_lon: bounds.minlon,
+            max_lon: bounds.maxlon,
+        };
+        super::insert_way_geometry(
+            &tx,
+            &way,
+            &node_ids,
+            &node_coords,
+            #[cfg(feature = "elevation")]
+            elevation.as_ref(),
+        )?;
+    }
+    summary.ways_moved = moved_ways.len();
+
+    let mut stmt = tx.prepare_cached(
+        "DELETE FROM Nodes WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM WayNodes WHERE node = ?1)",
+    )?;
+    let mut control_stmt = tx.prepare_cached("DELETE FROM NodeControls WHERE node = ?1")?;
+    for id in &orphan_candidates {
+        let deleted = stmt.execute([id])?;
+        if deleted > 0 {
+            control_stmt.execute([id])?;
+        }
+        summary.nodes_deleted += deleted;
+    }
+    drop(stmt);
+    drop(control_stmt);
+
+    // signals and signs come and go on Nodes that stay put, but only matter on those still routed
+    // over
+    let mut stmt = tx.prepare_cached("SELECT COUNT(*) > 0 FROM Nodes WHERE id = ?1")?;
+    for (id, element) in &node_elements {
+        if stmt.query_row([id], |row| row.get::<_, bool>(0))? {
+            super::insert_node_control(&tx, element)?;
+        }
+    }
+    drop(stmt);
+
+    invalidate_preprocessing(&tx)?;
+    tx.commit()?;
+
+    Ok(summary)
+}
+
+/// The Way's bike route labels in each direction, or none if it isn't in the DB
+fn get_routes(tx: &Transaction, way: WayId) -> Result<Vec<(WayId, u8, String)>, anyhow::Error> {
+    let mut stmt =
+        tx.prepare_cached("SELECT id, network, route FROM WayLabels WHERE id IN (?1, -?1)")?;
+    let routes = stmt
+        .query_map([way], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<_, _>>()?;
+    Ok(routes)
+}
+
+fn restore_routes(tx: &Transaction, routes: &[(WayId, u8, String)]) -> Result<(), anyhow::Error> {
+    let mut stmt =
+        tx.prepare_cached("UPDATE WayLabels SET network = ?2, route = ?3 WHERE id = ?1")?;
+    for (id, network, route) in routes {
+        stmt.execute((id, network, route))?;
+    }
+    Ok(())
+}
+
+/// Fills in a created or modified Way's geometry from the Nodes in the change file, then those
+/// already in the DB. None if too few of its Nodes are in either.
+fn resolve_changed_way(
+    tx: &Transaction,
+    element: Element,
+    nodes: &HashMap<NodeId, Option<Location>>,
+) -> Result<Option<Element>, anyhow::Error> {
+    let mut stmt = tx.prepare_cached("SELECT lon, lat FROM Nodes WHERE id = ?1")?;
+    let mut coords: HashMap<NodeId, (f64, f64)> = HashMap::new();
+    for id in element.nodes.as_deref().unwrap_or_default() {
+        let location = match nodes.get(id) {
+            Some(location) => location.as_ref().map(|l| (l.lon, l.lat)),
+            None => stmt
+                .query_row([id], |row| Ok((row.get(0)?, row.get(1)?)))
+                .optional()?,
+        };
+        if let Some(location) = location {
+            coords.insert(*id, location);
+        }
+    }
+    Ok(super::extract::resolve_way(element, &coords))
+}
+
+fn get_node_locations(
+    tx: &Transaction,
+    node_ids: &[NodeId],
+) -> Result<Vec<Location>, anyhow::Error> {
+    let mut stmt = tx.prepare_cached("SELECT lon, lat FROM Nodes WHERE id = ?1")?;
+    node_ids
+        .iter()
+        .map(|id| {
+            stmt.query_row([id], |row| {
+                Ok(Location {
+                    lon: row.get(0)?,
+                    lat: row.get(1)?,
+                })
+            })
+            .map_err(|e| anyhow!("Failed Node {id}: {e}"))
+        })
+        .collect()
+}
+
+/// Drops every profile's contraction hierarchy and landmarks, which the router falls back from
+fn invalidate_preprocessing(tx: &Transaction) -> Result<(), anyhow::Error> {
+    for table in ["ContractionProfiles", "LandmarkProfiles"] {
+        let exists: bool = tx.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = ?1",
+            [table],
+            |row| row.get(0),
+        )?;
+        if exists {
+            tx.execute(&format!("DELETE FROM {table}"), [])?;
+        }
+    }
+    Ok(())
+}
@@ -6,7 +6,7 @@ use geo::prelude::*;
 use geo::{point, Point};
 
 use super::{Element, OSMMapper};
-use crate::osm::Way;
+use crate::osm::{Control, Location, NodeId, Way, WayId};
 use std::env;
 
 pub type DBConnection = Connection;
@@ -24,9 +24,17 @@ pub fn init_tables(conn: &Connection) -> Result<(), anyhow::Error> {
     // note that no foreign key relationships are allowed to virtual tables
     conn.execute_batch(
         "
+        DROP TABLE IF EXISTS ContractionEdges;
+        DROP TABLE IF EXISTS ContractionNodes;
+        DROP TABLE IF EXISTS ContractionProfiles;
+        DROP TABLE IF EXISTS LandmarkNodes;
+        DROP TABLE IF EXISTS LandmarkProfiles;
         DROP TABLE IF EXISTS Segments;
         DROP TABLE IF EXISTS WayNodes;
         DROP TABLE IF EXISTS WayLabels;
+        DROP TABLE IF EXISTS WayRestrictions;
+        DROP TABLE IF EXISTS TurnRestrictions;
+        DROP TABLE IF EXISTS NodeControls;
         DROP TABLE IF EXISTS Nodes;
         DROP TABLE IF EXISTS Ways;
 
@@ -65,13 +73,39 @@ pub fn init_tables(conn: &Connection) -> Result<(), anyhow::Error> {
             FOREIGN KEY (n2) REFERENCES Nodes(id)
         );
         CREATE INDEX n1_index ON Segments(n1);
+        CREATE INDEX n2_index ON Segments(n2);
 
         CREATE TABLE WayLabels (
             id       INTEGER PRIMARY KEY,
             cycleway INTEGER NOT NULL,
             road     INTEGER NOT NULL,
             salmon   INTEGER NOT NULL,
-            name     TEXT NOT NULL DEFAULT ''
+            network  INTEGER NOT NULL DEFAULT 0,
+            surface  INTEGER NOT NULL DEFAULT 0,
+            lts      INTEGER NOT NULL DEFAULT 1,
+            name     TEXT NOT NULL DEFAULT '',
+            route    TEXT NOT NULL DEFAULT ''
+        );
+
+        CREATE TABLE WayRestrictions (
+            way   INTEGER NOT NULL,
+            tag   INTEGER NOT NULL,
+            open  INTEGER NOT NULL,
+            hours TEXT NOT NULL
+        );
+        CREATE INDEX way_restrictions_index ON WayRestrictions(way);
+
+        CREATE TABLE TurnRestrictions (
+            from_way INTEGER NOT NULL,
+            via      INTEGER NOT NULL,
+            to_way   INTEGER NOT NULL,
+            only     INTEGER NOT NULL
+        );
+        CREATE INDEX turn_restrictions_index ON TurnRestrictions(via);
+
+        CREATE TABLE NodeControls (
+            node    INTEGER PRIMARY KEY,
+            control INTEGER NOT NULL
         );
     ",
     )?;
@@ -80,11 +114,145 @@ pub fn init_tables(conn: &Connection) -> Result<(), anyhow::Error> {
     Ok(())
 }
 
+/// (re)creates the tables holding preprocessed contraction hierarchies, one per cost profile
+pub fn init_contraction_tables(conn: &Connection) -> Result<(), anyhow::Error> {
+    conn.execute_batch(
+        "
+        DROP TABLE IF EXISTS ContractionEdges;
+        DROP TABLE IF EXISTS ContractionNodes;
+        DROP TABLE IF EXISTS ContractionProfiles;
+
+        CREATE TABLE ContractionProfiles (
+            id         INTEGER PRIMARY KEY,
+            name       TEXT NOT NULL UNIQUE,
+            cost_model TEXT NOT NULL
+        );
+
+        CREATE TABLE ContractionNodes (
+            profile INTEGER NOT NULL,
+            node    INTEGER NOT NULL,
+            rank    INTEGER NOT NULL,
+            PRIMARY KEY (profile, node),
+            FOREIGN KEY (profile) REFERENCES ContractionProfiles(id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE ContractionEdges (
+            profile INTEGER NOT NULL,
+            n1      INTEGER NOT NULL,
+            n2      INTEGER NOT NULL,
+            cost    REAL NOT NULL,
+            way     INTEGER NOT NULL,
+            middle  INTEGER,
+            FOREIGN KEY (profile) REFERENCES ContractionProfiles(id) ON DELETE CASCADE
+        );
+        CREATE INDEX contraction_edges_profile_index ON ContractionEdges(profile);
+    ",
+    )?;
+    println!("Contraction tables created");
+
+    Ok(())
+}
+
+/// (re)creates the tables holding precomputed landmark costs, one set per cost profile
+pub fn init_landmark_tables(conn: &Connection) -> Result<(), anyhow::Error> {
+    conn.execute_batch(
+        "
+        DROP TABLE IF EXISTS LandmarkNodes;
+        DROP TABLE IF EXISTS LandmarkProfiles;
+
+        CREATE TABLE LandmarkProfiles (
+            id         INTEGER PRIMARY KEY,
+            name       TEXT NOT NULL UNIQUE,
+            cost_model TEXT NOT NULL,
+            count      INTEGER NOT NULL
+        );
+
+        -- costs holds little-endian f32s: from each landmark to the node, then back to each
+        CREATE TABLE LandmarkNodes (
+            profile INTEGER NOT NULL,
+            node    INTEGER NOT NULL,
+            costs   BLOB NOT NULL,
+            PRIMARY KEY (profile, node),
+            FOREIGN KEY (profile) REFERENCES LandmarkProfiles(id) ON DELETE CASCADE
+        );
+    ",
+    )?;
+    println!("Landmark tables created");
+
+    Ok(())
+}
+
 /// Insert a OSM-parsed Node element into the DB, synchronously
 pub fn insert_node_element(tx: &Transaction, element: Element) -> anyhow::Result<()> {
     let mut stmt = tx.prepare_cached("INSERT INTO Nodes (id, lon, lat) VALUES (?1, ?2, ?3)")?;
     stmt.execute((&element.id, &element.lon, &element.lat))
         .map_err(|e| anyhow!("Failed Node:\n{:#?}\n{e}", element))?;
+    insert_node_control(tx, &element)?;
+
+    Ok(())
+}
+
+/// Records the traffic control device at an OSM-parsed Node, replacing any it had before. Nodes
+/// without one are left out, as are most Nodes.
+pub fn insert_node_control(tx: &Transaction, element: &Element) -> anyhow::Result<()> {
+    let mut delete_stmt = tx.prepare_cached("DELETE FROM NodeControls WHERE node = ?1")?;
+    delete_stmt.execute([element.id])?;
+
+    let control = element.get_control();
+    if control != Control::None {
+        let mut stmt =
+            tx.prepare_cached("INSERT INTO NodeControls (node, control) VALUES (?1, ?2)")?;
+        let params = (element.id, control as u8);
+        stmt.execute(params)
+            .map_err(|e| anyhow!("Failed NodeControl:\n{:#?}\n{e}", params))?;
+    }
+
+    Ok(())
+}
+
+/// Insert an OSM-parsed Relation element into the DB, synchronously: turn restrictions into
+/// TurnRestrictions, and bike routes onto the WayLabels of their member Ways, which must already
+/// have been inserted. Relations of any other kind are skipped.
+pub fn insert_relation_element(tx: &Transaction, element: Element) -> anyhow::Result<()> {
+    if let Some(route) = element.get_bike_route() {
+        // a Way on several routes is labeled with the most prominent of them, and the names of
+        // every route of that network, each recorded once (compared whole, since one name may
+        // be part of another)
+        let mut promote_stmt = tx.prepare_cached(
+            "UPDATE WayLabels SET network = ?2, route = ?3 WHERE id = ?1 AND network < ?2",
+        )?;
+        let mut join_stmt = tx.prepare_cached(
+            "UPDATE WayLabels SET route = route || '; ' || ?3
+            WHERE id = ?1 AND network = ?2 AND route != ''
+                AND instr('; ' || route || '; ', '; ' || ?3 || '; ') = 0",
+        )?;
+        for way in &route.ways {
+            let params = (way, route.network as u8, &route.name);
+            let promoted = promote_stmt
+                .execute(params)
+                .map_err(|e| anyhow!("Failed bike route WayLabel:\n{:#?}\n{e}", params))?;
+            if promoted == 0 {
+                join_stmt
+                    .execute(params)
+                    .map_err(|e| anyhow!("Failed bike route WayLabel:\n{:#?}\n{e}", params))?;
+            }
+        }
+        return Ok(());
+    }
+
+    let mut stmt = tx.prepare_cached(
+        "INSERT INTO TurnRestrictions (from_way, via, to_way, only) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for restriction in element.get_turn_restrictions() {
+        let params = (
+            restriction.from,
+            restriction.via,
+            restriction.to,
+            restriction.only,
+        );
+        stmt.execute(params)
+            .map_err(|e| anyhow!("Failed TurnRestriction:\n{:#?}\n{e}", params))?;
+    }
 
     Ok(())
 }
@@ -99,29 +267,19 @@ pub fn insert_way_element(
 ) -> anyhow::Result<()> {
     let way = Way::from(&element);
 
-    let mut way_insert_stmt = tx.prepare_cached(
-        "INSERT INTO Ways (id, minLat, maxLat, minLon, maxLon) VALUES (?1, ?2, ?3, ?4, ?5)",
-    )?;
-    way_insert_stmt
-        .execute((
-            &way.id,
-            &way.min_lat,
-            &way.max_lat,
-            &way.min_lon,
-            &way.max_lon,
-        ))
-        .map_err(|e| anyhow!("Failed Way:\n{:#?}\n{e}", way))?;
-
     let mut stmt = tx.prepare_cached(
-        "INSERT INTO WayLabels (id, cycleway, road, salmon, name) VALUES (?1, ?2, ?3, ?4, ?5)",
+        "INSERT INTO WayLabels (id, cycleway, road, salmon, surface, lts, name) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
     )?;
 
     // OSM tags -> internal labeling
     let osm_mapper: OSMMapper = (&element).into();
 
     let road = osm_mapper.get_road_label();
+    let surface = osm_mapper.get_surface_label();
     let (forward_cycleway, reverse_cycleway, salmon) =
         osm_mapper.get_cycleways_and_directionality();
+    let forward_lts = osm_mapper.get_lts(road, forward_cycleway, true);
+    let reverse_lts = osm_mapper.get_lts(road, reverse_cycleway, false);
 
     let name = element.tags.get("name").cloned().unwrap_or_default();
 
@@ -130,6 +288,8 @@ pub fn insert_way_element(
         forward_cycleway as isize,
         road as isize,
         false,
+        surface as isize,
+        forward_lts as isize,
         &name,
     );
     stmt.execute(params)
@@ -140,11 +300,63 @@ pub fn insert_way_element(
         reverse_cycleway as isize,
         road as isize,
         salmon,
+        surface as isize,
+        reverse_lts as isize,
         &name,
     );
     stmt.execute(params)
         .map_err(|e| anyhow!("Failed WayLabel:\n{:#?}\n{e}", params))?;
 
+    // restrictions apply to the Way in both directions, so are only stored for the positive one
+    let mut restriction_insert_stmt = tx.prepare_cached(
+        "INSERT INTO WayRestrictions (way, tag, open, hours) VALUES (?1, ?2, ?3, ?4)",
+    )?;
+    for restriction in osm_mapper.get_restrictions() {
+        let params = (
+            &way.id,
+            restriction.tag as isize,
+            restriction.open,
+            restriction.hours.as_str(),
+        );
+        restriction_insert_stmt
+            .execute(params)
+            .map_err(|e| anyhow!("Failed WayRestriction:\n{:#?}\n{e}", params))?;
+    }
+
+    let node_ids = element.nodes.unwrap_or_default();
+    let node_coords = element.geometry.unwrap_or_default();
+    insert_way_geometry(
+        tx,
+        &way,
+        &node_ids,
+        &node_coords,
+        #[cfg(feature = "elevation")]
+        elevation,
+    )
+}
+
+/// Insert a Way's bounds, Nodes, WayNodes and Segments, i.e. everything of it but its labels and
+/// restrictions, synchronously
+pub fn insert_way_geometry(
+    tx: &Transaction,
+    way: &Way,
+    node_ids: &[NodeId],
+    node_coords: &[Location],
+    #[cfg(feature = "elevation")] elevation: Option<&super::elevation::ElevationLookup>,
+) -> anyhow::Result<()> {
+    let mut way_insert_stmt = tx.prepare_cached(
+        "INSERT INTO Ways (id, minLat, maxLat, minLon, maxLon) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    way_insert_stmt
+        .execute((
+            &way.id,
+            &way.min_lat,
+            &way.max_lat,
+            &way.min_lon,
+            &way.max_lon,
+        ))
+        .map_err(|e| anyhow!("Failed Way:\n{:#?}\n{e}", way))?;
+
     let mut node_insert_stmt =
         tx.prepare_cached("INSERT OR IGNORE INTO Nodes (id, lon, lat) VALUES (?1, ?2, ?3)")?;
     let mut wn_insert_stmt =
@@ -153,8 +365,6 @@ pub fn insert_way_element(
         "INSERT INTO Segments (n1, n2, way, distance, elevation_gain, elevation_loss) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
     )?;
 
-    let node_ids = element.nodes.unwrap_or_default();
-    let node_coords = element.geometry.unwrap_or_default();
     assert!(
         node_ids.len() == node_coords.len(),
         "Ways should always have nodes[] and geometry[] of equal length"
@@ -219,3 +429,39 @@ pub fn insert_way_element(
 
     Ok(())
 }
+
+/// Delete a Way, in both directions, with its labels, restrictions and geometry, synchronously.
+/// Its Nodes are left in place, as other Ways may share them. Returns the Way's Node ids.
+pub fn delete_way(tx: &Transaction, way: WayId) -> anyhow::Result<Vec<NodeId>> {
+    for sql in [
+        "DELETE FROM WayLabels WHERE id IN (?1, -?1)",
+        "DELETE FROM WayRestrictions WHERE way = ?1",
+    ] {
+        tx.prepare_cached(sql)?
+            .execute([way])
+            .map_err(|e| anyhow!("Failed deleting Way {way}:\n{sql}\n{e}"))?;
+    }
+
+    delete_way_geometry(tx, way)
+}
+
+/// Delete a Way's bounds, WayNodes and Segments, synchronously, as the inverse of
+/// `insert_way_geometry`. Returns the Way's Node ids.
+pub fn delete_way_geometry(tx: &Transaction, way: WayId) -> anyhow::Result<Vec<NodeId>> {
+    let mut stmt = tx.prepare_cached("SELECT node FROM WayNodes WHERE way = ?1 ORDER BY pos")?;
+    let node_ids = stmt
+        .query_map([way], |row| row.get(0))?
+        .collect::<Result<Vec<NodeId>, _>>()?;
+
+    for sql in [
+        "DELETE FROM Segments WHERE way IN (?1, -?1)",
+        "DELETE FROM WayNodes WHERE way = ?1",
+        "DELETE FROM Ways WHERE id = ?1",
+    ] {
+        tx.prepare_cached(sql)?
+            .execute([way])
+            .map_err(|e| anyhow!("Failed deleting Way {way}:\n{sql}\n{e}"))?;
+    }
+
+    Ok(node_ids)
+}
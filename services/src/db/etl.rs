@@ -17,8 +17,27 @@ pub struct Bounds {
     pub maxlon: f64,
 }
 
+impl Bounds {
+    /// The bounding box of a Way's geometry
+    pub fn from_geometry(geometry: &[Location]) -> Self {
+        Self {
+            minlat: geometry.iter().map(|l| l.lat).fold(f64::MAX, f64::min),
+            minlon: geometry.iter().map(|l| l.lon).fold(f64::MAX, f64::min),
+            maxlat: geometry.iter().map(|l| l.lat).fold(f64::MIN, f64::max),
+            maxlon: geometry.iter().map(|l| l.lon).fold(f64::MIN, f64::max),
+        }
+    }
+}
+
 pub type Tags = HashMap<String, String>;
 
+#[derive(std::fmt::Debug, Deserialize)]
+pub struct Member {
+    pub r#type: String,
+    pub r#ref: i64,
+    pub role: String,
+}
+
 #[derive(std::fmt::Debug, Deserialize)]
 pub struct Element {
     pub id: i64,
@@ -33,6 +52,26 @@ pub struct Element {
     pub bounds: Option<Bounds>,
     pub nodes: Option<Vec<i64>>,
     pub geometry: Option<Vec<Location>>,
+
+    // Relation
+    pub members: Option<Vec<Member>>,
+}
+
+impl Element {
+    /// An Element of the given type, with none of the fields particular to a type filled in
+    pub fn new(id: i64, r#type: &str, tags: Tags) -> Self {
+        Self {
+            id,
+            r#type: r#type.to_owned(),
+            tags,
+            lat: None,
+            lon: None,
+            bounds: None,
+            nodes: None,
+            geometry: None,
+            members: None,
+        }
+    }
 }
 
 impl From<&Element> for Node {
@@ -89,7 +128,7 @@ where
         type Value = u128;
 
         fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-            formatter.write_str("a nonempty sequence of Node / Way objects")
+            formatter.write_str("a nonempty sequence of Node / Way / Relation objects")
         }
 
         fn visit_seq<S>(self, mut seq: S) -> Result<u128, S::Error>
@@ -124,6 +163,11 @@ where
                         )
                         .unwrap();
                     }
+                    "relation" => {
+                        // insert to TurnRestrictions table or label a bike route's Ways,
+                        // can we assume all Ways will appear before Relations?
+                        super::insert_relation_element(&tx, el).unwrap();
+                    }
                     other => panic!("unsupported type {}\nelement: {:?}", other, el),
                 }
             }
@@ -0,0 +1,119 @@
+/// Governs the ETL process from raw OSM extracts (`.osm.pbf` and `.osm` XML, e.g. Geofabrik's
+/// dumps) to SQLite, as an alternative to the Overpass export JSON
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use anyhow::bail;
+
+use super::{pbf, xml, Bounds, Element};
+use crate::osm::{Location, NodeId};
+
+/// Loads a raw OSM extract into the DB, through the same inserts as the Overpass JSON. Unlike the
+/// Overpass export, an extract holds every Way rather than just the routable ones, and lists
+/// Ways' Nodes only by id, so it's read twice: first to learn which Nodes the routable Ways use,
+/// then to look up those Nodes' coordinates on the way to inserting the Ways and Relations.
+/// Returns the number of Ways and Relations inserted.
+pub fn populate_from_extract(path: &Path) -> Result<u128, anyhow::Error> {
+    let mut needed: HashSet<NodeId> = HashSet::new();
+    read_extract(path, |element| {
+        if element.is_routable_way() {
+            needed.extend(element.nodes.unwrap_or_default());
+        }
+        Ok(())
+    })?;
+    println!("{} nodes referenced by routable ways", needed.len());
+
+    let mut conn = super::get_conn()?;
+    let tx = conn.transaction()?;
+
+    #[cfg(feature = "elevation")]
+    let elevation = std::env::var("ELEVATION_PATH")
+        .ok()
+        .map(|p| super::elevation::ElevationLookup::new(&p))
+        .transpose()?;
+
+    let mut coords: HashMap<NodeId, (f64, f64)> = HashMap::with_capacity(needed.len());
+    let mut count = 0;
+    read_extract(path, |element| {
+        match element.r#type.as_str() {
+            "node" if needed.contains(&element.id) => {
+                if let (Some(lon), Some(lat)) = (element.lon, element.lat) {
+                    coords.insert(element.id, (lon, lat));
+                }
+                super::insert_node_control(&tx, &element)?;
+            }
+            "way" if element.is_routable_way() => {
+                if let Some(way) = resolve_way(element, &coords) {
+                    super::insert_way_element(
+                        &tx,
+                        way,
+                        #[cfg(feature = "elevation")]
+                        elevation.as_ref(),
+                    )?;
+                    count += 1;
+                }
+            }
+            "relation" => {
+                super::insert_relation_element(&tx, element)?;
+                count += 1;
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+
+    tx.commit()?;
+
+    Ok(count)
+}
+
+/// Reads every element of the extract in order, by the format its extension names
+pub fn read_extract(
+    path: &Path,
+    visit: impl FnMut(Element) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    let name = path.to_string_lossy();
+    if name.ends_with(".pbf") {
+        pbf::read_pbf(path, visit)
+    } else if name.ends_with(".osm") || name.ends_with(".xml") {
+        xml::read_xml(path, visit)
+    } else {
+        bail!("unrecognized OSM extract format: {name}")
+    }
+}
+
+/// Fills in a Way's geometry and bounds from its Nodes' coordinates, as the Overpass export
+/// would have. Where the extract was clipped through the Way, leaving some of its Nodes out, only
+/// the longest run of Nodes that remain is kept.
+pub(super) fn resolve_way(
+    element: Element,
+    coords: &HashMap<NodeId, (f64, f64)>,
+) -> Option<Element> {
+    let node_ids = element.nodes.as_deref().unwrap_or_default();
+    let run = node_ids
+        .split(|id| !coords.contains_key(id))
+        .max_by_key(|run| run.len())
+        .filter(|run| run.len() >= 2)?;
+    if run.len() < node_ids.len() {
+        eprintln!(
+            "{}: {} of its {} nodes are missing from the extract",
+            element.id,
+            node_ids.len() - run.len(),
+            node_ids.len()
+        );
+    }
+
+    let geometry: Vec<Location> = run
+        .iter()
+        .map(|id| {
+            let (lon, lat) = coords[id];
+            Location { lat, lon }
+        })
+        .collect();
+    Some(Element {
+        bounds: Some(Bounds::from_geometry(&geometry)),
+        nodes: Some(run.to_vec()),
+        geometry: Some(geometry),
+        ..element
+    })
+}
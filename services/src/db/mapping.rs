@@ -1,5 +1,8 @@
 use super::Element;
-use crate::osm::{Cycleway, Road, Salmoning, WayId};
+use crate::osm::{
+    BikeNetwork, Control, Cycleway, Lts, Restriction, Road, Salmoning, Surface, TurnRestriction,
+    WayId,
+};
 
 /// Provides helper methods for interpreting and labeling the relevant OSM tags for bike routing
 #[derive(Debug)]
@@ -14,8 +17,30 @@ pub struct OSMMapper {
     pub cycleway_right_oneway: String,
     pub cycleway_left_oneway: String,
     pub oneway_bicycle: String,
+    pub surface: String,
+    pub smoothness: String,
+    pub tracktype: String,
+    pub maxspeed: String,
+    pub lanes: String,
+    pub lanes_forward: String,
+    pub lanes_backward: String,
+    pub parking: [String; 6],
+    pub opening_hours: String,
+    pub access: String,
+    pub access_conditional: String,
+    pub bicycle_conditional: String,
 }
 
+/// Tags for on-street parking, under both the older `parking:lane` scheme and its replacement
+const PARKING_TAGS: [&str; 6] = [
+    "parking:lane:both",
+    "parking:lane:left",
+    "parking:lane:right",
+    "parking:both",
+    "parking:left",
+    "parking:right",
+];
+
 /// Parses out the tags we want from this JSON map
 impl From<&Element> for OSMMapper {
     fn from(element: &Element) -> Self {
@@ -48,6 +73,36 @@ impl From<&Element> for OSMMapper {
             .cloned()
             .unwrap_or("none".to_owned());
 
+        let surface = tags.get("surface").cloned().unwrap_or("none".to_owned());
+        let smoothness = tags.get("smoothness").cloned().unwrap_or("none".to_owned());
+        let tracktype = tags.get("tracktype").cloned().unwrap_or("none".to_owned());
+
+        let maxspeed = tags.get("maxspeed").cloned().unwrap_or("none".to_owned());
+        let lanes = tags.get("lanes").cloned().unwrap_or("none".to_owned());
+        let lanes_forward = tags
+            .get("lanes:forward")
+            .cloned()
+            .unwrap_or("none".to_owned());
+        let lanes_backward = tags
+            .get("lanes:backward")
+            .cloned()
+            .unwrap_or("none".to_owned());
+        let parking = PARKING_TAGS.map(|tag| tags.get(tag).cloned().unwrap_or("none".to_owned()));
+
+        let opening_hours = tags
+            .get("opening_hours")
+            .cloned()
+            .unwrap_or("none".to_owned());
+        let access = tags.get("access").cloned().unwrap_or("none".to_owned());
+        let access_conditional = tags
+            .get("access:conditional")
+            .cloned()
+            .unwrap_or("none".to_owned());
+        let bicycle_conditional = tags
+            .get("bicycle:conditional")
+            .cloned()
+            .unwrap_or("none".to_owned());
+
         OSMMapper {
             way: element.id,
             highway,
@@ -59,6 +114,18 @@ impl From<&Element> for OSMMapper {
             cycleway_right_oneway,
             cycleway_left_oneway,
             oneway_bicycle,
+            surface,
+            smoothness,
+            tracktype,
+            maxspeed,
+            lanes,
+            lanes_forward,
+            lanes_backward,
+            parking,
+            opening_hours,
+            access,
+            access_conditional,
+            bicycle_conditional,
         }
     }
 }
@@ -81,6 +148,203 @@ impl OSMMapper {
         }
     }
 
+    /// Given these OSM tags, calculate surface label. `smoothness` says directly how a Way rides,
+    /// so wins out over what it's made of; `tracktype` is a last resort for tracks tagged with
+    /// neither. Untagged tracks are taken to be unpaved, and anything else untagged to be paved.
+    pub fn get_surface_label(&self) -> Surface {
+        match self.smoothness.as_str() {
+            "excellent" | "good" => return Surface::Paved,
+            "intermediate" => return Surface::Rough,
+            "bad" => return Surface::Cobbled,
+            "very_bad" | "horrible" | "very_horrible" | "impassable" => return Surface::Unpaved,
+            "none" => {}
+            other => eprintln!("{}: Unexpected smoothness value: {other}", self.way),
+        }
+
+        match self.surface.as_str() {
+            "asphalt" | "concrete" | "paved" | "concrete:lanes" | "chipseal" => {
+                return Surface::Paved
+            }
+            "paving_stones" | "concrete:plates" | "bricks" | "wood" | "metal" | "compacted"
+            | "fine_gravel" | "rubber" | "tartan" => return Surface::Rough,
+            "sett" | "cobblestone" | "unhewn_cobblestone" | "cobblestone:flattened" => {
+                return Surface::Cobbled
+            }
+            "unpaved" | "gravel" | "pebblestone" | "dirt" | "earth" | "ground" | "grass"
+            | "sand" | "mud" | "woodchips" | "rock" | "stone" => return Surface::Unpaved,
+            "none" => {}
+            other => eprintln!("{}: Unexpected surface value: {other}", self.way),
+        }
+
+        match self.tracktype.as_str() {
+            "grade1" => Surface::Paved,
+            "grade2" => Surface::Rough,
+            "grade3" | "grade4" | "grade5" => Surface::Unpaved,
+            _ if self.highway == "track" => Surface::Unpaved,
+            _ => Surface::Paved,
+        }
+    }
+
+    /// Given these OSM tags and the labels already taken from them, calculate the Level of Traffic
+    /// Stress of riding one direction of the Way, by the criteria of Mekuria, Furth & Nixon (2012):
+    /// - paths and separated bikeways are LTS 1
+    /// - bike lanes are rated on through lanes per direction and speed limit, more strictly when
+    ///   running alongside parked cars
+    /// - everything else is mixed traffic, rated on total lanes and speed limit, with residential
+    ///   streets a level lower
+    ///
+    /// Untagged speed limits and lane counts are assumed from the road's class, and parking is
+    /// assumed to line any street not tagged otherwise, as it does most of NYC's
+    pub fn get_lts(&self, road: Road, cycleway: Cycleway, forward: bool) -> Lts {
+        if matches!(road, Road::Pedestrian | Road::Bike) || cycleway == Cycleway::Track {
+            return Lts::Lts1;
+        }
+
+        let speed = self.get_speed_limit(road);
+        let oneway = self.oneway == "yes";
+        let total_lanes = self.get_lanes(road, oneway);
+        let directional_lanes = if forward {
+            &self.lanes_forward
+        } else {
+            &self.lanes_backward
+        };
+        let lanes_per_direction = directional_lanes.parse::<u32>().unwrap_or(if oneway {
+            total_lanes
+        } else {
+            total_lanes.div_ceil(2)
+        });
+
+        if cycleway == Cycleway::Lane {
+            let (by_lanes, by_speed) = if self.has_parking() {
+                (
+                    if lanes_per_direction <= 1 {
+                        Lts::Lts1
+                    } else {
+                        Lts::Lts3
+                    },
+                    match speed {
+                        ..=25 => Lts::Lts1,
+                        26..=30 => Lts::Lts2,
+                        31..=35 => Lts::Lts3,
+                        _ => Lts::Lts4,
+                    },
+                )
+            } else {
+                (
+                    // medians aren't tagged, so only oneway streets count as having their
+                    // directions separated
+                    match lanes_per_direction {
+                        ..=1 => Lts::Lts1,
+                        2 if oneway => Lts::Lts2,
+                        _ => Lts::Lts3,
+                    },
+                    match speed {
+                        ..=30 => Lts::Lts1,
+                        31..=35 => Lts::Lts3,
+                        _ => Lts::Lts4,
+                    },
+                )
+            };
+            return by_lanes.max(by_speed);
+        }
+
+        // sharrows are mixed traffic all the same
+        let residential = matches!(self.highway.as_str(), "residential" | "living_street");
+        match (speed, total_lanes) {
+            (..=25, ..=3) if residential => Lts::Lts1,
+            (..=25, ..=3) => Lts::Lts2,
+            (26..=30, ..=3) if residential => Lts::Lts2,
+            (26..=30, ..=3) | (..=25, 4..=5) => Lts::Lts3,
+            _ => Lts::Lts4,
+        }
+    }
+
+    /// The speed limit in mph, from `maxspeed` in km/h (as is OSM's default) or mph, else assumed
+    /// from the road's class
+    fn get_speed_limit(&self, road: Road) -> u32 {
+        let maxspeed = self.maxspeed.trim();
+        let parsed = match maxspeed.strip_suffix("mph") {
+            Some(mph) => mph.trim().parse::<f64>().ok(),
+            None => maxspeed
+                .trim_end_matches("km/h")
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .map(|kmh| kmh / 1.609),
+        };
+        match parsed {
+            Some(mph) => mph.round() as u32,
+            None => {
+                if maxspeed != "none" {
+                    eprintln!("{}: Unexpected maxspeed value: {maxspeed}", self.way);
+                }
+                match road {
+                    Road::Arterial => 35,
+                    Road::Collector => 30,
+                    _ => 25,
+                }
+            }
+        }
+    }
+
+    /// The number of through lanes in both directions, from `lanes`, else assumed from the road's
+    /// class
+    fn get_lanes(&self, road: Road, oneway: bool) -> u32 {
+        self.lanes.parse().unwrap_or(match (road, oneway) {
+            (_, true) => 1,
+            (Road::Arterial, false) => 4,
+            _ => 2,
+        })
+    }
+
+    /// Whether cars park along either side of the Way. Bike lanes on oneway streets may run along
+    /// either curb, so parking on one side is taken to line them either way.
+    fn has_parking(&self) -> bool {
+        let tagged: Vec<&str> = self
+            .parking
+            .iter()
+            .map(String::as_str)
+            .filter(|value| *value != "none")
+            .collect();
+        tagged.is_empty()
+            || tagged.iter().any(|value| {
+                !matches!(
+                    *value,
+                    "no" | "no_parking" | "no_stopping" | "separate" | "fire_lane"
+                )
+            })
+    }
+
+    /// Given these OSM tags, get the schedules on which the Way opens or closes. Conditions are
+    /// listed after the base access values they override while in effect.
+    pub fn get_restrictions(&self) -> Vec<Restriction> {
+        let scheduled: Vec<Restriction> = [
+            ("opening_hours", &self.opening_hours),
+            ("access:conditional", &self.access_conditional),
+            ("bicycle:conditional", &self.bicycle_conditional),
+        ]
+        .into_iter()
+        .filter(|(_, value)| value.as_str() != "none")
+        .flat_map(|(tag, value)| {
+            Restriction::parse(tag, value).unwrap_or_else(|e| {
+                eprintln!("{}: Unexpected {tag} value: {value} ({e})", self.way);
+                vec![]
+            })
+        })
+        .collect();
+        if scheduled.is_empty() {
+            return scheduled;
+        }
+
+        // the base values only matter against a schedule, and ones which aren't plainly yes or
+        // no (e.g. `bicycle=dismount`) are left to the conditions and opening hours
+        [("access", &self.access), ("bicycle", &self.bicycle)]
+            .into_iter()
+            .filter_map(|(tag, value)| Restriction::parse_base(tag, value).ok())
+            .chain(scheduled)
+            .collect()
+    }
+
     /// Given these OSM tags, get the forward and reverse cycleways and directionality
     // opted to make this a mega function, since the logic for
     // determining these 3 was always coupled
@@ -244,3 +508,169 @@ impl OSMMapper {
         }
     }
 }
+
+/// highway values left out by the Overpass query in `download_osm_data.sh`, as none of them can be
+/// ridden (or are even roads at all)
+const EXCLUDED_HIGHWAYS: [&str; 16] = [
+    "footway",
+    "motorway",
+    "motorway_link",
+    "trunk",
+    "trunk_link",
+    "bridleway",
+    "raceway",
+    "services",
+    "rest_area",
+    "construction",
+    "steps",
+    "street_lamp",
+    "elevator",
+    "bus_stop",
+    "platform",
+    "proposed",
+];
+
+/// A signed bike route, from an OSM `route=bicycle` relation
+#[derive(Debug)]
+pub struct BikeRoute {
+    pub network: BikeNetwork,
+    pub name: String,
+    /// the directed Ways along the route: both directions of each member Way, unless its role
+    /// limits the route to one of them
+    pub ways: Vec<WayId>,
+}
+
+impl Element {
+    /// Whether this is a Way worth routing over, by the same tags the Overpass query selects
+    /// Ways by. Raw extracts hold every Way, so have to be filtered down to these while loading.
+    pub fn is_routable_way(&self) -> bool {
+        let tags = &self.tags;
+        self.r#type == "way"
+            && tags
+                .get("highway")
+                .is_some_and(|highway| !EXCLUDED_HIGHWAYS.contains(&highway.as_str()))
+            && !tags.contains_key("footway")
+            && tags.get("bicycle").map(String::as_str) != Some("no")
+    }
+
+    /// Given a Node's tags, get the traffic control device at it. Signals win out over any other
+    /// tag, as a signalized crosswalk is stopped at all the same.
+    pub fn get_control(&self) -> Control {
+        let tags = &self.tags;
+        let highway = tags.get("highway").map(String::as_str);
+        let crossing = tags.get("crossing").map(String::as_str);
+        match (highway, crossing) {
+            (Some("traffic_signals"), _) | (_, Some("traffic_signals")) => Control::Signals,
+            _ if tags.get("crossing:signals").map(String::as_str) == Some("yes") => {
+                Control::Signals
+            }
+            (Some("stop"), _) => Control::Stop,
+            (_, Some("no")) => Control::None,
+            (Some("crossing"), _) | (_, Some(_)) => Control::Crossing,
+            _ => Control::None,
+        }
+    }
+
+    /// Given a relation's tags and members, get the bike route it describes, if it is one
+    pub fn get_bike_route(&self) -> Option<BikeRoute> {
+        let tags = &self.tags;
+        if tags.get("type").map(String::as_str) != Some("route")
+            || tags.get("route").map(String::as_str) != Some("bicycle")
+        {
+            return None;
+        }
+
+        let network = match tags.get("network").map(String::as_str) {
+            Some("icn") | Some("ncn") => BikeNetwork::National,
+            Some("rcn") => BikeNetwork::Regional,
+            Some("lcn") | None => BikeNetwork::Local,
+            Some(other) => {
+                eprintln!("{}: Unexpected network value: {other}", self.id);
+                BikeNetwork::Local
+            }
+        };
+        let name = tags
+            .get("name")
+            .or(tags.get("ref"))
+            .cloned()
+            .unwrap_or_default();
+
+        let ways = self
+            .members
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .filter(|m| m.r#type == "way")
+            .flat_map(|m| match m.role.as_str() {
+                "forward" => vec![m.r#ref],
+                "backward" => vec![-m.r#ref],
+                _ => vec![m.r#ref, -m.r#ref],
+            })
+            .collect();
+
+        Some(BikeRoute {
+            network,
+            name,
+            ways,
+        })
+    }
+
+    /// Given a relation's tags and members, get the turn restrictions it places on bicycles: those
+    /// of a `restriction:bicycle` tag, or else of a `restriction` tag not excepting bicycles
+    pub fn get_turn_restrictions(&self) -> Vec<TurnRestriction> {
+        let tags = &self.tags;
+        if tags.get("type").map(String::as_str) != Some("restriction") {
+            return vec![];
+        }
+
+        let excepted = tags
+            .get("except")
+            .is_some_and(|except| except.split(';').any(|mode| mode.trim() == "bicycle"));
+        let value = match (tags.get("restriction:bicycle"), tags.get("restriction")) {
+            (Some(value), _) => value,
+            (None, Some(value)) if !excepted => value,
+            _ => return vec![],
+        };
+        let only = match TurnRestriction::parse_kind(value) {
+            Ok(only) => only,
+            Err(e) => {
+                eprintln!("{}: Unexpected restriction value: {value} ({e})", self.id);
+                return vec![];
+            }
+        };
+
+        let members = self.members.as_deref().unwrap_or_default();
+        let ways = |role: &str| {
+            members
+                .iter()
+                .filter(|m| m.r#type == "way" && m.role == role)
+                .map(|m| m.r#ref)
+                .collect::<Vec<_>>()
+        };
+        let (from, to) = (ways("from"), ways("to"));
+        let via = match members
+            .iter()
+            .filter(|m| m.role == "via")
+            .collect::<Vec<_>>()
+            .as_slice()
+        {
+            [m] if m.r#type == "node" => m.r#ref,
+            _ => {
+                // restrictions via Ways would need the route's history beyond its last turn
+                eprintln!("{}: Unsupported restriction via members", self.id);
+                return vec![];
+            }
+        };
+
+        from.iter()
+            .flat_map(|from| {
+                to.iter().map(|to| TurnRestriction {
+                    from: *from,
+                    via,
+                    to: *to,
+                    only,
+                })
+            })
+            .collect()
+    }
+}
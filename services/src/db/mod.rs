@@ -1,8 +1,14 @@
+mod changes;
 mod core;
 pub mod elevation;
 mod etl;
+mod extract;
 mod mapping;
+mod pbf;
+mod xml;
 
+pub use changes::*;
 pub use core::*;
 pub use etl::*;
+pub use extract::*;
 pub use mapping::*;
@@ -0,0 +1,314 @@
+/// Reads OSM PBF extracts, such as Geofabrik's `.osm.pbf` dumps. As laid out in
+/// https://wiki.openstreetmap.org/wiki/PBF_Format, the file is a sequence of length-prefixed
+/// blobs, each a (usually zlib-compressed) block of Nodes, Ways or Relations whose strings are
+/// interned in a table per block. Only the fields routing needs are decoded.
+use anyhow::{anyhow, bail, Context};
+use flate2::read::ZlibDecoder;
+use prost::Message;
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read};
+use std::path::Path;
+
+use super::{Element, Member, Tags};
+
+/// the features a file may require of its reader which this one has
+const SUPPORTED_FEATURES: [&str; 2] = ["OsmSchema-V0.6", "DenseNodes"];
+
+/// blobs are capped at 32MB by the format
+const MAX_BLOB_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Clone, PartialEq, Message)]
+struct BlobHeader {
+    #[prost(string, required, tag = "1")]
+    r#type: String,
+    #[prost(int32, required, tag = "3")]
+    datasize: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Blob {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    raw: Option<Vec<u8>>,
+    #[prost(int32, optional, tag = "2")]
+    raw_size: Option<i32>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    zlib_data: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct HeaderBlock {
+    #[prost(string, repeated, tag = "4")]
+    required_features: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PrimitiveBlock {
+    #[prost(message, required, tag = "1")]
+    stringtable: StringTable,
+    #[prost(message, repeated, tag = "2")]
+    primitivegroup: Vec<PrimitiveGroup>,
+    /// in nanodegrees
+    #[prost(int32, optional, tag = "17", default = "100")]
+    granularity: Option<i32>,
+    #[prost(int64, optional, tag = "19", default = "0")]
+    lat_offset: Option<i64>,
+    #[prost(int64, optional, tag = "20", default = "0")]
+    lon_offset: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct StringTable {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    s: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PrimitiveGroup {
+    #[prost(message, repeated, tag = "1")]
+    nodes: Vec<PbfNode>,
+    #[prost(message, optional, tag = "2")]
+    dense: Option<DenseNodes>,
+    #[prost(message, repeated, tag = "3")]
+    ways: Vec<PbfWay>,
+    #[prost(message, repeated, tag = "4")]
+    relations: Vec<PbfRelation>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbfNode {
+    #[prost(sint64, required, tag = "1")]
+    id: i64,
+    #[prost(uint32, repeated, tag = "2")]
+    keys: Vec<u32>,
+    #[prost(uint32, repeated, tag = "3")]
+    vals: Vec<u32>,
+    #[prost(sint64, required, tag = "8")]
+    lat: i64,
+    #[prost(sint64, required, tag = "9")]
+    lon: i64,
+}
+
+/// Nodes packed column-wise, with ids and coordinates delta-encoded
+#[derive(Clone, PartialEq, Message)]
+struct DenseNodes {
+    #[prost(sint64, repeated, tag = "1")]
+    id: Vec<i64>,
+    #[prost(sint64, repeated, tag = "8")]
+    lat: Vec<i64>,
+    #[prost(sint64, repeated, tag = "9")]
+    lon: Vec<i64>,
+    /// each Node's (key, value) string indices in turn, ending with a 0
+    #[prost(int32, repeated, tag = "10")]
+    keys_vals: Vec<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbfWay {
+    #[prost(int64, required, tag = "1")]
+    id: i64,
+    #[prost(uint32, repeated, tag = "2")]
+    keys: Vec<u32>,
+    #[prost(uint32, repeated, tag = "3")]
+    vals: Vec<u32>,
+    /// delta-encoded
+    #[prost(sint64, repeated, tag = "8")]
+    refs: Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct PbfRelation {
+    #[prost(int64, required, tag = "1")]
+    id: i64,
+    #[prost(uint32, repeated, tag = "2")]
+    keys: Vec<u32>,
+    #[prost(uint32, repeated, tag = "3")]
+    vals: Vec<u32>,
+    #[prost(int32, repeated, tag = "8")]
+    roles_sid: Vec<i32>,
+    /// delta-encoded
+    #[prost(sint64, repeated, tag = "9")]
+    memids: Vec<i64>,
+    /// 0 = Node, 1 = Way, 2 = Relation
+    #[prost(int32, repeated, tag = "10")]
+    types: Vec<i32>,
+}
+
+/// Reads every Node, Way and Relation out of the PBF file in order, handing each to `visit`.
+/// Ways come with only their Node ids, and Relations with their members.
+pub(super) fn read_pbf(
+    path: &Path,
+    mut visit: impl FnMut(Element) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    while let Some(header) = read_blob_header(&mut reader)? {
+        let size = header.datasize as usize;
+        if size > MAX_BLOB_SIZE {
+            bail!(
+                "{} blob of {size} bytes is larger than allowed",
+                header.r#type
+            );
+        }
+        let mut buf = vec![0; size];
+        reader.read_exact(&mut buf)?;
+        let data = decompress(Blob::decode(buf.as_slice())?)?;
+
+        match header.r#type.as_str() {
+            "OSMHeader" => {
+                let header = HeaderBlock::decode(data.as_slice())?;
+                if let Some(feature) = header
+                    .required_features
+                    .iter()
+                    .find(|f| !SUPPORTED_FEATURES.contains(&f.as_str()))
+                {
+                    bail!("unsupported PBF feature {feature}");
+                }
+            }
+            "OSMData" => visit_block(PrimitiveBlock::decode(data.as_slice())?, &mut visit)?,
+            // unknown blobs are to be skipped
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the next blob's header, or None at the end of the file
+fn read_blob_header(reader: &mut impl Read) -> Result<Option<BlobHeader>, anyhow::Error> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        result => result?,
+    }
+    let mut buf = vec![0; u32::from_be_bytes(len) as usize];
+    reader.read_exact(&mut buf)?;
+    Ok(Some(BlobHeader::decode(buf.as_slice())?))
+}
+
+fn decompress(blob: Blob) -> Result<Vec<u8>, anyhow::Error> {
+    if let Some(raw) = blob.raw {
+        return Ok(raw);
+    }
+    let zlib_data = blob
+        .zlib_data
+        .ok_or_else(|| anyhow!("unsupported PBF blob compression"))?;
+    let mut data = Vec::with_capacity(blob.raw_size.unwrap_or_default() as usize);
+    ZlibDecoder::new(zlib_data.as_slice()).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn visit_block(
+    block: PrimitiveBlock,
+    visit: &mut impl FnMut(Element) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    let strings: Vec<String> = block
+        .stringtable
+        .s
+        .iter()
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect();
+    let string = |idx: u32| {
+        strings
+            .get(idx as usize)
+            .cloned()
+            .with_context(|| format!("string {idx} missing from the block's table"))
+    };
+    let tags = |keys: &[u32], vals: &[u32]| -> Result<Tags, anyhow::Error> {
+        keys.iter()
+            .zip(vals)
+            .map(|(k, v)| Ok((string(*k)?, string(*v)?)))
+            .collect()
+    };
+
+    let granularity = block.granularity() as i64;
+    let (lat_offset, lon_offset) = (block.lat_offset(), block.lon_offset());
+    let degrees = |offset: i64, value: i64| 1e-9 * (offset + granularity * value) as f64;
+    let node = |id: i64, lat: i64, lon: i64, tags: Tags| Element {
+        lat: Some(degrees(lat_offset, lat)),
+        lon: Some(degrees(lon_offset, lon)),
+        ..Element::new(id, "node", tags)
+    };
+
+    for group in block.primitivegroup {
+        for n in group.nodes {
+            visit(node(n.id, n.lat, n.lon, tags(&n.keys, &n.vals)?))?;
+        }
+
+        if let Some(dense) = group.dense {
+            if dense.lat.len() != dense.id.len() || dense.lon.len() != dense.id.len() {
+                bail!(
+                    "dense nodes have {} ids but {} latitudes and {} longitudes",
+                    dense.id.len(),
+                    dense.lat.len(),
+                    dense.lon.len()
+                );
+            }
+            let (mut id, mut lat, mut lon) = (0, 0, 0);
+            let mut keys_vals = dense.keys_vals.iter();
+            for ((id_delta, lat_delta), lon_delta) in
+                dense.id.iter().zip(&dense.lat).zip(&dense.lon)
+            {
+                id += id_delta;
+                lat += lat_delta;
+                lon += lon_delta;
+
+                let mut node_tags = Tags::new();
+                while let Some(&key) = keys_vals.next() {
+                    if key == 0 {
+                        break;
+                    }
+                    let value = keys_vals
+                        .next()
+                        .ok_or_else(|| anyhow!("Node {id} has a tag key without a value"))?;
+                    node_tags.insert(string(key as u32)?, string(*value as u32)?);
+                }
+                visit(node(id, lat, lon, node_tags))?;
+            }
+        }
+
+        for w in group.ways {
+            let mut node_id = 0;
+            let nodes = w
+                .refs
+                .iter()
+                .map(|delta| {
+                    node_id += delta;
+                    node_id
+                })
+                .collect();
+            visit(Element {
+                nodes: Some(nodes),
+                ..Element::new(w.id, "way", tags(&w.keys, &w.vals)?)
+            })?;
+        }
+
+        for r in group.relations {
+            let mut member_id = 0;
+            let members = r
+                .memids
+                .iter()
+                .zip(&r.types)
+                .zip(&r.roles_sid)
+                .map(|((delta, r#type), role)| {
+                    member_id += delta;
+                    let r#type = match r#type {
+                        0 => "node",
+                        1 => "way",
+                        _ => "relation",
+                    };
+                    Ok(Member {
+                        r#type: r#type.to_owned(),
+                        r#ref: member_id,
+                        role: string(*role as u32)?,
+                    })
+                })
+                .collect::<Result<_, anyhow::Error>>()?;
+            visit(Element {
+                members: Some(members),
+                ..Element::new(r.id, "relation", tags(&r.keys, &r.vals)?)
+            })?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+/// Reads OSM XML extracts (`.osm`), as exported by the OSM website, JOSM or osmium, and osmChange
+/// files (`.osc`, optionally gzipped), as published by the OSM replication feeds
+use anyhow::{anyhow, Context};
+use flate2::read::GzDecoder;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use super::{ChangeAction, Element, Member, Tags};
+
+/// Reads every Node, Way and Relation out of the XML file in order, handing each to `visit`.
+/// Ways come with only their Node ids, and Relations with their members.
+pub(super) fn read_xml(
+    path: &Path,
+    mut visit: impl FnMut(Element) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    read_elements(BufReader::new(File::open(path)?), |_, element| {
+        visit(element)
+    })
+}
+
+/// Reads every created, modified and deleted Node, Way and Relation out of the osmChange file in
+/// order, handing each to `visit` with what was done to it. Created and modified elements come in
+/// full, as in an extract; deleted ones may come with only their ids.
+pub(super) fn read_osc(
+    path: &Path,
+    mut visit: impl FnMut(ChangeAction, Element) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    let file = File::open(path)?;
+    let visit = |action: Option<ChangeAction>, element: Element| {
+        let action = action.ok_or_else(|| {
+            anyhow!(
+                "{} {} is outside of a create, modify or delete",
+                element.r#type,
+                element.id
+            )
+        })?;
+        visit(action, element)
+    };
+    if path.to_string_lossy().ends_with(".gz") {
+        read_elements(BufReader::new(GzDecoder::new(file)), visit)
+    } else {
+        read_elements(BufReader::new(file), visit)
+    }
+}
+
+/// Reads every element out of OSM XML, along with the osmChange action it's nested in, if any
+fn read_elements(
+    reader: impl BufRead,
+    mut visit: impl FnMut(Option<ChangeAction>, Element) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = vec![];
+    let mut action: Option<ChangeAction> = None;
+    // the element whose children are being read
+    let mut current: Option<Element> = None;
+
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => {
+                if let Some(element) = start_element(&e)? {
+                    current = Some(element);
+                } else if let Some(element) = current.as_mut() {
+                    add_child(element, &e)?;
+                } else {
+                    action = start_action(&e).or(action);
+                }
+            }
+            Event::Empty(e) => {
+                // e.g. an untagged Node
+                if let Some(element) = start_element(&e)? {
+                    visit(action, element)?;
+                } else if let Some(element) = current.as_mut() {
+                    add_child(element, &e)?;
+                }
+            }
+            Event::End(e) => match e.name().as_ref() {
+                b"node" | b"way" | b"relation" => {
+                    if let Some(element) = current.take() {
+                        visit(action, element)?;
+                    }
+                }
+                b"create" | b"modify" | b"delete" => action = None,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(())
+}
+
+/// The osmChange action a `<create>`, `<modify>` or `<delete>` tag begins, or None for any other
+fn start_action(e: &BytesStart) -> Option<ChangeAction> {
+    match e.name().as_ref() {
+        b"create" => Some(ChangeAction::Create),
+        b"modify" => Some(ChangeAction::Modify),
+        b"delete" => Some(ChangeAction::Delete),
+        _ => None,
+    }
+}
+
+/// Begins an Element from a `<node>`, `<way>` or `<relation>` tag, or None for any other tag
+fn start_element(e: &BytesStart) -> Result<Option<Element>, anyhow::Error> {
+    let element = match e.name().as_ref() {
+        // deleted Nodes may be missing their coordinates
+        b"node" => Element {
+            lat: optional_attribute(e, "lat")?
+                .map(|a| a.parse())
+                .transpose()?,
+            lon: optional_attribute(e, "lon")?
+                .map(|a| a.parse())
+                .transpose()?,
+            ..Element::new(attribute(e, "id")?.parse()?, "node", Tags::new())
+        },
+        b"way" => Element {
+            nodes: Some(vec![]),
+            ..Element::new(attribute(e, "id")?.parse()?, "way", Tags::new())
+        },
+        b"relation" => Element {
+            members: Some(vec![]),
+            ..Element::new(attribute(e, "id")?.parse()?, "relation", Tags::new())
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(element))
+}
+
+/// Adds a `<tag>`, `<nd>` or `<member>` to the Element it's nested in
+fn add_child(element: &mut Element, e: &BytesStart) -> Result<(), anyhow::Error> {
+    match e.name().as_ref() {
+        b"tag" => {
+            element.tags.insert(attribute(e, "k")?, attribute(e, "v")?);
+        }
+        b"nd" => {
+            if let Some(nodes) = element.nodes.as_mut() {
+                nodes.push(attribute(e, "ref")?.parse()?);
+            }
+        }
+        b"member" => {
+            if let Some(members) = element.members.as_mut() {
+                members.push(Member {
+                    r#type: attribute(e, "type")?,
+                    r#ref: attribute(e, "ref")?.parse()?,
+                    role: attribute(e, "role")?,
+                });
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<String, anyhow::Error> {
+    optional_attribute(e, name)?.ok_or_else(|| {
+        anyhow!(
+            "<{}> is missing {name}",
+            String::from_utf8_lossy(e.name().as_ref())
+        )
+    })
+}
+
+fn optional_attribute(e: &BytesStart, name: &str) -> Result<Option<String>, anyhow::Error> {
+    let Some(attribute) = e.try_get_attribute(name)? else {
+        return Ok(None);
+    };
+    Ok(Some(
+        attribute
+            .unescape_value()
+            .with_context(|| format!("unreadable {name}"))?
+            .into_owned(),
+    ))
+}
@@ -0,0 +1,183 @@
+/// Alternative routes, found with the iterative penalty method: after each search, the segments
+/// along the path it found are made more expensive, nudging the next search onto different
+/// streets. Candidates are kept only if they are distinct enough from (and not too much worse
+/// than) the routes already chosen.
+use super::core::{leg_total_cost, trace_back};
+use super::traversal::{Penalties, Route, Traversable, Waypoint, END_NODE_ID};
+use super::{Cost, CostModel, Graph, RouteMetadata, SegmentKey, Weight};
+use crate::osm::{Distance, Node, NodeId};
+use geo::Point;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// multiplier applied to a segment's cost each time it appears in a found path
+const PENALTY_FACTOR: Cost = 1.5;
+/// alternatives more than this many times the cost of the best route are discarded
+pub const MAX_STRETCH: Cost = 1.5;
+/// alternatives sharing more than this fraction of their distance with a chosen route are discarded
+pub const MAX_OVERLAP: f32 = 0.7;
+/// penalized searches to attempt per requested route before giving up
+const ATTEMPTS_PER_ROUTE: usize = 4;
+
+#[derive(Debug, Serialize)]
+pub struct Alternative {
+    pub route: Route,
+    pub meta: RouteMetadata,
+    /// cost of the route, without any of the penalties used to find it
+    pub cost: Cost,
+    /// fraction of this route's distance which is shared with the routes before it
+    pub overlap: f32,
+}
+
+impl Graph {
+    /// Calculates up to `k` distinct, loopless routes between the start and end points, with the
+    /// best route first
+    pub fn calculate_alternatives(
+        &self,
+        start: Point,
+        end: Point,
+        k: usize,
+        cost_model: Option<CostModel>,
+        heuristic_weight: Option<Weight>,
+    ) -> Result<Vec<Alternative>, anyhow::Error> {
+        let cost_model = self.prepare_cost_model(cost_model)?;
+        let start = self.snap_waypoint(start)?;
+        let end = self.snap_waypoint(end)?;
+
+        let mut alternatives: Vec<Alternative> = vec![];
+        let mut chosen_segments: HashSet<(NodeId, NodeId)> = HashSet::new();
+        let mut seen_paths: HashSet<Vec<SegmentKey>> = HashSet::new();
+        let mut penalties = Penalties::new();
+
+        for _ in 0..k.saturating_mul(ATTEMPTS_PER_ROUTE) {
+            if alternatives.len() >= k {
+                break;
+            }
+
+            let (penalized, meta) = match self.route_penalized(
+                &start,
+                &end,
+                cost_model.clone(),
+                heuristic_weight,
+                penalties,
+            ) {
+                Ok((route, meta, returned)) => {
+                    penalties = returned;
+                    (route, meta)
+                }
+                // the very first search failing means there's no route at all
+                Err(e) if alternatives.is_empty() => return Err(e),
+                Err(_) => break,
+            };
+
+            let path = inner_path(&penalized);
+            penalize(&mut penalties, &path, PENALTY_FACTOR);
+            if !seen_paths.insert(path.clone()) {
+                continue;
+            }
+            // edge-based searches (under turn costs) may pass through a Node more than once
+            if !is_loopless(&path) {
+                continue;
+            }
+
+            // recost the path as it would be without penalties
+            let route = self.rebuild_route(&start, &end, penalized[0].to.id, &path, &cost_model)?;
+            let cost = leg_total_cost(&route);
+
+            if let Some(best) = alternatives.first() {
+                if cost > best.cost * MAX_STRETCH {
+                    continue;
+                }
+            }
+
+            let overlap = overlap_ratio(&route, &chosen_segments);
+            if overlap > MAX_OVERLAP {
+                continue;
+            }
+
+            chosen_segments.extend(path.iter().map(|(n1, n2, _)| (*n1, *n2)));
+            alternatives.push(Alternative {
+                route,
+                meta,
+                cost,
+                overlap,
+            });
+        }
+
+        Ok(alternatives)
+    }
+
+    /// A* search between two snapped points under the given penalties, which are handed back
+    /// along with the Route so they can be built upon for the next search
+    pub(super) fn route_penalized(
+        &self,
+        start: &Waypoint,
+        end: &Waypoint,
+        cost_model: CostModel,
+        heuristic_weight: Option<Weight>,
+        penalties: Penalties,
+    ) -> Result<(Route, RouteMetadata, Penalties), anyhow::Error> {
+        let end_node = Node::new(END_NODE_ID, &end.point);
+
+        let mut context = self.initialize_traversal(start, cost_model, heuristic_weight)?;
+        context.penalties = penalties;
+        // penalties only ever raise costs, so landmark bounds stay admissible under them
+        self.apply_landmarks(&mut context, &end.arrivals);
+
+        self.traverse_between(&mut context, &end.arrivals, &end_node)
+            .map_err(|e| self.expand_partial(e))?;
+
+        // penalties are keyed by the Segments along the route, not the edges compacted from them
+        let route = self.expand_route(&trace_back(&context, END_NODE_ID))?;
+
+        let meta = RouteMetadata {
+            max_depth: context.max_depth,
+            cost_range: context.cost_range,
+            heuristic: context.heuristic_kind(),
+            signals: self.count_signals(&route)?,
+        };
+        Ok((route, meta, context.penalties))
+    }
+}
+
+/// The real graph Segments along a route, leaving off the virtual start and end segments
+pub(super) fn inner_path(route: &Route) -> Vec<SegmentKey> {
+    route[1..route.len() - 1]
+        .iter()
+        .map(|s| (s.from.id, s.to.id, s.way))
+        .collect()
+}
+
+/// Whether the path visits each of its Nodes only once
+fn is_loopless(path: &[SegmentKey]) -> bool {
+    let mut visited: HashSet<NodeId> = HashSet::with_capacity(path.len() + 1);
+    path.first().is_none_or(|(n1, _, _)| visited.insert(*n1))
+        && path.iter().all(|(_, n2, _)| visited.insert(*n2))
+}
+
+/// Compounds the penalty on each segment of the path, in both directions
+pub(super) fn penalize(penalties: &mut Penalties, path: &[SegmentKey], factor: Cost) {
+    for (n1, n2, _) in path {
+        for key in [(*n1, *n2), (*n2, *n1)] {
+            *penalties.entry(key).or_insert(1.0) *= factor;
+        }
+    }
+}
+
+/// The fraction of the route's distance covered by segments already in `chosen`, in either direction
+fn overlap_ratio(route: &Route, chosen: &HashSet<(NodeId, NodeId)>) -> f32 {
+    let total: Distance = route.iter().map(|s| s.length).sum();
+    if total == 0 {
+        return 0.0;
+    }
+
+    let shared: Distance = route
+        .iter()
+        .filter(|s| {
+            chosen.contains(&(s.from.id, s.to.id)) || chosen.contains(&(s.to.id, s.from.id))
+        })
+        .map(|s| s.length)
+        .sum();
+
+    shared as f32 / total as f32
+}
@@ -0,0 +1,147 @@
+/// Limits on how much work a search may do before giving up. When the target can't be reached
+/// at all (an island, a missing bridge), a search would otherwise drain the entire graph before
+/// reporting failure, which on a Lambda can burn through the whole request timeout.
+use super::Cost;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// how many expansions pass between checks of the clock and cancellation token
+const CHECK_INTERVAL: usize = 256;
+
+/// Limits applied to each search, all unlimited by default
+#[derive(Debug, Clone, Default)]
+pub struct SearchBudget {
+    /// most Nodes (or edges, in edge-based searches) to expand
+    pub max_expanded: Option<usize>,
+    /// greatest accumulated cost to expand out to
+    pub max_cost: Option<Cost>,
+    /// wall-clock time each search may run for, from when it's initialized
+    pub timeout: Option<Duration>,
+    /// checked periodically, so that a search can be stopped from elsewhere
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl SearchBudget {
+    pub fn with_max_expanded(mut self, max_expanded: usize) -> Self {
+        self.max_expanded = Some(max_expanded);
+        self
+    }
+
+    pub fn with_max_cost(mut self, max_cost: Cost) -> Self {
+        self.max_cost = Some(max_cost);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+}
+
+/// The budget of a single search in progress
+#[derive(Debug, Clone)]
+pub struct BudgetTracker {
+    budget: SearchBudget,
+    deadline: Option<Instant>,
+    /// Nodes (or edges) expanded so far
+    pub expanded: usize,
+    /// the greatest accumulated cost expanded from so far
+    pub cost_reached: Cost,
+}
+
+impl BudgetTracker {
+    /// starts the clock on a search limited by `budget`
+    pub fn start(budget: SearchBudget) -> Self {
+        Self {
+            deadline: budget.timeout.map(|timeout| Instant::now() + timeout),
+            budget,
+            expanded: 0,
+            cost_reached: 0.0,
+        }
+    }
+
+    /// Counts one expansion, from a Node reached at `cost`, returning the limit which has been
+    /// hit, if any
+    pub fn spend(&mut self, cost: Cost) -> Option<BudgetLimit> {
+        self.expanded += 1;
+        self.cost_reached = self.cost_reached.max(cost);
+
+        if self.budget.max_cost.is_some_and(|max| cost > max) {
+            return Some(BudgetLimit::Cost);
+        }
+        if self
+            .budget
+            .max_expanded
+            .is_some_and(|max| self.expanded > max)
+        {
+            return Some(BudgetLimit::Expanded);
+        }
+
+        // checking the clock on every expansion would cost more than it's worth, but checking the
+        // first catches searches that were out of time (or cancelled) before they began
+        if (self.expanded - 1).is_multiple_of(CHECK_INTERVAL) {
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                return Some(BudgetLimit::Deadline);
+            }
+            if self
+                .budget
+                .cancellation
+                .as_ref()
+                .is_some_and(|token| token.is_cancelled())
+            {
+                return Some(BudgetLimit::Cancelled);
+            }
+        }
+
+        None
+    }
+}
+
+/// Which of a SearchBudget's limits stopped a search
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BudgetLimit {
+    Expanded,
+    Cost,
+    Deadline,
+    Cancelled,
+}
+
+impl fmt::Display for BudgetLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            BudgetLimit::Expanded => "ran out of expansions",
+            BudgetLimit::Cost => "exceeded its cost limit",
+            BudgetLimit::Deadline => "ran out of time",
+            BudgetLimit::Cancelled => "was cancelled",
+        };
+        f.write_str(description)
+    }
+}
+
+/// A flag shared between a search and whoever may want to stop it early
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,729 @@
+/// Contraction hierarchies: an offline preprocessing of the graph for a fixed cost model, which
+/// lets routes under that model be found by two small upward searches instead of a full A*.
+///
+/// Preprocessing "contracts" Nodes one at a time, least important first. Removing a Node adds
+/// a shortcut edge between each pair of its neighbors whose cheapest path ran through it, so the
+/// remaining graph keeps every shortest-path cost. A query then only ever needs to climb toward
+/// more important Nodes, from both ends, until the two searches meet.
+use super::{Cost, CostModel, MobileCostModel};
+use crate::db::DBConnection;
+use crate::osm::{Distance, NodeId, WayId, WayLabels};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time::Instant;
+use tracing::info;
+
+/// Dense index of a Node within a hierarchy
+pub(super) type NodeIndex = u32;
+
+/// An original Segment, as (from, to, way)
+pub type SegmentKey = (NodeId, NodeId, WayId);
+
+/// A Segment between two indexed Nodes, as (from, to, way, cost)
+pub(super) type CostedSegment = (NodeIndex, NodeIndex, WayId, Cost);
+
+/// `middle` of an edge that is an original Segment rather than a shortcut
+const NO_MIDDLE: NodeIndex = NodeIndex::MAX;
+
+/// Witness searches give up after settling this many Nodes, at the price of an occasional
+/// unnecessary shortcut
+const WITNESS_SETTLE_LIMIT: usize = 500;
+
+/// An edge of the hierarchy. Depending on which list it sits in, `node` is either its target
+/// (upward edges) or its source (downward edges).
+#[derive(Debug, Copy, Clone)]
+struct HierarchyEdge {
+    node: NodeIndex,
+    cost: Cost,
+    /// the Way of an original Segment, or 0 for shortcuts
+    way: WayId,
+    /// the contracted Node this shortcut bypasses, or NO_MIDDLE for original Segments
+    middle: NodeIndex,
+}
+
+/// A contraction hierarchy for one cost model
+pub struct ContractionHierarchy {
+    pub name: String,
+    pub cost_model: CostModel,
+
+    node_ids: Vec<NodeId>,
+    node_index: HashMap<NodeId, NodeIndex>,
+    rank: Vec<u32>,
+    /// `up[u]`: edges u → v where v outranks u
+    up: Vec<Vec<HierarchyEdge>>,
+    /// `down[v]`: edges u → v where u outranks v, keyed by their target so that the backward
+    /// search can climb them from v
+    down: Vec<Vec<HierarchyEdge>>,
+}
+
+impl ContractionHierarchy {
+    /// The cost models that are preprocessed: the default CostModel, plus the two anchors of the
+    /// MobileCostModel speed ↔ comfort slider
+    pub fn profiles() -> Vec<(&'static str, CostModel)> {
+        vec![
+            ("default", CostModel::default()),
+            ("mobile-speed", MobileCostModel::speed().resolve()),
+            ("mobile-comfort", MobileCostModel::comfort().resolve()),
+        ]
+    }
+
+    /// Contracts the full graph in the DB under the given cost model
+    pub fn build(
+        conn: &DBConnection,
+        name: &str,
+        cost_model: CostModel,
+    ) -> Result<Self, anyhow::Error> {
+        let mut builder = HierarchyBuilder::load(conn, &cost_model)?;
+        info!(
+            "{name}: contracting {} nodes, {} segments",
+            builder.node_ids.len(),
+            builder.out.iter().map(Vec::len).sum::<usize>()
+        );
+
+        let now = Instant::now();
+        builder.contract_all();
+        info!(
+            "{name}: contracted in {}s, {} shortcuts added",
+            now.elapsed().as_secs(),
+            builder.shortcuts
+        );
+
+        Ok(builder.into_hierarchy(name, cost_model))
+    }
+
+    /// Persists this hierarchy into the Contraction tables, replacing any previous version of it
+    pub fn save(&self, conn: &mut DBConnection) -> Result<(), anyhow::Error> {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM ContractionProfiles WHERE name = ?1",
+            [&self.name],
+        )?;
+        tx.execute(
+            "INSERT INTO ContractionProfiles (name, cost_model) VALUES (?1, ?2)",
+            (&self.name, format!("{:?}", self.cost_model)),
+        )?;
+        let profile = tx.last_insert_rowid();
+        {
+            let mut node_stmt = tx.prepare_cached(
+                "INSERT INTO ContractionNodes (profile, node, rank) VALUES (?1, ?2, ?3)",
+            )?;
+            for (idx, node_id) in self.node_ids.iter().enumerate() {
+                node_stmt.execute((profile, node_id, self.rank[idx]))?;
+            }
+
+            let mut edge_stmt = tx.prepare_cached(
+                "INSERT INTO ContractionEdges (profile, n1, n2, cost, way, middle) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            let mut insert = |from: NodeIndex, to: NodeIndex, e: &HierarchyEdge| {
+                let middle = (e.middle != NO_MIDDLE).then(|| self.node_ids[e.middle as usize]);
+                edge_stmt.execute((
+                    profile,
+                    self.node_ids[from as usize],
+                    self.node_ids[to as usize],
+                    e.cost,
+                    e.way,
+                    middle,
+                ))
+            };
+            for (idx, edges) in self.up.iter().enumerate() {
+                for e in edges {
+                    insert(idx as NodeIndex, e.node, e)?;
+                }
+            }
+            for (idx, edges) in self.down.iter().enumerate() {
+                for e in edges {
+                    insert(e.node, idx as NodeIndex, e)?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Loads every stored hierarchy whose cost model still matches its profile's current
+    /// definition. Hierarchies built against an older model are skipped, rather than silently
+    /// routing with stale costs.
+    pub fn load_all(conn: &DBConnection) -> Result<Vec<Self>, anyhow::Error> {
+        let has_tables: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'ContractionProfiles'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_tables {
+            return Ok(vec![]);
+        }
+
+        let mut hierarchies = vec![];
+        for (name, cost_model) in Self::profiles() {
+            let stored: Option<(i64, String)> = conn
+                .query_row(
+                    "SELECT id, cost_model FROM ContractionProfiles WHERE name = ?1",
+                    [name],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .ok();
+            match stored {
+                Some((profile, fingerprint)) if fingerprint == format!("{:?}", cost_model) => {
+                    hierarchies.push(Self::load(conn, profile, name, cost_model)?);
+                    info!("Loaded contraction hierarchy for {name}");
+                }
+                Some(_) => info!("Skipping stale contraction hierarchy for {name}"),
+                None => {}
+            }
+        }
+        Ok(hierarchies)
+    }
+
+    fn load(
+        conn: &DBConnection,
+        profile: i64,
+        name: &str,
+        cost_model: CostModel,
+    ) -> Result<Self, anyhow::Error> {
+        let mut node_ids = vec![];
+        let mut node_index = HashMap::new();
+        let mut rank = vec![];
+
+        let mut stmt =
+            conn.prepare("SELECT node, rank FROM ContractionNodes WHERE profile = ?1")?;
+        let mut rows = stmt.query([profile])?;
+        while let Some(row) = rows.next()? {
+            let node_id: NodeId = row.get(0)?;
+            node_index.insert(node_id, node_ids.len() as NodeIndex);
+            node_ids.push(node_id);
+            rank.push(row.get(1)?);
+        }
+
+        let mut up = vec![vec![]; node_ids.len()];
+        let mut down = vec![vec![]; node_ids.len()];
+
+        let mut stmt = conn
+            .prepare("SELECT n1, n2, cost, way, middle FROM ContractionEdges WHERE profile = ?1")?;
+        let mut rows = stmt.query([profile])?;
+        while let Some(row) = rows.next()? {
+            let from = node_index[&row.get::<_, NodeId>(0)?];
+            let to = node_index[&row.get::<_, NodeId>(1)?];
+            let middle = row
+                .get::<_, Option<NodeId>>(4)?
+                .map(|m| node_index[&m])
+                .unwrap_or(NO_MIDDLE);
+            let cost = row.get(2)?;
+            let way = row.get(3)?;
+
+            if rank[to as usize] > rank[from as usize] {
+                up[from as usize].push(HierarchyEdge {
+                    node: to,
+                    cost,
+                    way,
+                    middle,
+                });
+            } else {
+                down[to as usize].push(HierarchyEdge {
+                    node: from,
+                    cost,
+                    way,
+                    middle,
+                });
+            }
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            cost_model,
+            node_ids,
+            node_index,
+            rank,
+            up,
+            down,
+        })
+    }
+
+    /// Finds the cheapest path from any of the `sources` to any of the `targets`, returning its
+    /// cost and the original Segments along it as (from, to, way), with shortcuts unpacked.
+    /// Each source and target carries the cost already spent getting to (or still to spend
+    /// getting away from) that Node.
+    pub fn find_path(
+        &self,
+        sources: &[(NodeId, Cost)],
+        targets: &[(NodeId, Cost)],
+    ) -> Option<(Cost, Vec<SegmentKey>)> {
+        let mut forward = UpwardSearch::new(&self.up, sources, &self.node_index);
+        let mut backward = UpwardSearch::new(&self.down, targets, &self.node_index);
+
+        // cheapest complete path seen so far, as (total cost, meeting node)
+        let mut best: Option<(Cost, NodeIndex)> = None;
+
+        loop {
+            let best_cost = best.map(|(c, _)| c).unwrap_or(Cost::MAX);
+            let forward_done = forward.peek().is_none_or(|c| c >= best_cost);
+            let backward_done = backward.peek().is_none_or(|c| c >= best_cost);
+            if forward_done && backward_done {
+                break;
+            }
+
+            let (search, other) =
+                if !forward_done && (backward_done || forward.peek() <= backward.peek()) {
+                    (&mut forward, &backward)
+                } else {
+                    (&mut backward, &forward)
+                };
+
+            if let Some((node, cost)) = search.settle_next() {
+                if let Some(&(other_cost, _)) = other.settled.get(&node) {
+                    if cost + other_cost < best_cost {
+                        best = Some((cost + other_cost, node));
+                    }
+                }
+            }
+        }
+
+        let (cost, meeting) = best?;
+
+        // forward parents lead back to a source, backward parents lead on to a target
+        let mut path = vec![];
+        let mut node = meeting;
+        while let Some((prev, edge)) = forward.settled[&node].1 {
+            self.unpack(prev, node, &edge, &mut path);
+            node = prev;
+        }
+        path.reverse();
+
+        let mut tail = vec![];
+        let mut node = meeting;
+        while let Some((next, edge)) = backward.settled[&node].1 {
+            let mut unpacked = vec![];
+            self.unpack(node, next, &edge, &mut unpacked);
+            tail.extend(unpacked.into_iter().rev());
+            node = next;
+        }
+        path.extend(tail);
+
+        Some((cost, path))
+    }
+
+    /// Expands the edge `from → to` into its original Segments, pushed in _reverse_ route order
+    fn unpack(
+        &self,
+        from: NodeIndex,
+        to: NodeIndex,
+        edge: &HierarchyEdge,
+        out: &mut Vec<SegmentKey>,
+    ) {
+        if edge.middle == NO_MIDDLE {
+            out.push((
+                self.node_ids[from as usize],
+                self.node_ids[to as usize],
+                edge.way,
+            ));
+            return;
+        }
+
+        // the bypassed Node was contracted before both ends, so the first half is stored
+        // downward into it and the second half upward out of it
+        let middle = edge.middle;
+        let first = cheapest(&self.down[middle as usize], from);
+        let second = cheapest(&self.up[middle as usize], to);
+        self.unpack(middle, to, &second, out);
+        self.unpack(from, middle, &first, out);
+    }
+}
+
+fn cheapest(edges: &[HierarchyEdge], node: NodeIndex) -> HierarchyEdge {
+    *edges
+        .iter()
+        .filter(|e| e.node == node)
+        .min_by(|a, b| a.cost.total_cmp(&b.cost))
+        .expect("shortcut halves should always be present in the hierarchy")
+}
+
+/// One direction of a hierarchy query: Dijkstra restricted to edges leading to higher ranks
+struct UpwardSearch<'a> {
+    edges: &'a [Vec<HierarchyEdge>],
+    queue: BinaryHeap<Reverse<(OrderedCost, NodeIndex)>>,
+    /// best known cost per Node, with the Node and edge it was reached through
+    settled: HashMap<NodeIndex, (Cost, Option<(NodeIndex, HierarchyEdge)>)>,
+}
+
+impl<'a> UpwardSearch<'a> {
+    fn new(
+        edges: &'a [Vec<HierarchyEdge>],
+        seeds: &[(NodeId, Cost)],
+        node_index: &HashMap<NodeId, NodeIndex>,
+    ) -> Self {
+        let mut search = Self {
+            edges,
+            queue: BinaryHeap::new(),
+            settled: HashMap::new(),
+        };
+        for (idx, cost) in seeds
+            .iter()
+            .filter_map(|(id, cost)| Some((*node_index.get(id)?, *cost)))
+        {
+            if search.settled.get(&idx).is_none_or(|(c, _)| cost < *c) {
+                search.settled.insert(idx, (cost, None));
+                search.queue.push(Reverse((OrderedCost(cost), idx)));
+            }
+        }
+        search
+    }
+
+    fn peek(&self) -> Option<Cost> {
+        self.queue.peek().map(|Reverse((c, _))| c.0)
+    }
+
+    /// Pops the cheapest Node and relaxes its upward edges, returning it and its final cost.
+    /// Returns None for stale queue entries.
+    fn settle_next(&mut self) -> Option<(NodeIndex, Cost)> {
+        let Reverse((OrderedCost(cost), node)) = self.queue.pop()?;
+        if self.settled[&node].0 < cost {
+            return None;
+        }
+
+        for edge in &self.edges[node as usize] {
+            let next_cost = cost + edge.cost;
+            let improved = self
+                .settled
+                .get(&edge.node)
+                .is_none_or(|(c, _)| next_cost < *c);
+            if improved {
+                self.settled
+                    .insert(edge.node, (next_cost, Some((node, *edge))));
+                self.queue
+                    .push(Reverse((OrderedCost(next_cost), edge.node)));
+            }
+        }
+
+        Some((node, cost))
+    }
+}
+
+/// Total ordering over costs, for use in BinaryHeap keys
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(super) struct OrderedCost(pub(super) Cost);
+
+impl Eq for OrderedCost {}
+
+impl PartialOrd for OrderedCost {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderedCost {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Loads every Segment in the DB, costed under the given model, as (from, to, way, cost) with
+/// the Nodes densely indexed. Returns the NodeIds in index order alongside them.
+pub(super) fn load_segment_costs(
+    conn: &DBConnection,
+    cost_model: &CostModel,
+) -> Result<(Vec<NodeId>, Vec<CostedSegment>), anyhow::Error> {
+    let mut stmt = conn.prepare(
+        "
+        SELECT S.n1, S.n2, S.way, S.distance, WL.cycleway, WL.road, WL.salmon, WL.network, WL.surface, WL.lts, S.elevation_gain, S.elevation_loss
+        FROM Segments S
+        JOIN WayLabels WL ON S.way = WL.id
+        ",
+    )?;
+
+    let mut node_ids = vec![];
+    let mut node_index: HashMap<NodeId, NodeIndex> = HashMap::new();
+    let mut index_of = |id: NodeId, node_ids: &mut Vec<NodeId>| {
+        *node_index.entry(id).or_insert_with(|| {
+            node_ids.push(id);
+            (node_ids.len() - 1) as NodeIndex
+        })
+    };
+
+    let mut segments = vec![];
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let n1 = index_of(row.get(0)?, &mut node_ids);
+        let n2 = index_of(row.get(1)?, &mut node_ids);
+        let way: WayId = row.get(2)?;
+        let distance: Distance = row.get(3)?;
+        let labels: WayLabels = (
+            row.get(4)?,
+            row.get(5)?,
+            row.get(6)?,
+            row.get(7)?,
+            row.get(8)?,
+            row.get(9)?,
+        );
+        let elevation_gain: i16 = row.get(10)?;
+        let elevation_loss: i16 = row.get(11)?;
+        // a search capped on traffic stress never rides the Ways above it
+        if !cost_model.allows(&labels) {
+            continue;
+        }
+
+        // must match the segment cost computed by TraversalSegmentBuilder
+        let cost = cost_model.calculate_way_cost(way, &labels)
+            * distance as f32
+            * (1.0
+                + cost_model.calculate_elevation_multiplier(
+                    elevation_gain,
+                    elevation_loss,
+                    distance,
+                ));
+        segments.push((n1, n2, way, cost));
+    }
+
+    Ok((node_ids, segments))
+}
+
+/// Mutable graph state used while contracting
+struct HierarchyBuilder {
+    node_ids: Vec<NodeId>,
+    /// out[u]: edges u → v, including shortcuts. `node` is the target.
+    out: Vec<Vec<HierarchyEdge>>,
+    /// inc[v]: edges u → v, including shortcuts. `node` is the source.
+    inc: Vec<Vec<HierarchyEdge>>,
+    contracted: Vec<bool>,
+    rank: Vec<u32>,
+    deleted_neighbors: Vec<i32>,
+    shortcuts: usize,
+
+    // reusable witness search buffers
+    witness_cost: Vec<Cost>,
+    witness_touched: Vec<NodeIndex>,
+}
+
+impl HierarchyBuilder {
+    fn load(conn: &DBConnection, cost_model: &CostModel) -> Result<Self, anyhow::Error> {
+        let (node_ids, segments) = load_segment_costs(conn, cost_model)?;
+
+        let n = node_ids.len();
+        let mut builder = Self {
+            node_ids,
+            out: vec![vec![]; n],
+            inc: vec![vec![]; n],
+            contracted: vec![false; n],
+            rank: vec![0; n],
+            deleted_neighbors: vec![0; n],
+            shortcuts: 0,
+            witness_cost: vec![Cost::MAX; n],
+            witness_touched: vec![],
+        };
+        for (n1, n2, way, cost) in segments {
+            builder.push_edge(n1, n2, cost, way, NO_MIDDLE);
+        }
+        Ok(builder)
+    }
+
+    fn push_edge(
+        &mut self,
+        from: NodeIndex,
+        to: NodeIndex,
+        cost: Cost,
+        way: WayId,
+        middle: NodeIndex,
+    ) {
+        self.out[from as usize].push(HierarchyEdge {
+            node: to,
+            cost,
+            way,
+            middle,
+        });
+        self.inc[to as usize].push(HierarchyEdge {
+            node: from,
+            cost,
+            way,
+            middle,
+        });
+    }
+
+    /// Contracts every Node, ordered by a lazily-updated importance estimate
+    fn contract_all(&mut self) {
+        let mut queue: BinaryHeap<Reverse<(i32, NodeIndex)>> = (0..self.node_ids.len())
+            .map(|v| Reverse((self.priority(v as NodeIndex), v as NodeIndex)))
+            .collect();
+
+        let mut next_rank = 0;
+        while let Some(Reverse((priority, v))) = queue.pop() {
+            if self.contracted[v as usize] {
+                continue;
+            }
+
+            // lazy update: if this Node became more important since it was queued, requeue it
+            let current = self.priority(v);
+            if current > priority {
+                if let Some(Reverse((next, _))) = queue.peek() {
+                    if current > *next {
+                        queue.push(Reverse((current, v)));
+                        continue;
+                    }
+                }
+            }
+
+            self.shortcuts += self.contract(v, false);
+            self.contracted[v as usize] = true;
+            self.rank[v as usize] = next_rank;
+            next_rank += 1;
+
+            for neighbor in self.live_neighbors(v) {
+                self.deleted_neighbors[neighbor as usize] += 1;
+            }
+        }
+    }
+
+    /// Importance estimate: edge difference plus how many neighbors are already gone, which
+    /// keeps contraction spread evenly across the graph
+    fn priority(&mut self, v: NodeIndex) -> i32 {
+        let shortcuts = self.contract(v, true) as i32;
+        let removed = (self.live_edges(&self.inc[v as usize]).len()
+            + self.live_edges(&self.out[v as usize]).len()) as i32;
+        shortcuts - removed + self.deleted_neighbors[v as usize]
+    }
+
+    fn live_neighbors(&self, v: NodeIndex) -> Vec<NodeIndex> {
+        self.inc[v as usize]
+            .iter()
+            .chain(self.out[v as usize].iter())
+            .map(|e| e.node)
+            .filter(|n| !self.contracted[*n as usize])
+            .collect()
+    }
+
+    /// Cheapest edge to each uncontracted neighbor in the list
+    fn live_edges(&self, edges: &[HierarchyEdge]) -> Vec<(NodeIndex, Cost)> {
+        let mut cheapest: HashMap<NodeIndex, Cost> = HashMap::new();
+        for e in edges.iter().filter(|e| !self.contracted[e.node as usize]) {
+            cheapest
+                .entry(e.node)
+                .and_modify(|c| *c = c.min(e.cost))
+                .or_insert(e.cost);
+        }
+        cheapest.into_iter().collect()
+    }
+
+    /// Adds (or, on a dry run, only counts) the shortcuts needed to remove `v` from the graph
+    fn contract(&mut self, v: NodeIndex, dry_run: bool) -> usize {
+        let incoming = self.live_edges(&self.inc[v as usize]);
+        let outgoing = self.live_edges(&self.out[v as usize]);
+        let Some(max_out) = outgoing.iter().map(|(_, c)| *c).reduce(Cost::max) else {
+            return 0;
+        };
+
+        let mut shortcuts = vec![];
+        for &(u, in_cost) in &incoming {
+            self.witness_search(u, v, in_cost + max_out);
+            for &(x, out_cost) in &outgoing {
+                if x == u {
+                    continue;
+                }
+                let via_cost = in_cost + out_cost;
+                if self.witness_cost[x as usize] > via_cost {
+                    shortcuts.push((u, x, via_cost));
+                }
+            }
+        }
+
+        if !dry_run {
+            for &(u, x, cost) in &shortcuts {
+                self.add_shortcut(u, x, cost, v);
+            }
+        }
+        shortcuts.len()
+    }
+
+    /// Bounded Dijkstra from `source` that avoids `skip`, leaving the costs in `witness_cost`
+    fn witness_search(&mut self, source: NodeIndex, skip: NodeIndex, max_cost: Cost) {
+        for n in self.witness_touched.drain(..) {
+            self.witness_cost[n as usize] = Cost::MAX;
+        }
+
+        let mut queue = BinaryHeap::new();
+        self.witness_cost[source as usize] = 0.0;
+        self.witness_touched.push(source);
+        queue.push(Reverse((OrderedCost(0.0), source)));
+
+        let mut settled = 0;
+        while let Some(Reverse((OrderedCost(cost), node))) = queue.pop() {
+            if cost > self.witness_cost[node as usize] {
+                continue;
+            }
+            if cost > max_cost || settled >= WITNESS_SETTLE_LIMIT {
+                break;
+            }
+            settled += 1;
+
+            for e in &self.out[node as usize] {
+                if e.node == skip || self.contracted[e.node as usize] {
+                    continue;
+                }
+                let next_cost = cost + e.cost;
+                if next_cost < self.witness_cost[e.node as usize] {
+                    if self.witness_cost[e.node as usize] == Cost::MAX {
+                        self.witness_touched.push(e.node);
+                    }
+                    self.witness_cost[e.node as usize] = next_cost;
+                    queue.push(Reverse((OrderedCost(next_cost), e.node)));
+                }
+            }
+        }
+    }
+
+    fn add_shortcut(&mut self, from: NodeIndex, to: NodeIndex, cost: Cost, middle: NodeIndex) {
+        // improve an existing shortcut between the same pair, rather than duplicating it
+        let existing = self.out[from as usize]
+            .iter()
+            .position(|e| e.node == to && e.middle != NO_MIDDLE);
+        match existing {
+            Some(idx) => {
+                if self.out[from as usize][idx].cost <= cost {
+                    return;
+                }
+                self.out[from as usize][idx].cost = cost;
+                self.out[from as usize][idx].middle = middle;
+                if let Some(e) = self.inc[to as usize]
+                    .iter_mut()
+                    .find(|e| e.node == from && e.middle != NO_MIDDLE)
+                {
+                    e.cost = cost;
+                    e.middle = middle;
+                }
+            }
+            None => self.push_edge(from, to, cost, 0, middle),
+        }
+    }
+
+    fn into_hierarchy(self, name: &str, cost_model: CostModel) -> ContractionHierarchy {
+        let n = self.node_ids.len();
+        let mut up = vec![vec![]; n];
+        let mut down = vec![vec![]; n];
+
+        for (from, edges) in self.out.into_iter().enumerate() {
+            for e in edges {
+                if self.rank[e.node as usize] > self.rank[from] {
+                    up[from].push(e);
+                } else {
+                    down[e.node as usize].push(HierarchyEdge {
+                        node: from as NodeIndex,
+                        ..e
+                    });
+                }
+            }
+        }
+
+        let node_index = self
+            .node_ids
+            .iter()
+            .enumerate()
+            .map(|(idx, id)| (*id, idx as NodeIndex))
+            .collect();
+
+        ContractionHierarchy {
+            name: name.to_owned(),
+            cost_model,
+            node_ids: self.node_ids,
+            node_index,
+            rank: self.rank,
+            up,
+            down,
+        }
+    }
+}
@@ -1,64 +1,280 @@
 use super::traversal::{
-    Route, Traversable, Traversal, TraversalSegment, END_NODE_ID, START_NODE_ID,
+    arrival_after, riding_time, turn_cost, HeuristicKind, Route, SearchMode, Traversable,
+    Traversal, TraversalContext, TraversalSegment, Waypoint, END_NODE_ID, START_NODE_ID,
 };
-use super::InMemoryGraphRepository;
-use super::{repository::GraphRepository, Cost, CostModel, Depth, Weight};
-use crate::osm::{Node, NodeId, WayId};
-use geo::Point;
+use super::{
+    repository::GraphRepository, ContractionHierarchy, Cost, CostModel, Depth,
+    InMemoryGraphRepository, Landmarks, RoutingError, SearchBudget, SegmentKey,
+    SnapshotGraphRepository, Weight,
+};
+use crate::db::{self, DBConnection};
+use crate::osm::{Control, Distance, Neighbor, Node, NodeId, WayId};
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
+use geo::{Intersects, Line, Point, Rect};
 use serde::Serialize;
 use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+
+/// The search options shared by every leg of a route
+#[derive(Clone)]
+struct LegOptions {
+    with_traversal: bool,
+    cost_model: CostModel,
+    heuristic_weight: Option<Weight>,
+    search_mode: Option<SearchMode>,
+}
 
 /// The Graph "service object", through which routing interfaces are exposed
 pub struct Graph {
     pub db: Box<dyn GraphRepository>,
+    /// Preprocessed hierarchies for the default cost profiles, if they've been built
+    pub hierarchies: Vec<ContractionHierarchy>,
+    /// Precomputed landmarks for the default cost profiles, if they've been built
+    pub landmarks: Vec<Arc<Landmarks>>,
+    /// Bounding box around every Way in the graph, outside of which points can't be snapped
+    pub bounds: Rect,
+    /// Limits applied to each search run over the graph
+    pub budget: SearchBudget,
 }
 
 #[derive(Debug, Serialize)]
 pub struct RouteMetadata {
-    max_depth: Depth,
-    cost_range: (Cost, Cost),
+    pub(super) max_depth: Depth,
+    pub(super) cost_range: (Cost, Cost),
+    /// what guided the search toward its target
+    pub(super) heuristic: HeuristicKind,
+    /// how many traffic signals the route passes through
+    pub(super) signals: usize,
+}
+
+impl RouteMetadata {
+    /// how many traffic signals the route passes through
+    pub fn signals(&self) -> usize {
+        self.signals
+    }
+
+    /// metadata for a route with no legs yet, to be built up with `merge`
+    pub(super) fn empty() -> Self {
+        Self {
+            max_depth: 0,
+            cost_range: (f32::MAX, f32::MIN),
+            heuristic: HeuristicKind::None,
+            signals: 0,
+        }
+    }
+
+    /// widens this metadata to also cover another leg's
+    pub(super) fn merge(&mut self, other: &RouteMetadata) {
+        self.max_depth = self.max_depth.max(other.max_depth);
+        self.cost_range.0 = self.cost_range.0.min(other.cost_range.0);
+        self.cost_range.1 = self.cost_range.1.max(other.cost_range.1);
+        // legs may be searched differently; report the most informed of them
+        self.heuristic = self.heuristic.max(other.heuristic);
+        self.signals += other.signals;
+    }
 }
 
 impl Graph {
+    /// Maps the graph snapshot at `GRAPH_PATH`, if given, or else loads the graph at `DB_PATH`
+    /// into memory.
+    ///
+    /// A snapshot needs no DB: without `DB_PATH`, routes are simply searched without the
+    /// contraction hierarchies and landmarks preprocessed into it.
     pub fn new() -> Result<Self, anyhow::Error> {
+        if let Ok(path) = env::var("GRAPH_PATH") {
+            let snapshot = SnapshotGraphRepository::open(Path::new(&path))?;
+            let bounds = snapshot.bounds();
+            let (hierarchies, landmarks) = match env::var("DB_PATH") {
+                Ok(_) => {
+                    let conn = db::get_conn()?;
+                    (
+                        ContractionHierarchy::load_all(&conn)?,
+                        Landmarks::load_all(&conn)?,
+                    )
+                }
+                Err(_) => (vec![], vec![]),
+            };
+            return Ok(Self {
+                db: Box::new(snapshot),
+                hierarchies,
+                landmarks,
+                bounds,
+                budget: SearchBudget::default(),
+            });
+        }
+
+        let conn = db::get_conn()?;
         Ok(Self {
             db: Box::new(InMemoryGraphRepository::new()?),
+            hierarchies: ContractionHierarchy::load_all(&conn)?,
+            landmarks: Landmarks::load_all(&conn)?,
+            bounds: load_bounds(&conn)?,
+            budget: SearchBudget::default(),
         })
     }
 
-    /// Calculates a Route between the start and end points, optionally attaching the raw underlying traversal
+    pub fn with_budget(mut self, budget: SearchBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Calculates a Route through each of the points, in order, optionally attaching the raw
+    /// underlying traversal. Each consecutive pair of points is routed as its own leg, and the
+    /// legs are joined into one continuous Route, with each segment marked by its `leg`.
+    ///
+    /// Given a (local) departure time, Ways closed by time-based restrictions when the rider
+    /// would reach them are avoided, or penalized if the cost model allows riding them. Each leg
+    /// sets off when the previous one is expected to arrive.
     pub fn calculate_route(
         &self,
-        start: Point,
-        end: Point,
+        points: &[Point],
         with_traversal: bool,
         cost_model: Option<CostModel>,
         heuristic_weight: Option<Weight>,
+        search_mode: Option<SearchMode>,
+        departure: Option<NaiveDateTime>,
     ) -> Result<(Route, Option<Traversal>, RouteMetadata), anyhow::Error> {
-        let end_node = Node::new(END_NODE_ID, &end);
-        let target_neighbors = self.db.get_snapped_neighbors(end, None)?;
-        let target_neighbor_node_ids: Vec<NodeId> =
-            target_neighbors.iter().map(|n| n.node.id).collect();
+        if points.len() < 2 {
+            return Err(RoutingError::invalid_request(
+                "A route needs at least a start and an end point",
+            )
+            .into());
+        }
+        let cost_model = self.prepare_cost_model(cost_model)?;
 
-        let mut context = self.initialize_traversal(&start, cost_model, heuristic_weight)?;
+        // snap each point once, so that a waypoint ending one leg starts the next from the same spot
+        let waypoints = points
+            .iter()
+            .map(|p| self.snap_waypoint(*p))
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
 
-        self.traverse_between(&mut context, &target_neighbor_node_ids, &end_node)?;
+        let options = LegOptions {
+            with_traversal,
+            cost_model,
+            heuristic_weight,
+            search_mode,
+        };
+        let mut route: Route = vec![];
+        let mut traversal: Option<Traversal> = with_traversal.then(Vec::new);
+        let mut meta = RouteMetadata::empty();
+        let mut departure = departure;
 
-        // construct route from traversal information, tracing backwards from the end node
-        let mut current_segment = context.came_from.get(&END_NODE_ID).unwrap();
-        let mut result: VecDeque<TraversalSegment> = VecDeque::from([current_segment.clone()]);
+        for (leg, pair) in waypoints.windows(2).enumerate() {
+            let (leg_route, leg_traversal, leg_meta) =
+                self.calculate_leg(&pair[0], &pair[1], &options, departure)?;
+
+            // the next leg sets off from wherever this one arrives
+            departure = departure.map(|departure| {
+                let distance = leg_route.last().map(|s| s.distance_so_far).unwrap_or(0);
+                arrival_after(departure, distance)
+            });
 
-        loop {
-            if current_segment.from.id == START_NODE_ID {
-                break;
+            append_leg(&mut route, leg_route, leg);
+
+            if let (Some(traversal), Some(leg_traversal)) = (traversal.as_mut(), leg_traversal) {
+                traversal.extend(leg_traversal.into_iter().map(|mut segment| {
+                    segment.leg = leg;
+                    segment
+                }));
+            }
+
+            meta.merge(&leg_meta);
+        }
+
+        Ok((route, traversal, meta))
+    }
+
+    /// Routes a single leg between two snapped points, setting off at `departure` if given
+    fn calculate_leg(
+        &self,
+        start: &Waypoint,
+        end: &Waypoint,
+        options: &LegOptions,
+        departure: Option<NaiveDateTime>,
+    ) -> Result<(Route, Option<Traversal>, RouteMetadata), anyhow::Error> {
+        let LegOptions {
+            with_traversal,
+            cost_model,
+            heuristic_weight,
+            search_mode,
+        } = options.clone();
+
+        // there's nothing to search for when both points lie along the same segment
+        if let Some(segment) = direct_segment(start, end, &cost_model) {
+            let meta = RouteMetadata {
+                max_depth: 0,
+                cost_range: (segment.cost_factor, segment.cost_factor),
+                heuristic: HeuristicKind::None,
+                signals: 0,
+            };
+            let route = vec![segment];
+            let traversal = with_traversal.then(|| route.clone());
+            return Ok((route, traversal, meta));
+        }
+
+        // a preprocessed hierarchy answers the same query far faster, but only explores a handful
+        // of Nodes, so it can't produce a meaningful traversal. Hierarchies are built without
+        // regard to time, so can't route around closures either.
+        if !with_traversal && departure.is_none() {
+            if let Some(hierarchy) = self.hierarchies.iter().find(|h| h.cost_model == cost_model) {
+                // nor around turn restrictions, so a route breaking one is searched for instead
+                let contracted = self.route_contracted(hierarchy, start, end)?;
+                if self.obeys_turn_restrictions(&contracted.0)? {
+                    return Ok(contracted);
+                }
+            }
+        }
+
+        // turn costs make the search edge-based, and a departure time has to be carried forward
+        // from the start, both of which only the unidirectional search supports
+        let search_mode = if cost_model.has_turn_costs() || departure.is_some() {
+            SearchMode::Unidirectional
+        } else {
+            search_mode.unwrap_or_default()
+        };
+
+        match search_mode {
+            SearchMode::Unidirectional => self.route_unidirectional(
+                start,
+                end,
+                with_traversal,
+                cost_model,
+                heuristic_weight,
+                departure,
+            ),
+            SearchMode::Bidirectional => {
+                self.route_bidirectional(start, end, with_traversal, cost_model, heuristic_weight)
             }
-            current_segment = context.came_from.get(&current_segment.from.id).unwrap();
-            result.push_front(current_segment.clone());
         }
+    }
+
+    fn route_unidirectional(
+        &self,
+        start: &Waypoint,
+        end: &Waypoint,
+        with_traversal: bool,
+        cost_model: CostModel,
+        heuristic_weight: Option<Weight>,
+        departure: Option<NaiveDateTime>,
+    ) -> Result<(Route, Option<Traversal>, RouteMetadata), anyhow::Error> {
+        let end_node = Node::new(END_NODE_ID, &end.point);
+
+        let mut context = self.initialize_traversal(start, cost_model, heuristic_weight)?;
+        self.apply_landmarks(&mut context, &end.arrivals);
+        context.depart_at(departure);
+
+        self.traverse_between(&mut context, &end.arrivals, &end_node)
+            .map_err(|e| self.expand_partial(e))?;
+
+        // construct route from traversal information, tracing backwards from the end node
+        let result = self.expand_route(&trace_back(&context, END_NODE_ID))?;
 
         // include the traversal if requested
         let traversal = if with_traversal {
-            Some(context.came_from.values().cloned().collect())
+            Some(self.expand_traversal(context.came_from.values())?)
         } else {
             None
         };
@@ -66,8 +282,458 @@ impl Graph {
         let meta = RouteMetadata {
             max_depth: context.max_depth,
             cost_range: context.cost_range,
+            heuristic: context.heuristic_kind(),
+            signals: self.count_signals(&result)?,
+        };
+        Ok((result, traversal, meta))
+    }
+
+    fn route_bidirectional(
+        &self,
+        start: &Waypoint,
+        end: &Waypoint,
+        with_traversal: bool,
+        cost_model: CostModel,
+        heuristic_weight: Option<Weight>,
+    ) -> Result<(Route, Option<Traversal>, RouteMetadata), anyhow::Error> {
+        let start_node = Node::new(START_NODE_ID, &start.point);
+        let end_node = Node::new(END_NODE_ID, &end.point);
+
+        let mut forward = self.initialize_traversal(start, cost_model.clone(), heuristic_weight)?;
+        let mut backward = self.initialize_reverse_traversal(end, cost_model, heuristic_weight)?;
+        self.apply_landmarks(&mut forward, &end.arrivals);
+        self.apply_landmarks(&mut backward, &start.departures);
+
+        let meeting_node_id = self
+            .traverse_bidirectional(&mut forward, &mut backward, &start_node, &end_node)
+            .map_err(|e| self.expand_partial(e))?;
+
+        // the forward half traces back from the meeting node to the start...
+        let mut result = trace_back(&forward, meeting_node_id);
+
+        // ...and the backward half is walked out to the end. Its segments carry costs and
+        // distances accumulated from the end, so they're re-accumulated from the start here.
+        let mut current_node_id = meeting_node_id;
+        while current_node_id != END_NODE_ID {
+            let segment = backward.came_from.get(&current_node_id).unwrap();
+            let (prev_cost, prev_distance, prev_depth) = result
+                .last()
+                .map(|s| (s.cost, s.distance_so_far, s.depth))
+                .unwrap_or_default();
+
+            let mut segment = segment.clone();
+            segment.cost = prev_cost + (segment.cost - segment.cost_so_far);
+            segment.cost_so_far = prev_cost;
+            segment.distance_so_far = prev_distance + segment.length;
+            segment.depth = prev_depth + 1;
+
+            current_node_id = segment.to.id;
+            result.push(segment);
+        }
+        let result = self.expand_route(&result)?;
+
+        let traversal = if with_traversal {
+            Some(
+                self.expand_traversal(
+                    forward
+                        .came_from
+                        .values()
+                        .chain(backward.came_from.values()),
+                )?,
+            )
+        } else {
+            None
+        };
+
+        let meta = RouteMetadata {
+            max_depth: forward.max_depth.max(backward.max_depth),
+            cost_range: (
+                forward.cost_range.0.min(backward.cost_range.0),
+                forward.cost_range.1.max(backward.cost_range.1),
+            ),
+            heuristic: forward.heuristic_kind().max(backward.heuristic_kind()),
+            signals: self.count_signals(&result)?,
+        };
+        Ok((result, traversal, meta))
+    }
+
+    fn route_contracted(
+        &self,
+        hierarchy: &ContractionHierarchy,
+        start: &Waypoint,
+        end: &Waypoint,
+    ) -> Result<(Route, Option<Traversal>, RouteMetadata), anyhow::Error> {
+        // the hierarchy is entered from the ends of each point's segment, already carrying the
+        // cost of the partial edges between them and the points themselves
+        let start_node = Node::new(START_NODE_ID, &start.point);
+        let end_node = Node::new(END_NODE_ID, &end.point);
+        let sources: Vec<(NodeId, Cost)> = start
+            .departures
+            .iter()
+            .map(|(neighbor, way_labels)| {
+                let segment = TraversalSegment::build_to_neighbor(&start_node, neighbor)
+                    .with_cost(
+                        &hierarchy.cost_model,
+                        way_labels,
+                        neighbor.elevation_gain,
+                        neighbor.elevation_loss,
+                        0.0,
+                    )
+                    .build();
+                (neighbor.node.id, segment.cost)
+            })
+            .collect();
+        let targets: Vec<(NodeId, Cost)> = end
+            .arrivals
+            .iter()
+            .map(|(neighbor, way_labels)| {
+                let segment = TraversalSegment::build_from_neighbor(neighbor, &end_node)
+                    .with_cost(
+                        &hierarchy.cost_model,
+                        way_labels,
+                        neighbor.elevation_gain,
+                        neighbor.elevation_loss,
+                        0.0,
+                    )
+                    .build();
+                (neighbor.node.id, segment.cost)
+            })
+            .collect();
+
+        let (_, path) = hierarchy
+            .find_path(&sources, &targets)
+            .ok_or(RoutingError::NoPathFound)?;
+
+        // the path begins at whichever Node turned out to be cheapest
+        let first_node_id = path.first().map(|(n1, _, _)| *n1).unwrap_or_else(|| {
+            sources
+                .iter()
+                .filter_map(|(id, cost)| {
+                    let (_, target_cost) = targets.iter().find(|(t, _)| t == id)?;
+                    Some((*id, cost + target_cost))
+                })
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(id, _)| id)
+                .expect("an empty path should only be found when start and end share a Node")
+        });
+        let result = self.rebuild_route(start, end, first_node_id, &path, &hierarchy.cost_model)?;
+
+        let meta = RouteMetadata {
+            max_depth: result.len() - 1,
+            cost_range: result[1..result.len() - 1]
+                .iter()
+                .fold((f32::MAX, f32::MIN), |(lo, hi), s| {
+                    (lo.min(s.cost_factor), hi.max(s.cost_factor))
+                }),
+            heuristic: HeuristicKind::None,
+            signals: self.count_signals(&result)?,
+        };
+        Ok((result, None, meta))
+    }
+
+    /// How many traffic signals the route passes through. The virtual start and end nodes never
+    /// have any.
+    pub(super) fn count_signals(&self, route: &Route) -> Result<usize, anyhow::Error> {
+        let mut signals = 0;
+        for segment in route {
+            if self.db.get_control(segment.to.id)? == Control::Signals {
+                signals += 1;
+            }
+        }
+        Ok(signals)
+    }
+
+    /// Whether every turn between consecutive segments of the route is allowed
+    fn obeys_turn_restrictions(&self, route: &Route) -> Result<bool, anyhow::Error> {
+        for pair in route.windows(2) {
+            if !self
+                .db
+                .is_turn_allowed(pair[0].way, pair[0].to.id, pair[1].way)?
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Reconstructs a full Route along a known sequence of Segments, leaving `start` for the
+    /// Node `first_node_id`. Each Segment is rebuilt just as the A* expansion would have,
+    /// so costs are consistent with a search under the same `cost_model`.
+    pub(super) fn rebuild_route(
+        &self,
+        start: &Waypoint,
+        end: &Waypoint,
+        first_node_id: NodeId,
+        path: &[SegmentKey],
+        cost_model: &CostModel,
+    ) -> Result<Route, anyhow::Error> {
+        let start_node = Node::new(START_NODE_ID, &start.point);
+        let end_node = Node::new(END_NODE_ID, &end.point);
+        let (first_neighbor, first_labels) = start
+            .departures
+            .iter()
+            .find(|(n, _)| n.node.id == first_node_id)
+            .ok_or_else(|| anyhow!("Node {first_node_id} is not snapped to the start"))?;
+
+        let mut result = vec![
+            TraversalSegment::build_to_neighbor(&start_node, first_neighbor)
+                .with_cost(
+                    cost_model,
+                    first_labels,
+                    first_neighbor.elevation_gain,
+                    first_neighbor.elevation_loss,
+                    0.0,
+                )
+                .build(),
+        ];
+
+        for &(n1, n2, way) in path {
+            let edges = self.db.get_neighbors_with_labels(n1)?;
+            let (neighbor, way_labels) = edges
+                .iter()
+                .find(|(neighbor, _)| neighbor.node.id == n2 && neighbor.way == way)
+                .ok_or_else(|| anyhow!("Segment {n1} -> {n2} missing from graph"))?;
+            let current = result.last().unwrap();
+            let control = self.db.get_control(n1)?;
+            let turn_cost = turn_cost(cost_model, current, neighbor, way_labels, &edges, control);
+            let segment = TraversalSegment::build_to_neighbor(&current.to, neighbor)
+                .with_depth(current.depth + 1)
+                .with_prev_distance(current.distance_so_far)
+                .with_cost(
+                    cost_model,
+                    way_labels,
+                    neighbor.elevation_gain,
+                    neighbor.elevation_loss,
+                    current.cost + turn_cost,
+                )
+                .build();
+            result.push(segment);
+        }
+
+        let current = result.last().unwrap();
+        let last_node_id = current.to.id;
+        let (last_neighbor, last_labels) = end
+            .arrivals
+            .iter()
+            .find(|(n, _)| n.node.id == last_node_id)
+            .ok_or_else(|| anyhow!("Node {last_node_id} is not snapped to the end"))?;
+        let edges = self.db.get_neighbors_with_labels(last_node_id)?;
+        let toward_end = Neighbor {
+            node: end_node,
+            ..*last_neighbor
         };
-        Ok((result.make_contiguous().to_vec(), traversal, meta))
+        let control = self.db.get_control(last_node_id)?;
+        let turn_cost = turn_cost(
+            cost_model,
+            current,
+            &toward_end,
+            last_labels,
+            &edges,
+            control,
+        );
+        let segment = TraversalSegment::build_from_neighbor(last_neighbor, &end_node)
+            .with_depth(current.depth + 1)
+            .with_prev_distance(current.distance_so_far)
+            .with_cost(
+                cost_model,
+                last_labels,
+                last_neighbor.elevation_gain,
+                last_neighbor.elevation_loss,
+                current.cost + turn_cost,
+            )
+            .build();
+        result.push(segment);
+
+        Ok(result)
+    }
+
+    /// Expands each compacted segment along a route back into the Segments it was compacted
+    /// from, renumbering their depths to match
+    pub(super) fn expand_route(&self, route: &Route) -> Result<Route, anyhow::Error> {
+        let mut expanded: Route = Vec::with_capacity(route.len());
+        for segment in route {
+            expanded.extend(self.expand_segment(segment)?);
+        }
+        let first_depth = expanded.first().map(|s| s.depth).unwrap_or_default();
+        for (i, segment) in expanded.iter_mut().enumerate() {
+            segment.depth = first_depth + i;
+        }
+        Ok(expanded)
+    }
+
+    fn expand_traversal<'a>(
+        &self,
+        segments: impl Iterator<Item = &'a TraversalSegment>,
+    ) -> Result<Traversal, anyhow::Error> {
+        let mut expanded: Traversal = vec![];
+        for segment in segments {
+            expanded.extend(self.expand_segment(segment)?);
+        }
+        Ok(expanded)
+    }
+
+    /// The Segments a compacted segment was compacted from, sharing out its cost by length and
+    /// its arrival time by distance ridden; any other segment as it is
+    fn expand_segment(
+        &self,
+        segment: &TraversalSegment,
+    ) -> Result<Vec<TraversalSegment>, anyhow::Error> {
+        let Some(shape) = segment.shape else {
+            return Ok(vec![segment.clone()]);
+        };
+        let pieces = self.db.get_shape(shape.id)?;
+
+        let total = pieces.iter().map(|p| p.distance).sum::<Distance>().max(1);
+        let start_distance = segment.distance_so_far - segment.length;
+        let mut from = segment.from;
+        let mut ridden: Distance = 0;
+        let mut cost_so_far = segment.cost_so_far;
+
+        let mut expanded = Vec::with_capacity(pieces.len());
+        for (i, piece) in pieces.iter().enumerate() {
+            ridden += piece.distance;
+            let cost = if i == pieces.len() - 1 {
+                segment.cost
+            } else {
+                segment.cost_so_far
+                    + (segment.cost - segment.cost_so_far) * ridden as Cost / total as Cost
+            };
+            expanded.push(TraversalSegment {
+                from,
+                to: piece.node,
+                way: piece.way,
+                geometry: Line::new(from.geometry, piece.node.geometry),
+                length: piece.distance,
+                distance_so_far: start_distance + ridden,
+                cost,
+                cost_so_far,
+                elevation_gain: piece.elevation_gain,
+                elevation_loss: piece.elevation_loss,
+                arrival: segment
+                    .arrival
+                    .map(|arrival| arrival - riding_time(total - ridden)),
+                shape: None,
+                ..segment.clone()
+            });
+            from = piece.node;
+            cost_so_far = cost;
+        }
+        Ok(expanded)
+    }
+
+    /// Expands the partial route a search gave up with, if any, passing any other error on
+    pub(super) fn expand_partial(&self, e: anyhow::Error) -> anyhow::Error {
+        match e.downcast::<RoutingError>() {
+            Ok(RoutingError::BudgetExceeded {
+                limit,
+                expanded,
+                cost_reached,
+                partial,
+            }) => {
+                let partial = partial.map(|partial| self.expand_route(&partial));
+                match partial.transpose() {
+                    Ok(partial) => RoutingError::BudgetExceeded {
+                        limit,
+                        expanded,
+                        cost_reached,
+                        partial,
+                    }
+                    .into(),
+                    Err(e) => e,
+                }
+            }
+            Ok(e) => e.into(),
+            Err(e) => e,
+        }
+    }
+
+    /// Snaps the point onto the nearest segment, splitting that segment into partial edges to
+    /// and from the projected point. Distance and elevation are shared out between the partial
+    /// edges in proportion to where the point falls.
+    pub(super) fn snap_waypoint(&self, point: Point) -> Result<Waypoint, anyhow::Error> {
+        if !self.bounds.intersects(&point) {
+            return Err(RoutingError::OutOfCoverage {
+                lon: point.x(),
+                lat: point.y(),
+            }
+            .into());
+        }
+
+        let snap = self.db.snap(point, None)?;
+        let (n1, edge, labels, fraction) = (snap.from, snap.edge, snap.labels, snap.fraction);
+        let (n2, way) = (edge.node, edge.way);
+
+        let reverse_labels = self
+            .db
+            .get_neighbors_with_labels(n2.id)?
+            .into_iter()
+            .find(|(n, _)| n.node.id == n1.id && n.way == -way)
+            .map(|(_, l)| l)
+            .unwrap_or(labels);
+
+        let to_n1 = snap.offset;
+        let to_n2 = edge.distance - to_n1;
+        let (gain, loss) = (edge.elevation_gain, edge.elevation_loss);
+        let partial = |node: Node, way: WayId, distance: Distance, gain: i16, loss: i16| Neighbor {
+            way,
+            node,
+            distance,
+            elevation_gain: gain,
+            elevation_loss: loss,
+            shape: None,
+        };
+
+        // riding the segment backward (on -way) swaps its climbs and descents
+        Ok(Waypoint {
+            point: snap.point,
+            segment: snap.segment(),
+            edge,
+            fraction,
+            departures: vec![
+                (
+                    partial(
+                        n2,
+                        way,
+                        to_n2,
+                        portion(gain, 1.0 - fraction),
+                        portion(loss, 1.0 - fraction),
+                    ),
+                    labels,
+                ),
+                (
+                    partial(
+                        n1,
+                        -way,
+                        to_n1,
+                        portion(loss, fraction),
+                        portion(gain, fraction),
+                    ),
+                    reverse_labels,
+                ),
+            ],
+            arrivals: vec![
+                (
+                    partial(
+                        n1,
+                        way,
+                        to_n1,
+                        portion(gain, fraction),
+                        portion(loss, fraction),
+                    ),
+                    labels,
+                ),
+                (
+                    partial(
+                        n2,
+                        -way,
+                        to_n2,
+                        portion(loss, 1.0 - fraction),
+                        portion(gain, 1.0 - fraction),
+                    ),
+                    reverse_labels,
+                ),
+            ],
+        })
     }
 
     /// Look up street names for the given way IDs
@@ -78,6 +744,26 @@ impl Graph {
         self.db.get_way_names(way_ids)
     }
 
+    /// Look up signed bike route names for the given way IDs
+    pub fn get_route_names(
+        &self,
+        way_ids: &[WayId],
+    ) -> Result<HashMap<WayId, String>, anyhow::Error> {
+        self.db.get_route_names(way_ids)
+    }
+
+    /// Validates the cost model (or the default one) and resolves any rules it has over Way
+    /// names, once for all of the searches a request runs with it
+    pub(super) fn prepare_cost_model(
+        &self,
+        cost_model: Option<CostModel>,
+    ) -> Result<CostModel, anyhow::Error> {
+        let mut cost_model = cost_model.unwrap_or_default();
+        cost_model.validate()?;
+        cost_model.resolve_rules(&*self.db)?;
+        Ok(cost_model)
+    }
+
     /// Generates a breadth-first traversal from the start point to the depth specified
     pub fn calculate_traversal(
         &self,
@@ -86,6 +772,8 @@ impl Graph {
         cost_model: Option<CostModel>,
         heuristic_weight: Option<Weight>,
     ) -> Result<Traversal, anyhow::Error> {
+        let cost_model = self.prepare_cost_model(cost_model)?;
+        let start = self.snap_waypoint(start)?;
         let mut context = self.initialize_traversal(&start, cost_model, heuristic_weight)?;
 
         self.traverse_from(&mut context, max_depth)?;
@@ -93,3 +781,145 @@ impl Graph {
         Ok(context.came_from.values().cloned().collect())
     }
 }
+
+/// Follows `edge_parents` back from the cheapest edge arriving at `node_id`, for routes found by an
+/// edge-based search (which may pass through the same Node more than once)
+fn trace_back_edges(context: &TraversalContext, node_id: NodeId) -> Route {
+    let mut key = *context
+        .came_from_edge
+        .iter()
+        .filter(|(key, _)| key.1 == node_id)
+        .min_by(|(_, a), (_, b)| a.cost.total_cmp(&b.cost))
+        .map(|(key, _)| key)
+        .unwrap();
+    let mut result: VecDeque<TraversalSegment> = VecDeque::new();
+
+    loop {
+        let segment = context.came_from_edge.get(&key).unwrap();
+        result.push_front(segment.clone());
+        if segment.from.id == START_NODE_ID {
+            break;
+        }
+        key = *context.edge_parents.get(&key).unwrap();
+    }
+
+    result.into()
+}
+
+/// The bounding box around every Way, from the extents of the Ways R*tree
+fn load_bounds(conn: &DBConnection) -> Result<Rect, anyhow::Error> {
+    let (min_lon, max_lon, min_lat, max_lat): (f64, f64, f64, f64) = conn.query_row(
+        "SELECT min(minLon), max(maxLon), min(minLat), max(maxLat) FROM Ways",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )?;
+    Ok(Rect::new((min_lon, min_lat), (max_lon, max_lat)))
+}
+
+/// The share of a segment's elevation change covered by `fraction` of it. Negative values mark
+/// unknown elevation, which stays unknown.
+fn portion(elevation: i16, fraction: f64) -> i16 {
+    if elevation < 0 {
+        elevation
+    } else {
+        (elevation as f64 * fraction).round() as i16
+    }
+}
+
+/// When both points lie along the same segment, the single partial edge running from one to the
+/// other, in whichever direction that is
+fn direct_segment(
+    start: &Waypoint,
+    end: &Waypoint,
+    cost_model: &CostModel,
+) -> Option<TraversalSegment> {
+    let (n1, n2, _) = start.segment;
+    // where the end falls, measured along the start's segment
+    let end_fraction = match end.segment {
+        (e1, e2, _) if (e1, e2) == (n1, n2) => end.fraction,
+        (e1, e2, _) if (e1, e2) == (n2, n1) => 1.0 - end.fraction,
+        _ => return None,
+    };
+
+    // departures[0] runs along the segment toward n2, departures[1] back toward n1, which
+    // swaps the segment's climbs and descents
+    let edge = &start.edge;
+    let ((neighbor, way_labels), gain, loss) = if end_fraction >= start.fraction {
+        (
+            &start.departures[0],
+            edge.elevation_gain,
+            edge.elevation_loss,
+        )
+    } else {
+        (
+            &start.departures[1],
+            edge.elevation_loss,
+            edge.elevation_gain,
+        )
+    };
+    // shared out from the whole segment, rather than from the already rounded partial edge
+    let share = (end_fraction - start.fraction).abs();
+
+    let direct = Neighbor {
+        node: Node::new(START_NODE_ID, &start.point),
+        distance: (edge.distance as f64 * share).round() as Distance,
+        elevation_gain: portion(gain, share),
+        elevation_loss: portion(loss, share),
+        ..*neighbor
+    };
+    let end_node = Node::new(END_NODE_ID, &end.point);
+    Some(
+        TraversalSegment::build_from_neighbor(&direct, &end_node)
+            .with_cost(
+                cost_model,
+                way_labels,
+                direct.elevation_gain,
+                direct.elevation_loss,
+                0.0,
+            )
+            .build(),
+    )
+}
+
+/// Appends the next leg onto a route, continuing to accumulate cost, distance and depth from
+/// where the previous leg left off
+pub(super) fn append_leg(route: &mut Route, leg_route: Route, leg: usize) {
+    let (cost_offset, distance_offset, depth_offset) = route
+        .last()
+        .map(|s| (leg_total_cost(route), s.distance_so_far, s.depth + 1))
+        .unwrap_or_default();
+    route.extend(leg_route.into_iter().map(|mut segment| {
+        segment.leg = leg;
+        segment.cost += cost_offset;
+        segment.cost_so_far += cost_offset;
+        segment.distance_so_far += distance_offset;
+        segment.depth += depth_offset;
+        segment
+    }));
+}
+
+/// The accumulated cost of a route, including the partial edge it arrives along into the virtual
+/// end node.
+pub(super) fn leg_total_cost(route: &[TraversalSegment]) -> Cost {
+    route.iter().map(|s| s.cost).fold(0.0, Cost::max)
+}
+
+/// Follows `came_from` back from `node_id` to the start, returning the segments in route order
+pub(super) fn trace_back(context: &TraversalContext, node_id: NodeId) -> Route {
+    if context.is_edge_based() {
+        return trace_back_edges(context, node_id);
+    }
+
+    let mut current_segment = context.came_from.get(&node_id).unwrap();
+    let mut result: VecDeque<TraversalSegment> = VecDeque::from([current_segment.clone()]);
+
+    loop {
+        if current_segment.from.id == START_NODE_ID {
+            break;
+        }
+        current_segment = context.came_from.get(&current_segment.from.id).unwrap();
+        result.push_front(current_segment.clone());
+    }
+
+    result.into()
+}
@@ -1,4 +1,5 @@
-use crate::osm::{Cycleway, Distance, Road, WayLabels};
+use super::{CostRule, CostRules, GraphRepository, RoutingError};
+use crate::osm::{BikeNetwork, Control, Cycleway, Distance, Lts, Road, Surface, WayId, WayLabels};
 use serde::{Deserialize, Serializer};
 use std::collections::HashMap;
 
@@ -46,14 +47,84 @@ struct CostModelInput {
     distance_coefficient: Cost,
     #[serde(default)]
     elevation_coefficient: Cost,
+    #[serde(default)]
+    bike_route_coefficient: Cost,
+    #[serde(default)]
+    surface_coefficient: Cost,
+    #[serde(default)]
+    lts_coefficient: Cost,
     cycleway_weights: HashMap<Cycleway, Cost>,
     road_weights: HashMap<Road, Cost>,
+    #[serde(default)]
+    surface_weights: HashMap<Surface, Cost>,
+    #[serde(default)]
+    lts_weights: HashMap<Lts, Cost>,
+    #[serde(default)]
+    turn_costs: TurnCosts,
+    #[serde(default)]
+    intersection_delays: IntersectionDelays,
+    #[serde(default)]
+    closed_penalty: Option<Cost>,
+    #[serde(default)]
+    max_lts: Option<Lts>,
+    #[serde(default)]
+    rules: Vec<CostRule>,
+}
+
+/// The maneuver made when passing through a Node, from one segment onto the next
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Turn {
+    Straight,
+    Right,
+    Left,
+    UTurn,
+}
+
+impl Turn {
+    /// Classifies the maneuver between two compass bearings, in degrees
+    pub fn from_bearings(incoming: f64, outgoing: f64) -> Self {
+        // signed change in heading, in (-180, 180], positive when turning clockwise (right)
+        let delta = (outgoing - incoming + 540.0) % 360.0 - 180.0;
+        match delta {
+            d if d.abs() < 30.0 => Turn::Straight,
+            d if d.abs() > 150.0 => Turn::UTurn,
+            d if d > 0.0 => Turn::Right,
+            _ => Turn::Left,
+        }
+    }
+}
+
+/// Flat penalties added to the accumulated cost for each maneuver, in the same units as a
+/// segment's cost (roughly: meters of a neutral street)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct TurnCosts {
+    pub left: Cost,
+    pub right: Cost,
+    pub u_turn: Cost,
+    /// going straight or left across a Road of a higher class than both the one being left and
+    /// the one being joined
+    pub cross_major: Cost,
+}
+
+/// Flat penalties added to the accumulated cost for passing through a Node, for the time spent
+/// waiting there, in the same units as turn costs
+#[derive(Debug, Default, Copy, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct IntersectionDelays {
+    /// passing through traffic signals
+    pub signal: Cost,
+    /// passing a stop sign
+    pub stop: Cost,
+    /// going straight or left across an Arterial where there are no signals, waiting for a gap
+    /// in its traffic
+    pub arterial_crossing: Cost,
 }
 
 /// Cost model with array-backed weight lookups.
 /// Cycleway and Road are #[repr(u8)] enums, so weights[variant as usize] is a direct
 /// array index — no HashMap overhead in the hot path.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct CostModel {
     cycleway_coefficient: Cost,
     road_coefficient: Cost,
@@ -63,10 +134,33 @@ pub struct CostModel {
     distance_coefficient: Cost,
     /// Controls hill avoidance. 0.0 = ignore elevation, higher = avoid hills more.
     elevation_coefficient: Cost,
+    /// The fraction of a segment's cost taken off while riding a signed bike route, of any
+    /// network. 0.0 = routes don't matter, approaching 1.0 = stick to routes at nearly any cost.
+    bike_route_coefficient: Cost,
+    surface_coefficient: Cost,
+    lts_coefficient: Cost,
     /// Indexed by Cycleway discriminant (No=0, Shared=1, Lane=2, Track=3)
     cycleway_weights: [Cost; 4],
     /// Indexed by Road discriminant (Pedestrian=0, Bike=1, Local=2, Collector=3, Arterial=4)
     road_weights: [Cost; 5],
+    /// Indexed by Surface discriminant (Paved=0, Rough=1, Cobbled=2, Unpaved=3)
+    surface_weights: [Cost; 4],
+    /// Indexed by Lts discriminant, less one (Lts1=0, Lts2=1, Lts3=2, Lts4=3)
+    lts_weights: [Cost; 4],
+    /// Penalties for maneuvers at intersections. All zero by default, and when all zero, routing
+    /// can stay node-based (and so use bidirectional search and contraction hierarchies).
+    turn_costs: TurnCosts,
+    /// Penalties for passing through Nodes with traffic control devices, or across Arterials.
+    /// Like turn costs, they're all zero by default and require edge-based routing otherwise.
+    intersection_delays: IntersectionDelays,
+    /// Cost multiplier for riding a Way while a time-based restriction has it closed, when
+    /// routing with a departure time. None avoids closed Ways altogether.
+    closed_penalty: Option<Cost>,
+    /// The most stressful Ways a route may ride, other than to reach its start or leave its end.
+    /// None rides any.
+    max_lts: Option<Lts>,
+    /// Multipliers for Ways meeting the conditions of custom rules, applied on top of the rest
+    rules: CostRules,
     /// When true, invert the salmon flag during cost calculation.
     /// Used for backward exploration where the traversal direction is
     /// opposite to the cyclist's actual direction of travel.
@@ -85,14 +179,34 @@ impl From<CostModelInput> for CostModel {
             road_weights[*variant as usize] = *weight;
         }
 
+        let mut surface_weights = [0.0f32; 4];
+        for (variant, weight) in &input.surface_weights {
+            surface_weights[*variant as usize] = *weight;
+        }
+
+        let mut lts_weights = [0.0f32; 4];
+        for (variant, weight) in &input.lts_weights {
+            lts_weights[*variant as usize - 1] = *weight;
+        }
+
         Self {
             cycleway_coefficient: input.cycleway_coefficient,
             road_coefficient: input.road_coefficient,
             salmon_coefficient: input.salmon_coefficient,
             distance_coefficient: input.distance_coefficient,
             elevation_coefficient: input.elevation_coefficient,
+            bike_route_coefficient: input.bike_route_coefficient,
+            surface_coefficient: input.surface_coefficient,
+            lts_coefficient: input.lts_coefficient,
             cycleway_weights,
             road_weights,
+            surface_weights,
+            lts_weights,
+            turn_costs: input.turn_costs,
+            intersection_delays: input.intersection_delays,
+            closed_penalty: input.closed_penalty,
+            max_lts: input.max_lts,
+            rules: CostRules::new(input.rules),
             reverse_salmon: false,
         }
     }
@@ -119,23 +233,180 @@ impl Default for CostModel {
         road_weights[Road::Collector as usize] = 1.4;
         road_weights[Road::Arterial as usize] = 2.0;
 
+        let mut surface_weights = [0.0f32; 4];
+        surface_weights[Surface::Paved as usize] = 0.0;
+        surface_weights[Surface::Rough as usize] = 0.5;
+        surface_weights[Surface::Cobbled as usize] = 1.5;
+        surface_weights[Surface::Unpaved as usize] = 2.0;
+
         Self {
             cycleway_coefficient: 0.3,
             road_coefficient: 0.4,
             salmon_coefficient: 1.3,
             distance_coefficient: 0.0,
             elevation_coefficient: 0.0,
+            bike_route_coefficient: 0.0,
+            surface_coefficient: 0.3,
+            lts_coefficient: 0.0,
             cycleway_weights,
             road_weights,
+            surface_weights,
+            lts_weights: LTS_WEIGHTS,
+            turn_costs: TurnCosts::default(),
+            intersection_delays: IntersectionDelays::default(),
+            closed_penalty: None,
+            max_lts: None,
+            rules: CostRules::default(),
             reverse_salmon: false,
         }
     }
 }
 
 impl CostModel {
+    /// Checks that every coefficient, weight, turn cost and delay is a finite, non-negative number, that
+    /// signed bike routes keep some of their cost, and that any penalty for closed Ways is at
+    /// least 1.
+    /// A negative cost would let a search lower a route's cost by riding further.
+    pub fn validate(&self) -> Result<(), RoutingError> {
+        let TurnCosts {
+            left,
+            right,
+            u_turn,
+            cross_major,
+        } = self.turn_costs;
+        let IntersectionDelays {
+            signal,
+            stop,
+            arterial_crossing,
+        } = self.intersection_delays;
+        let named = [
+            ("cycleway_coefficient", self.cycleway_coefficient),
+            ("road_coefficient", self.road_coefficient),
+            ("salmon_coefficient", self.salmon_coefficient),
+            ("distance_coefficient", self.distance_coefficient),
+            ("elevation_coefficient", self.elevation_coefficient),
+            ("bike_route_coefficient", self.bike_route_coefficient),
+            ("surface_coefficient", self.surface_coefficient),
+            ("lts_coefficient", self.lts_coefficient),
+            ("turn_costs.left", left),
+            ("turn_costs.right", right),
+            ("turn_costs.u_turn", u_turn),
+            ("turn_costs.cross_major", cross_major),
+            ("intersection_delays.signal", signal),
+            ("intersection_delays.stop", stop),
+            ("intersection_delays.arterial_crossing", arterial_crossing),
+        ];
+        let weights = self
+            .cycleway_weights
+            .iter()
+            .map(|w| ("cycleway_weights", *w))
+            .chain(self.road_weights.iter().map(|w| ("road_weights", *w)))
+            .chain(self.surface_weights.iter().map(|w| ("surface_weights", *w)))
+            .chain(self.lts_weights.iter().map(|w| ("lts_weights", *w)));
+
+        match named
+            .into_iter()
+            .chain(weights)
+            .find(|(_, value)| !value.is_finite() || *value < 0.0)
+        {
+            Some((name, value)) => Err(RoutingError::InvalidCostModel {
+                reason: format!("{name} must be a non-negative number, got {value}"),
+            }),
+            // a free ride along a route would leave the search nothing to tell routes apart by
+            None if self.bike_route_coefficient >= 1.0 => Err(RoutingError::InvalidCostModel {
+                reason: format!(
+                    "bike_route_coefficient must be less than 1, got {}",
+                    self.bike_route_coefficient
+                ),
+            }),
+            // a closed Way mustn't come out cheaper than it would be if it were open
+            None => match self.closed_penalty {
+                Some(penalty) if !penalty.is_finite() || penalty < 1.0 => {
+                    Err(RoutingError::InvalidCostModel {
+                        reason: format!("closed_penalty must be at least 1, got {penalty}"),
+                    })
+                }
+                _ => self.rules.validate(),
+            },
+        }
+    }
+
+    /// Finds the Ways any of this model's rules match by name, so routing only has to look them
+    /// up. Until then, name rules apply nowhere.
+    pub fn resolve_rules(&mut self, db: &dyn GraphRepository) -> Result<(), anyhow::Error> {
+        self.rules.resolve_names(db)
+    }
+
+    /// the multiplier for riding a closed Way, if closed Ways may be ridden at all
+    pub fn closed_penalty(&self) -> Option<Cost> {
+        self.closed_penalty
+    }
+
+    /// the most stressful Ways a route may ride, if capped at all
+    pub fn max_lts(&self) -> Option<Lts> {
+        self.max_lts
+    }
+
+    /// whether a route may ride a Way with these labels, given the cap on its traffic stress
+    #[inline]
+    pub fn allows(&self, way_labels: &WayLabels) -> bool {
+        self.max_lts.is_none_or(|max| way_labels.5 <= max)
+    }
+
+    /// whether any maneuver or intersection is penalized, requiring routing to be edge-based
+    pub fn has_turn_costs(&self) -> bool {
+        self.turn_costs != TurnCosts::default()
+            || self.intersection_delays != IntersectionDelays::default()
+    }
+
+    /// Flat cost of making the `turn`, optionally while crossing a higher-class Road
+    #[inline]
+    pub fn calculate_turn_cost(&self, turn: Turn, crosses_major: bool) -> Cost {
+        let turn_cost = match turn {
+            Turn::Straight => 0.0,
+            Turn::Right => self.turn_costs.right,
+            Turn::Left => self.turn_costs.left,
+            Turn::UTurn => self.turn_costs.u_turn,
+        };
+        let crossing_cost = if crosses_major && matches!(turn, Turn::Straight | Turn::Left) {
+            self.turn_costs.cross_major
+        } else {
+            0.0
+        };
+        turn_cost + crossing_cost
+    }
+
+    /// Flat cost of waiting to make the `turn` through a Node with the `control`, optionally
+    /// across an Arterial. Signals stop all traffic, so nothing more is waited for to cross.
+    #[inline]
+    pub fn calculate_intersection_delay(
+        &self,
+        control: Control,
+        turn: Turn,
+        crosses_arterial: bool,
+    ) -> Cost {
+        let delays = &self.intersection_delays;
+        match control {
+            Control::Signals => delays.signal,
+            _ => {
+                let stop = if control == Control::Stop {
+                    delays.stop
+                } else {
+                    0.0
+                };
+                let crossing = if crosses_arterial && matches!(turn, Turn::Straight | Turn::Left) {
+                    delays.arterial_crossing
+                } else {
+                    0.0
+                };
+                stop + crossing
+            }
+        }
+    }
+
     #[inline]
     pub fn calculate_cost(&self, way_labels: &WayLabels) -> Cost {
-        let (cycleway, road, salmon) = way_labels;
+        let (cycleway, road, salmon, network, surface, lts) = way_labels;
         let salmon_flag = if self.reverse_salmon {
             !*salmon
         } else {
@@ -143,12 +414,35 @@ impl CostModel {
         };
         let cycleway_cost = self.cycleway_coefficient * self.cycleway_weights[*cycleway as usize];
         let road_cost = self.road_coefficient * self.road_weights[*road as usize];
+        let surface_cost = self.surface_coefficient * self.surface_weights[*surface as usize];
+        let lts_cost = self.lts_coefficient * self.lts_weights[*lts as usize - 1];
         let salmon_cost = if salmon_flag {
             self.salmon_coefficient
         } else {
             1.0
         };
-        (cycleway_cost + road_cost + self.distance_coefficient) * salmon_cost
+        let route_discount = if *network == BikeNetwork::None {
+            1.0
+        } else {
+            1.0 - self.bike_route_coefficient
+        };
+        (cycleway_cost + road_cost + surface_cost + lts_cost + self.distance_coefficient)
+            * salmon_cost
+            * route_discount
+            * self.rules.label_multiplier(way_labels, salmon_flag)
+    }
+
+    /// The cost of riding the Way, which unlike `calculate_cost` takes in any rules matching
+    /// Ways by name
+    #[inline]
+    pub fn calculate_way_cost(&self, way: WayId, way_labels: &WayLabels) -> Cost {
+        let cost = self.calculate_cost(way_labels);
+        if self.rules.is_empty() {
+            return cost;
+        }
+        cost * self
+            .rules
+            .name_multiplier(way, way_labels, way_labels.2 != self.reverse_salmon)
     }
 
     /// Dimensionless elevation multiplier for a segment.
@@ -224,6 +518,12 @@ const COMFORT_PROFILE: CostModelProfile = CostModelProfile {
     road_weights: [0.9, 0.5, 1.2, 1.4, 2.0], // Ped, Bike, Local, Collector, Arterial
 };
 
+/// How much rougher surfaces cost than pavement, scaled by the mobile surface tolerance
+const SURFACE_WEIGHTS: [Cost; 4] = [0.0, 0.5, 1.5, 2.0]; // Paved, Rough, Cobbled, Unpaved
+
+/// How much more stressful streets cost, for models weighing traffic stress at all
+const LTS_WEIGHTS: [Cost; 4] = [0.0, 0.5, 1.5, 3.0]; // Lts1, Lts2, Lts3, Lts4
+
 struct CostModelProfile {
     cycleway_coefficient: Cost,
     road_coefficient: Cost,
@@ -245,9 +545,47 @@ pub struct MobileCostModel {
     /// When true, further penalizes arterials and collectors
     #[serde(default)]
     avoid_major_roads: bool,
+    /// 0 = ignore turns and waits at intersections, 1 = avoid, 2 = strongly avoid
+    #[serde(default)]
+    turn_penalty: u8,
+    /// 0 = stick to pavement, 1 = tolerate some rough surfaces, 2 = ride any surface
+    #[serde(default = "default_surface_tolerance")]
+    surface_tolerance: u8,
+    /// The most stressful Ways to ride, from 1 (suitable for children) to 4 (any street)
+    #[serde(default)]
+    max_lts: Option<Lts>,
+    /// Custom rules, applied on top of the model the controls above resolve to
+    #[serde(default)]
+    rules: Vec<CostRule>,
+}
+
+fn default_surface_tolerance() -> u8 {
+    1
 }
 
 impl MobileCostModel {
+    /// The pure-speed end of the priority slider, with the default hill and salmon settings
+    pub fn speed() -> Self {
+        Self {
+            priority: 0.0,
+            hill_penalty: 0,
+            salmon_penalty: 1,
+            avoid_major_roads: false,
+            turn_penalty: 0,
+            surface_tolerance: default_surface_tolerance(),
+            max_lts: None,
+            rules: vec![],
+        }
+    }
+
+    /// The pure-comfort end of the priority slider, with the default hill and salmon settings
+    pub fn comfort() -> Self {
+        Self {
+            priority: 1.0,
+            ..Self::speed()
+        }
+    }
+
     /// Resolve this high-level model into the concrete CostModel used by A*.
     pub fn resolve(self) -> CostModel {
         let t = self.priority.clamp(0.0, 1.0);
@@ -297,14 +635,60 @@ impl MobileCostModel {
             _ => 2.5,
         };
 
+        let surface_coefficient = match self.surface_tolerance {
+            0 => 0.8,
+            1 => 0.3,
+            _ => 0.0,
+        };
+
+        let (turn_costs, intersection_delays) = match self.turn_penalty {
+            0 => (TurnCosts::default(), IntersectionDelays::default()),
+            1 => (
+                TurnCosts {
+                    left: 15.0,
+                    right: 5.0,
+                    u_turn: 60.0,
+                    cross_major: 20.0,
+                },
+                IntersectionDelays {
+                    signal: 40.0,
+                    stop: 15.0,
+                    arterial_crossing: 60.0,
+                },
+            ),
+            _ => (
+                TurnCosts {
+                    left: 40.0,
+                    right: 10.0,
+                    u_turn: 150.0,
+                    cross_major: 50.0,
+                },
+                IntersectionDelays {
+                    signal: 100.0,
+                    stop: 40.0,
+                    arterial_crossing: 150.0,
+                },
+            ),
+        };
+
         CostModel {
             cycleway_coefficient,
             road_coefficient,
             salmon_coefficient,
             distance_coefficient,
             elevation_coefficient,
+            bike_route_coefficient: 0.0,
+            surface_coefficient,
+            lts_coefficient: 0.0,
             cycleway_weights,
             road_weights,
+            surface_weights: SURFACE_WEIGHTS,
+            lts_weights: LTS_WEIGHTS,
+            turn_costs,
+            intersection_delays,
+            closed_penalty: None,
+            max_lts: self.max_lts,
+            rules: CostRules::new(self.rules),
             reverse_salmon: false,
         }
     }
@@ -0,0 +1,63 @@
+/// The ways a routing request can fail which are the fault of the request rather than the
+/// router, so that they can be reported back distinctly (e.g. "you clicked in the river").
+/// These are raised wrapped in an anyhow::Error; anything else is an internal failure.
+use super::{BudgetLimit, Cost, Route};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RoutingError {
+    /// the point is within the graph's coverage, but not near any routable street
+    #[error("Could not snap coords to graph near ({lon}, {lat})")]
+    SnapFailed { lon: f64, lat: f64 },
+    /// the point is outside the area the graph covers
+    #[error("({lon}, {lat}) is outside the area covered by the graph")]
+    OutOfCoverage { lon: f64, lat: f64 },
+    /// the points snapped fine, but there's no way to ride between them
+    #[error("No path found between the requested points")]
+    NoPathFound,
+    #[error("Invalid cost model: {reason}")]
+    InvalidCostModel { reason: String },
+    /// the request itself is malformed: missing, unparseable, or out of range
+    #[error("Invalid request: {reason}")]
+    InvalidRequest { reason: String },
+    /// the search gave up before finding its target, having expanded `expanded` Nodes out to a
+    /// cost of `cost_reached`. `partial` is the path to wherever it got closest to the target.
+    #[error("Search {limit} after expanding {expanded} nodes, reaching cost {cost_reached}")]
+    BudgetExceeded {
+        limit: BudgetLimit,
+        expanded: usize,
+        cost_reached: Cost,
+        partial: Option<Route>,
+    },
+}
+
+impl RoutingError {
+    /// A stable, machine-readable name for this kind of error
+    pub fn code(&self) -> &'static str {
+        match self {
+            RoutingError::SnapFailed { .. } => "snap_failed",
+            RoutingError::OutOfCoverage { .. } => "out_of_coverage",
+            RoutingError::NoPathFound => "no_path_found",
+            RoutingError::InvalidCostModel { .. } => "invalid_cost_model",
+            RoutingError::InvalidRequest { .. } => "invalid_request",
+            RoutingError::BudgetExceeded { .. } => "budget_exceeded",
+        }
+    }
+
+    /// The HTTP status to report this error with: 4xx for requests the router can't satisfy,
+    /// and 503 for searches which gave up before they could
+    pub fn status(&self) -> u16 {
+        match self {
+            RoutingError::InvalidRequest { .. } | RoutingError::InvalidCostModel { .. } => 400,
+            RoutingError::NoPathFound => 404,
+            RoutingError::SnapFailed { .. } | RoutingError::OutOfCoverage { .. } => 422,
+            RoutingError::BudgetExceeded { .. } => 503,
+        }
+    }
+
+    pub fn invalid_request(reason: impl Into<String>) -> Self {
+        RoutingError::InvalidRequest {
+            reason: reason.into(),
+        }
+    }
+}
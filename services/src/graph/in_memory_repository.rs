@@ -1,53 +1,138 @@
-use super::repository::GraphRepository;
+use super::repository::{
+    load_controls, load_restrictions, load_turn_restrictions, GraphRepository,
+};
+use super::spatial::SpatialIndex;
 use crate::db::{self, DBConnection};
-use crate::osm::{Distance, Neighbor, Node, NodeId, WayId, WayLabels};
+use crate::osm::{
+    Control, Distance, EdgeShape, Neighbor, Node, NodeId, Restriction, ShapeId, TurnRestriction,
+    WayId, WayLabels,
+};
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
 use geo::Point;
 use std::collections::{HashMap, HashSet};
 use tracing::info;
 
 /// A compact edge in the adjacency list
 #[derive(Clone)]
-struct InMemoryEdge {
-    way: WayId,
-    node: Node,
-    distance: Distance,
-    labels: WayLabels,
-    elevation_gain: i16,
-    elevation_loss: i16,
+pub(super) struct InMemoryEdge {
+    pub(super) way: WayId,
+    pub(super) node: Node,
+    pub(super) distance: Distance,
+    pub(super) labels: WayLabels,
+    pub(super) elevation_gain: i16,
+    pub(super) elevation_loss: i16,
+    pub(super) shape: Option<EdgeShape>,
+}
+
+impl InMemoryEdge {
+    pub(super) fn neighbor(&self) -> Neighbor {
+        Neighbor {
+            way: self.way,
+            node: self.node,
+            distance: self.distance,
+            elevation_gain: self.elevation_gain,
+            elevation_loss: self.elevation_loss,
+            shape: self.shape,
+        }
+    }
+
+    /// this edge ridden the other way, arriving at the Node it leaves from
+    pub(super) fn mirrored(&self, shape: Option<EdgeShape>) -> Neighbor {
+        Neighbor {
+            way: -self.way,
+            node: self.node,
+            distance: self.distance,
+            elevation_gain: self.elevation_loss,
+            elevation_loss: self.elevation_gain,
+            shape,
+        }
+    }
+}
+
+/// A chain of Segments compacted into one edge, in one direction
+pub(super) struct Shape {
+    /// the Segments in the chain, in order, each as the Neighbor it leads to
+    pub(super) segments: Vec<Neighbor>,
+    /// the same chain, ridden the other way
+    pub(super) reverse: ShapeId,
 }
 
 /// In-memory graph repository: loads Segments + Nodes + WayLabels into a HashMap at startup,
 /// eliminating per-expansion SQL queries from the A* hot loop.
 ///
-/// R*Tree snapping queries (called only 2× per route) are delegated to a retained SQLite
-/// connection so spatial indexing is preserved.
+/// Way bounding boxes are bucketed into an in-memory grid for snapping, so that no SQLite
+/// connection is held once the graph is loaded.
 pub struct InMemoryGraphRepository {
-    /// Retained SQLite connection for R*Tree-backed snapping queries only
-    snap_db: DBConnection,
+    /// Way bounding boxes and Nodes, for snapping queries
+    pub(super) spatial: SpatialIndex,
     /// Adjacency list: NodeId → outgoing edges (with labels pre-joined)
-    adjacency: HashMap<NodeId, Vec<InMemoryEdge>>,
+    pub(super) adjacency: HashMap<NodeId, Vec<InMemoryEdge>>,
     /// Way ID → street name, loaded at startup for navigation serialization
-    way_names: HashMap<WayId, String>,
+    pub(super) way_names: HashMap<WayId, String>,
+    /// Way ID → the names of the signed bike routes along it, for the Ways on any
+    pub(super) route_names: HashMap<WayId, String>,
+    /// Way ID → labels, for both the OSM-normal (positive) and reverse (negative) Ways
+    pub(super) way_labels: HashMap<WayId, WayLabels>,
+    /// positive Way ID → the schedules on which it opens or closes, for the few Ways that have any
+    pub(super) restrictions: HashMap<WayId, Vec<Restriction>>,
+    /// Node ID → the turn restrictions at it, for the few Nodes that have any
+    pub(super) turn_restrictions: HashMap<NodeId, Vec<TurnRestriction>>,
+    /// Node ID → the traffic control device at it, for the few Nodes that have one
+    pub(super) controls: HashMap<NodeId, Control>,
+    /// the adjacency list with chains of Segments compacted into single edges
+    pub(super) compacted: Compacted,
+}
+
+/// The adjacency list with every chain of Nodes that only continue a Way compacted into a single
+/// edge, in each direction, and the chains behind those edges
+#[derive(Default)]
+pub(super) struct Compacted {
+    /// Node ID → edges, for the Nodes that start any chain. Nodes along chains, and those starting
+    /// none, are only in the full adjacency list.
+    pub(super) edges: HashMap<NodeId, Vec<InMemoryEdge>>,
+    /// indexed by ShapeId
+    pub(super) shapes: Vec<Shape>,
+    /// Node ID → the chain it lies along, in either direction, for every Node along one
+    pub(super) shapes_through: HashMap<NodeId, [ShapeId; 2]>,
 }
 
 impl InMemoryGraphRepository {
     pub fn new() -> Result<Self, anyhow::Error> {
-        let snap_db = db::get_conn()?;
         let load_conn = db::get_conn()?;
 
         info!("Loading graph into memory...");
         let adjacency = Self::load_adjacency(&load_conn)?;
         let way_names = Self::load_way_names(&load_conn)?;
+        let route_names = Self::load_route_names(&load_conn)?;
+        let way_labels = Self::load_way_labels(&load_conn)?;
+        let restrictions = load_restrictions(&load_conn)?;
+        let turn_restrictions = load_turn_restrictions(&load_conn)?;
+        let controls = load_controls(&load_conn)?;
+        let spatial = SpatialIndex::load(&load_conn)?;
+        let compacted = compact(&adjacency, &turn_restrictions, &controls);
         info!(
-            "Graph loaded: {} nodes in adjacency list, {} way names",
+            "Graph loaded: {} nodes in adjacency list, {} way names, {} restricted ways, {} restricted turns, {} controlled nodes, {} chains compacted over {} nodes, {} ways indexed",
             adjacency.len(),
-            way_names.len()
+            way_names.len(),
+            restrictions.len(),
+            turn_restrictions.values().map(Vec::len).sum::<usize>(),
+            controls.len(),
+            compacted.shapes.len() / 2,
+            compacted.shapes_through.len(),
+            spatial.ways.len()
         );
 
         Ok(Self {
-            snap_db,
+            spatial,
             adjacency,
             way_names,
+            route_names,
+            way_labels,
+            restrictions,
+            turn_restrictions,
+            controls,
+            compacted,
         })
     }
 
@@ -58,7 +143,7 @@ impl InMemoryGraphRepository {
     ) -> Result<HashMap<NodeId, Vec<InMemoryEdge>>, anyhow::Error> {
         let mut stmt = conn.prepare(
             "
-            SELECT S.n1, S.way, S.n2, N2.lon, N2.lat, S.distance, WL.cycleway, WL.road, WL.salmon, S.elevation_gain, S.elevation_loss
+            SELECT S.n1, S.way, S.n2, N2.lon, N2.lat, S.distance, WL.cycleway, WL.road, WL.salmon, WL.network, WL.surface, WL.lts, S.elevation_gain, S.elevation_loss
             FROM Segments S
             JOIN Nodes N2 ON S.n2 = N2.id
             JOIN WayLabels WL ON S.way = WL.id
@@ -74,9 +159,17 @@ impl InMemoryGraphRepository {
                 way: row.get(1)?,
                 node: Node::new(row.get(2)?, &Point::new(row.get(3)?, row.get(4)?)),
                 distance: row.get(5)?,
-                labels: (row.get(6)?, row.get(7)?, row.get(8)?),
-                elevation_gain: row.get(9)?,
-                elevation_loss: row.get(10)?,
+                labels: (
+                    row.get(6)?,
+                    row.get(7)?,
+                    row.get(8)?,
+                    row.get(9)?,
+                    row.get(10)?,
+                    row.get(11)?,
+                ),
+                elevation_gain: row.get(12)?,
+                elevation_loss: row.get(13)?,
+                shape: None,
             };
             adjacency.entry(n1).or_default().push(edge);
         }
@@ -94,45 +187,85 @@ impl InMemoryGraphRepository {
         }
         Ok(names)
     }
+
+    /// Load bike route names from WayLabels table into a HashMap for navigation lookups.
+    fn load_route_names(conn: &DBConnection) -> Result<HashMap<WayId, String>, anyhow::Error> {
+        let mut stmt = conn.prepare("SELECT id, route FROM WayLabels WHERE route != ''")?;
+        let mut names = HashMap::new();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            names.insert(row.get(0)?, row.get(1)?);
+        }
+        Ok(names)
+    }
+
+    /// Load labels for every directed Way, so reversed edges can be re-labeled without a query.
+    fn load_way_labels(conn: &DBConnection) -> Result<HashMap<WayId, WayLabels>, anyhow::Error> {
+        let mut stmt = conn
+            .prepare("SELECT id, cycleway, road, salmon, network, surface, lts FROM WayLabels")?;
+        let mut labels = HashMap::new();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            labels.insert(
+                row.get(0)?,
+                (
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                ),
+            );
+        }
+        Ok(labels)
+    }
+
+    /// The chains through any of the Nodes
+    fn shapes_through_any(&self, nodes: &[NodeId]) -> Vec<ShapeId> {
+        nodes
+            .iter()
+            .filter_map(|id| self.compacted.shapes_through.get(id))
+            .flatten()
+            .copied()
+            .collect()
+    }
 }
 
 impl GraphRepository for InMemoryGraphRepository {
-    /// Delegate to SQLite — uses R*Tree spatial index, only called 2× per route
-    fn get_snapped_neighbors(
+    fn get_way_nodes_near(
         &self,
         center: Point,
-        snap_radius: Option<f64>,
-    ) -> Result<Vec<Neighbor>, anyhow::Error> {
-        const DEFAULT_SNAP_RADIUS: f64 = 0.0002;
-        snap_snapped_neighbors_from_conn(
-            &self.snap_db,
-            center,
-            snap_radius.unwrap_or(DEFAULT_SNAP_RADIUS),
-        )
+        radius: f64,
+    ) -> Result<Vec<(Node, WayId)>, anyhow::Error> {
+        Ok(self.spatial.way_nodes_near(center, radius))
     }
 
     fn get_neighbors(&self, id: NodeId) -> Result<Vec<Neighbor>, anyhow::Error> {
         Ok(self
             .adjacency
             .get(&id)
-            .map(|edges| {
-                edges
-                    .iter()
-                    .map(|e| Neighbor {
-                        way: e.way,
-                        node: e.node,
-                        distance: e.distance,
-                        elevation_gain: e.elevation_gain,
-                        elevation_loss: e.elevation_loss,
-                    })
-                    .collect()
-            })
+            .map(|edges| edges.iter().map(InMemoryEdge::neighbor).collect())
             .unwrap_or_default())
     }
 
     fn get_neighbors_with_labels(
         &self,
         id: NodeId,
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        Ok(self
+            .adjacency
+            .get(&id)
+            .map(|edges| edges.iter().map(|e| (e.neighbor(), e.labels)).collect())
+            .unwrap_or_default())
+    }
+
+    /// Every Segment is stored in both directions (n1 → n2 on `way`, n2 → n1 on `-way`), so the
+    /// edges arriving at a Node are its outgoing edges mirrored: the Way flips sign, and what was
+    /// a climb on the way out is a descent on the way in.
+    fn get_incoming_neighbors_with_labels(
+        &self,
+        id: NodeId,
     ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
         Ok(self
             .adjacency
@@ -140,32 +273,89 @@ impl GraphRepository for InMemoryGraphRepository {
             .map(|edges| {
                 edges
                     .iter()
-                    .map(|e| {
-                        (
-                            Neighbor {
-                                way: e.way,
-                                node: e.node,
-                                distance: e.distance,
-                                elevation_gain: e.elevation_gain,
-                                elevation_loss: e.elevation_loss,
-                            },
-                            e.labels,
-                        )
+                    .filter_map(|e| {
+                        let labels = self.way_labels.get(&-e.way)?;
+                        Some((e.mirrored(None), *labels))
                     })
                     .collect()
             })
             .unwrap_or_default())
     }
 
+    fn get_compacted_neighbors_with_labels(
+        &self,
+        id: NodeId,
+        stops: &[NodeId],
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        let Some(edges) = self.compacted.edges.get(&id) else {
+            return self.get_neighbors_with_labels(id);
+        };
+        let open = self.shapes_through_any(stops);
+        Ok(edges
+            .iter()
+            .map(|e| match e.shape {
+                // a chain through a stop is set off along Segment by Segment instead
+                Some(shape) if open.contains(&shape.id) => (
+                    self.compacted.shapes[shape.id as usize].segments[0],
+                    e.labels,
+                ),
+                _ => (e.neighbor(), e.labels),
+            })
+            .collect())
+    }
+
+    /// The compacted edges arriving at a Node are its compacted edges out, mirrored, with their
+    /// chains reversed
+    fn get_compacted_incoming_neighbors_with_labels(
+        &self,
+        id: NodeId,
+        stops: &[NodeId],
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        let Some(edges) = self.compacted.edges.get(&id) else {
+            return self.get_incoming_neighbors_with_labels(id);
+        };
+        let open = self.shapes_through_any(stops);
+        Ok(edges
+            .iter()
+            .filter_map(|e| {
+                let labels = self.way_labels.get(&-e.way)?;
+                let neighbor = match e.shape {
+                    Some(shape) if open.contains(&shape.id) => {
+                        let first = self.compacted.shapes[shape.id as usize].segments[0];
+                        InMemoryEdge {
+                            node: first.node,
+                            distance: first.distance,
+                            elevation_gain: first.elevation_gain,
+                            elevation_loss: first.elevation_loss,
+                            ..e.clone()
+                        }
+                        .mirrored(None)
+                    }
+                    Some(shape) => e.mirrored(Some(EdgeShape {
+                        id: self.compacted.shapes[shape.id as usize].reverse,
+                        first: shape.last,
+                        last: shape.first,
+                    })),
+                    None => e.mirrored(None),
+                };
+                Some((neighbor, *labels))
+            })
+            .collect())
+    }
+
+    fn get_shape(&self, shape: ShapeId) -> Result<Vec<Neighbor>, anyhow::Error> {
+        self.compacted
+            .shapes
+            .get(shape as usize)
+            .map(|shape| shape.segments.clone())
+            .ok_or_else(|| anyhow!("no compacted edge has shape {shape}"))
+    }
+
     fn get_way_labels(&self, way: WayId) -> Result<WayLabels, anyhow::Error> {
-        // Find any edge with this way_id and return its labels.
-        // Called rarely (only for snapped starting edges), so linear scan is acceptable.
-        for edges in self.adjacency.values() {
-            if let Some(e) = edges.iter().find(|e| e.way == way) {
-                return Ok(e.labels);
-            }
-        }
-        anyhow::bail!("Way {} not found in adjacency list", way)
+        self.way_labels
+            .get(&way)
+            .copied()
+            .ok_or_else(|| anyhow::anyhow!("Way {} not found in way labels", way))
     }
 
     fn get_way_names(&self, way_ids: &[WayId]) -> Result<HashMap<WayId, String>, anyhow::Error> {
@@ -175,6 +365,23 @@ impl GraphRepository for InMemoryGraphRepository {
             .collect())
     }
 
+    fn get_route_names(&self, way_ids: &[WayId]) -> Result<HashMap<WayId, String>, anyhow::Error> {
+        Ok(way_ids
+            .iter()
+            .filter_map(|id| self.route_names.get(id).map(|name| (*id, name.clone())))
+            .collect())
+    }
+
+    fn find_ways_named(&self, text: &str) -> Result<Vec<WayId>, anyhow::Error> {
+        let text = text.to_lowercase();
+        Ok(self
+            .way_names
+            .iter()
+            .filter(|(_, name)| name.to_lowercase().contains(&text))
+            .map(|(way, _)| *way)
+            .collect())
+    }
+
     fn get_nodes_with_edge_to(
         &self,
         from_nodes: &[NodeId],
@@ -191,88 +398,135 @@ impl GraphRepository for InMemoryGraphRepository {
         }
         Ok(result)
     }
+
+    fn is_closed(&self, way: WayId, at: NaiveDateTime) -> Result<bool, anyhow::Error> {
+        Ok(self
+            .restrictions
+            .get(&way.abs())
+            .is_some_and(|restrictions| Restriction::is_closed(restrictions, at)))
+    }
+
+    fn is_turn_allowed(
+        &self,
+        from_way: WayId,
+        via: NodeId,
+        to_way: WayId,
+    ) -> Result<bool, anyhow::Error> {
+        Ok(self
+            .turn_restrictions
+            .get(&via)
+            .is_none_or(|restrictions| TurnRestriction::is_allowed(restrictions, from_way, to_way)))
+    }
+
+    fn get_control(&self, node: NodeId) -> Result<Control, anyhow::Error> {
+        Ok(self.controls.get(&node).copied().unwrap_or_default())
+    }
+}
+
+/// Whether a Node only ever continues a Way: it has two Segments, one back along the Way and one
+/// on, and there's no turn restricted or traffic control device at it. Such Nodes need never be
+/// stopped at while searching.
+fn is_interior(
+    id: NodeId,
+    edges: &[InMemoryEdge],
+    turn_restrictions: &HashMap<NodeId, Vec<TurnRestriction>>,
+    controls: &HashMap<NodeId, Control>,
+) -> bool {
+    matches!(edges, [a, b] if a.way == -b.way && a.node.id != b.node.id && a.node.id != id)
+        && !turn_restrictions.contains_key(&id)
+        && !controls.contains_key(&id)
 }
 
-/// Snapping logic extracted from SqliteGraphRepository so we can reuse it
-/// against the snap_db connection without constructing a full SqliteGraphRepository.
-fn snap_snapped_neighbors_from_conn(
-    conn: &DBConnection,
-    center: Point,
-    snap_radius: f64,
-) -> Result<Vec<Neighbor>, anyhow::Error> {
-    use crate::osm::Distance;
-    use anyhow::anyhow;
-    use geo::prelude::*;
-
-    const MAX_SNAP_RADIUS: f64 = 0.001;
-    const SNAP_INCREMENT: f64 = 0.0002;
-
-    type Bearing = f64;
-
-    let mut stmt = conn.prepare_cached(
-        "
-        SELECT WayNodes.node, lon, lat, WayNodes.way
-        FROM Ways
-        JOIN WayNodes ON WayNodes.way=Ways.id
-        JOIN Nodes ON WayNodes.node=Nodes.id
-        WHERE minLon <= ?1
-          AND maxLon >= ?2
-          AND minLat <= ?3
-          AND maxLat >= ?4
-    ",
-    )?;
-
-    let center_lon = center.x();
-    let center_lat = center.y();
-    let results = stmt.query_map(
-        [
-            center_lon + snap_radius,
-            center_lon - snap_radius,
-            center_lat + snap_radius,
-            center_lat - snap_radius,
-        ],
-        |row| {
-            let lon = row.get(1)?;
-            let lat = row.get(2)?;
-            let loc = Point::new(lon, lat);
-            Ok((
-                Neighbor {
-                    node: Node::new(row.get(0)?, &loc),
-                    way: row.get(3)?,
-                    distance: center.haversine_distance(&loc) as Distance,
-                    elevation_gain: 0,
-                    elevation_loss: 0,
-                },
-                center.haversine_bearing(loc),
-            ))
-        },
-    )?;
-
-    let mut results: Vec<(Neighbor, Bearing)> = results.map(|r| r.unwrap()).collect();
-
-    if results.is_empty() {
-        if snap_radius >= MAX_SNAP_RADIUS {
-            return Err(anyhow!("Could not snap coords to graph"));
+/// Compacts every chain of interior Nodes between two others into a single edge, in each
+/// direction. Rings of interior Nodes, reachable from no other, are left as they are.
+fn compact(
+    adjacency: &HashMap<NodeId, Vec<InMemoryEdge>>,
+    turn_restrictions: &HashMap<NodeId, Vec<TurnRestriction>>,
+    controls: &HashMap<NodeId, Control>,
+) -> Compacted {
+    let interior = |id: NodeId| {
+        adjacency
+            .get(&id)
+            .is_some_and(|edges| is_interior(id, edges, turn_restrictions, controls))
+    };
+
+    let mut compacted: HashMap<NodeId, Vec<InMemoryEdge>> = HashMap::new();
+    let mut shapes: Vec<Shape> = vec![];
+    // (first Node, second Node) → the chain setting off between them
+    let mut starts: HashMap<(NodeId, NodeId), ShapeId> = HashMap::new();
+
+    // numbered in order of the Nodes they set off from, so that the same graph always compacts
+    // the same way
+    let mut junctions: Vec<NodeId> = adjacency.keys().copied().collect();
+    junctions.sort_unstable();
+    for junction in junctions {
+        let edges = &adjacency[&junction];
+        if interior(junction) || !edges.iter().any(|e| interior(e.node.id)) {
+            continue;
         }
-        return snap_snapped_neighbors_from_conn(conn, center, snap_radius + SNAP_INCREMENT);
-    }
+        let chains = compacted.entry(junction).or_default();
+        for edge in edges {
+            if !interior(edge.node.id) {
+                chains.push(edge.clone());
+                continue;
+            }
 
-    results.sort_by(|(n1, _), (n2, _)| n1.distance.partial_cmp(&n2.distance).unwrap());
+            let mut segments = vec![edge.neighbor()];
+            let (mut previous, mut current) = (junction, edge.node.id);
+            while interior(current) {
+                let next = adjacency[&current]
+                    .iter()
+                    .find(|e| e.node.id != previous)
+                    .expect("interior Nodes have a Segment on");
+                segments.push(next.neighbor());
+                (previous, current) = (current, next.node.id);
+            }
 
-    let mut results_iter = results.into_iter();
-    let (closest_neighbor, closest_bearing) = results_iter.next().unwrap();
+            let id = shapes.len() as ShapeId;
+            starts.insert((junction, edge.node.id), id);
+            chains.push(InMemoryEdge {
+                node: segments[segments.len() - 1].node,
+                distance: segments.iter().map(|s| s.distance).sum(),
+                // unknown for the whole chain if unknown for any of it
+                elevation_gain: sum_elevation(segments.iter().map(|s| s.elevation_gain)),
+                elevation_loss: sum_elevation(segments.iter().map(|s| s.elevation_loss)),
+                shape: Some(EdgeShape {
+                    id,
+                    first: segments[0].node,
+                    last: segments[segments.len() - 2].node,
+                }),
+                ..edge.clone()
+            });
+            shapes.push(Shape {
+                segments,
+                reverse: 0,
+            });
+        }
+    }
 
-    let mut next_closest: Option<Neighbor> = None;
-    for (neighbor, bearing) in results_iter {
-        if closest_neighbor.way == neighbor.way && ((closest_bearing - bearing).abs() >= 90.) {
-            next_closest = Some(neighbor);
-            break;
+    // the chain from J through x1 … xn to K is ridden back from K through xn
+    let mut shapes_through: HashMap<NodeId, [ShapeId; 2]> = HashMap::new();
+    for id in 0..shapes.len() as ShapeId {
+        let shape = &shapes[id as usize];
+        let [.., before_end, end] = shape.segments[..] else {
+            unreachable!("chains are at least two Segments long");
+        };
+        let reverse = starts[&(end.node.id, before_end.node.id)];
+        for segment in &shape.segments[..shape.segments.len() - 1] {
+            shapes_through.insert(segment.node.id, [id, reverse]);
         }
+        shapes[id as usize].reverse = reverse;
     }
 
-    let mut out = vec![closest_neighbor];
-    if let Some(n) = next_closest {
-        out.push(n);
+    Compacted {
+        edges: compacted,
+        shapes,
+        shapes_through,
     }
-    Ok(out)
+}
+
+fn sum_elevation(mut elevations: impl Iterator<Item = i16>) -> i16 {
+    elevations
+        .try_fold(0i16, |sum, e| (e >= 0).then(|| sum.saturating_add(e)))
+        .unwrap_or(-1)
 }
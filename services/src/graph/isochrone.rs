@@ -0,0 +1,88 @@
+/// Isochrones: the area reachable from a point within some budget of distance, cost or time,
+/// outlined as a concave hull around everywhere the traversal managed to reach.
+use super::traversal::{Traversable, TraversalMetric, TraversalSegment};
+use super::{CostModel, Graph, RoutingError};
+use geo::{ConcaveHull, Coord, MultiPoint, Point, Polygon};
+use serde::Serialize;
+
+/// how tightly hulls wrap their points; lower values follow the street network more closely
+const HULL_CONCAVITY: f64 = 2.0;
+
+#[derive(Debug, Serialize)]
+pub struct Isochrone {
+    #[serde(serialize_with = "geojson::ser::serialize_geometry")]
+    pub geometry: Polygon,
+    pub metric: TraversalMetric,
+    pub threshold: f64,
+}
+
+impl Graph {
+    /// Calculates one isochrone per threshold in `bands`, each covering everywhere reachable from
+    /// `start` within that threshold of `metric`. Isochrones are returned from smallest to largest.
+    pub fn calculate_isochrones(
+        &self,
+        start: Point,
+        metric: TraversalMetric,
+        bands: &[f64],
+        cost_model: Option<CostModel>,
+    ) -> Result<Vec<Isochrone>, anyhow::Error> {
+        if bands.is_empty() || bands.iter().any(|b| !b.is_finite() || *b <= 0.0) {
+            return Err(
+                RoutingError::invalid_request("Isochrone bands must be positive numbers").into(),
+            );
+        }
+        let mut bands = bands.to_vec();
+        bands.sort_by(f64::total_cmp);
+        bands.dedup();
+
+        let cost_model = self.prepare_cost_model(cost_model)?;
+        let start = self.snap_waypoint(start)?;
+        let mut context = self.initialize_traversal(&start, cost_model, None)?;
+        self.traverse_within(&mut context, metric, *bands.last().unwrap())?;
+
+        Ok(bands
+            .into_iter()
+            .map(|threshold| {
+                let mut points: Vec<Point> = vec![start.point];
+                points.extend(
+                    context
+                        .came_from
+                        .values()
+                        .filter_map(|segment| reach_along(segment, metric, threshold)),
+                );
+
+                Isochrone {
+                    geometry: MultiPoint::new(points).concave_hull(HULL_CONCAVITY),
+                    metric,
+                    threshold,
+                }
+            })
+            .collect())
+    }
+}
+
+/// How far along the segment a rider gets before `threshold` runs out: its end, if reachable
+/// entirely, or the point partway along where the threshold is crossed
+fn reach_along(
+    segment: &TraversalSegment,
+    metric: TraversalMetric,
+    threshold: f64,
+) -> Option<Point> {
+    let before = metric.measure_before(segment);
+    let after = metric.measure(segment);
+
+    if after <= threshold {
+        return Some(segment.geometry.end.into());
+    }
+    if before >= threshold {
+        return None;
+    }
+
+    let fraction = (threshold - before) / (after - before);
+    let Coord { x: x1, y: y1 } = segment.geometry.start;
+    let Coord { x: x2, y: y2 } = segment.geometry.end;
+    Some(Point::new(
+        x1 + (x2 - x1) * fraction,
+        y1 + (y2 - y1) * fraction,
+    ))
+}
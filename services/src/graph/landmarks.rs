@@ -0,0 +1,293 @@
+/// Landmarks for the ALT (A*, Landmarks, Triangle inequality) heuristic: an offline
+/// preprocessing of the graph for a fixed cost model, storing the cost from each of a handful
+/// of far-flung landmark Nodes to every Node, and from every Node back to each landmark.
+///
+/// For any landmark L, the triangle inequality bounds the cost of getting from v to t from below
+/// by both cost(L → t) - cost(L → v) and cost(v → L) - cost(t → L). Unlike scaled straight-line
+/// distance, the best of these bounds never overestimates, so searches guided by it stay optimal,
+/// and it follows the street network closely enough to explore far fewer Nodes.
+use super::contraction::{load_segment_costs, NodeIndex, OrderedCost};
+use super::traversal::{Heuristic, TraversalContext};
+use super::{ContractionHierarchy, Cost, CostModel, Graph};
+use crate::db::DBConnection;
+use crate::osm::{Neighbor, NodeId, WayLabels};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::info;
+
+/// how many landmarks to place. Each adds two costs per Node, and a little to every estimate.
+const LANDMARK_COUNT: usize = 8;
+
+/// Landmark cost tables for one cost model
+pub struct Landmarks {
+    pub name: String,
+    pub cost_model: CostModel,
+
+    node_index: HashMap<NodeId, NodeIndex>,
+    /// how many landmarks were placed, at most LANDMARK_COUNT
+    count: usize,
+    /// `from[v * count + l]`: cost from landmark l to Node v, infinite if unreachable
+    from: Vec<Cost>,
+    /// `to[v * count + l]`: cost from Node v to landmark l, infinite if unreachable
+    to: Vec<Cost>,
+}
+
+impl Landmarks {
+    /// Places landmarks across the full graph in the DB and computes their cost tables under the
+    /// given cost model. Each landmark is the Node farthest from those placed before it, which
+    /// spreads them around the edges of the graph, where their bounds are tightest.
+    pub fn build(
+        conn: &DBConnection,
+        name: &str,
+        cost_model: CostModel,
+    ) -> Result<Self, anyhow::Error> {
+        let (node_ids, segments) = load_segment_costs(conn, &cost_model)?;
+        let n = node_ids.len();
+
+        let mut out: Vec<Vec<(NodeIndex, Cost)>> = vec![vec![]; n];
+        let mut inc: Vec<Vec<(NodeIndex, Cost)>> = vec![vec![]; n];
+        for (n1, n2, _, cost) in segments {
+            out[n1 as usize].push((n2, cost));
+            inc[n2 as usize].push((n1, cost));
+        }
+
+        let now = Instant::now();
+        let mut placed: Vec<NodeIndex> = vec![];
+        let mut from_landmark: Vec<Vec<Cost>> = vec![];
+        let mut to_landmark: Vec<Vec<Cost>> = vec![];
+
+        // cost to each Node from its nearest landmark, or from an arbitrary Node to begin with
+        let mut nearest = if n > 0 { costs_from(&out, 0) } else { vec![] };
+        while placed.len() < LANDMARK_COUNT {
+            let Some(next) = farthest(&nearest) else {
+                break;
+            };
+            if placed.contains(&next) {
+                break;
+            }
+
+            let from = costs_from(&out, next);
+            for (nearest, cost) in nearest.iter_mut().zip(&from) {
+                // the arbitrary starting Node is forgotten once a real landmark is placed
+                *nearest = if placed.is_empty() {
+                    *cost
+                } else {
+                    nearest.min(*cost)
+                };
+            }
+
+            placed.push(next);
+            from_landmark.push(from);
+            to_landmark.push(costs_from(&inc, next));
+        }
+        info!(
+            "{name}: placed {} landmarks across {n} nodes in {}s",
+            placed.len(),
+            now.elapsed().as_secs()
+        );
+
+        // transposed, so that the costs for a Node sit together
+        let count = placed.len();
+        let mut from = vec![Cost::INFINITY; n * count];
+        let mut to = vec![Cost::INFINITY; n * count];
+        for l in 0..count {
+            for v in 0..n {
+                from[v * count + l] = from_landmark[l][v];
+                to[v * count + l] = to_landmark[l][v];
+            }
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            cost_model,
+            node_index: node_ids
+                .iter()
+                .enumerate()
+                .map(|(idx, id)| (*id, idx as NodeIndex))
+                .collect(),
+            count,
+            from,
+            to,
+        })
+    }
+
+    /// Persists these landmarks into the Landmark tables, replacing any previous version of them
+    pub fn save(&self, conn: &mut DBConnection) -> Result<(), anyhow::Error> {
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM LandmarkProfiles WHERE name = ?1", [&self.name])?;
+        tx.execute(
+            "INSERT INTO LandmarkProfiles (name, cost_model, count) VALUES (?1, ?2, ?3)",
+            (&self.name, format!("{:?}", self.cost_model), self.count),
+        )?;
+        let profile = tx.last_insert_rowid();
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO LandmarkNodes (profile, node, costs) VALUES (?1, ?2, ?3)",
+            )?;
+            for (node_id, idx) in &self.node_index {
+                let range = *idx as usize * self.count..(*idx as usize + 1) * self.count;
+                let costs: Vec<u8> = self.from[range.clone()]
+                    .iter()
+                    .chain(&self.to[range])
+                    .flat_map(|c| c.to_le_bytes())
+                    .collect();
+                stmt.execute((profile, node_id, costs))?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Loads the stored landmarks for every preprocessed profile whose cost model still matches
+    /// its current definition, skipping any built against an older model
+    pub fn load_all(conn: &DBConnection) -> Result<Vec<Arc<Self>>, anyhow::Error> {
+        let has_tables: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'LandmarkProfiles'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_tables {
+            return Ok(vec![]);
+        }
+
+        let mut all = vec![];
+        for (name, cost_model) in ContractionHierarchy::profiles() {
+            let stored: Option<(i64, String, usize)> = conn
+                .query_row(
+                    "SELECT id, cost_model, count FROM LandmarkProfiles WHERE name = ?1",
+                    [name],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .ok();
+            match stored {
+                Some((profile, fingerprint, count))
+                    if fingerprint == format!("{:?}", cost_model) =>
+                {
+                    all.push(Arc::new(Self::load(
+                        conn, profile, name, cost_model, count,
+                    )?));
+                    info!("Loaded landmarks for {name}");
+                }
+                Some(_) => info!("Skipping stale landmarks for {name}"),
+                None => {}
+            }
+        }
+        Ok(all)
+    }
+
+    fn load(
+        conn: &DBConnection,
+        profile: i64,
+        name: &str,
+        cost_model: CostModel,
+        count: usize,
+    ) -> Result<Self, anyhow::Error> {
+        let mut node_index = HashMap::new();
+        let mut from = vec![];
+        let mut to = vec![];
+
+        let mut stmt = conn.prepare("SELECT node, costs FROM LandmarkNodes WHERE profile = ?1")?;
+        let mut rows = stmt.query([profile])?;
+        while let Some(row) = rows.next()? {
+            node_index.insert(row.get(0)?, node_index.len() as NodeIndex);
+            let costs: Vec<Cost> = row
+                .get::<_, Vec<u8>>(1)?
+                .chunks_exact(4)
+                .map(|bytes| Cost::from_le_bytes(bytes.try_into().unwrap()))
+                .collect();
+            from.extend_from_slice(&costs[..count]);
+            to.extend_from_slice(&costs[count..]);
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            cost_model,
+            node_index,
+            count,
+            from,
+            to,
+        })
+    }
+
+    /// A lower bound on the cost of the cheapest path from one Node to another, or 0 when either
+    /// is unknown to the landmarks (e.g. the virtual start and end nodes)
+    pub fn lower_bound(&self, from: NodeId, to: NodeId) -> Cost {
+        let (Some(&v), Some(&t)) = (self.node_index.get(&from), self.node_index.get(&to)) else {
+            return 0.0;
+        };
+        let (v, t) = (v as usize * self.count, t as usize * self.count);
+
+        let mut bound: Cost = 0.0;
+        for l in 0..self.count {
+            // landmarks which can't reach (or be reached from) both Nodes say nothing about them
+            let (from_v, from_t) = (self.from[v + l], self.from[t + l]);
+            if from_v.is_finite() && from_t.is_finite() {
+                bound = bound.max(from_t - from_v);
+            }
+            let (to_v, to_t) = (self.to[v + l], self.to[t + l]);
+            if to_v.is_finite() && to_t.is_finite() {
+                bound = bound.max(to_v - to_t);
+            }
+        }
+        bound
+    }
+}
+
+impl Graph {
+    /// Steers the search toward the `anchors` (the Nodes it must pass through to reach its
+    /// target) with landmarks, whenever they've been precomputed for its cost model. The heuristic
+    /// weight only scales the straight-line distance searches fall back on without them.
+    pub(super) fn apply_landmarks(
+        &self,
+        context: &mut TraversalContext,
+        anchors: &[(Neighbor, WayLabels)],
+    ) {
+        if anchors.is_empty() {
+            return;
+        }
+        if let Some(landmarks) = self
+            .landmarks
+            .iter()
+            .find(|l| l.cost_model == context.cost_model)
+        {
+            context.heuristic = Heuristic::Landmarks {
+                landmarks: landmarks.clone(),
+                anchors: anchors.iter().map(|(n, _)| n.node.id).collect(),
+            };
+        }
+    }
+}
+
+/// Dijkstra from `source` over every edge, returning the cost to each Node
+fn costs_from(edges: &[Vec<(NodeIndex, Cost)>], source: NodeIndex) -> Vec<Cost> {
+    let mut costs = vec![Cost::INFINITY; edges.len()];
+    let mut queue = BinaryHeap::from([Reverse((OrderedCost(0.0), source))]);
+    costs[source as usize] = 0.0;
+
+    while let Some(Reverse((OrderedCost(cost), node))) = queue.pop() {
+        if costs[node as usize] < cost {
+            continue;
+        }
+        for (next, edge_cost) in &edges[node as usize] {
+            let next_cost = cost + edge_cost;
+            if next_cost < costs[*next as usize] {
+                costs[*next as usize] = next_cost;
+                queue.push(Reverse((OrderedCost(next_cost), *next)));
+            }
+        }
+    }
+
+    costs
+}
+
+/// The reachable Node with the greatest cost
+fn farthest(costs: &[Cost]) -> Option<NodeIndex> {
+    costs
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.is_finite())
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(idx, _)| idx as NodeIndex)
+}
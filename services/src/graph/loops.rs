@@ -0,0 +1,155 @@
+/// Round-trip loops: closed routes of roughly a requested length, leaving and returning to the
+/// same point. Each candidate heads out through two via points which, together with the start,
+/// form a triangle pointed in a different direction. Legs are routed one after another, with
+/// every segment already ridden heavily penalized, so the way back avoids the way out.
+use super::alternatives::{inner_path, penalize};
+use super::core::append_leg;
+use super::traversal::{Penalties, Route};
+use super::{Cost, CostModel, Graph, RouteMetadata, RoutingError};
+use crate::osm::{Distance, NodeId};
+use geo::{HaversineDestination, Point};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// how many directions to try sending a loop out in
+const LOOP_BEARINGS: usize = 8;
+/// ratio of street network distance to straight line distance, for sizing the triangles
+const ROAD_DETOUR: f64 = 1.3;
+/// loops whose length is off from the target by more than this fraction are discarded
+pub const LENGTH_TOLERANCE: f64 = 0.25;
+/// loops riding more than this fraction of their distance back over segments they've already
+/// ridden are discarded
+pub const MAX_LOOP_OVERLAP: f32 = 0.25;
+/// multiplier applied to segments already ridden earlier in the loop
+const REUSE_PENALTY: Cost = 4.0;
+
+#[derive(Debug, Serialize)]
+pub struct Loop {
+    pub route: Route,
+    pub meta: RouteMetadata,
+    pub distance: Distance,
+    /// length-weighted average cost_factor across the loop, lower is better
+    pub avg_cost_factor: Cost,
+    /// fraction of the loop's distance ridden over segments an earlier leg rode, in either direction
+    pub overlap: f32,
+}
+
+impl Graph {
+    /// Calculates up to `count` candidate loops of approximately `distance` meters, starting and
+    /// ending at `start`, ranked by their average cost factor
+    pub fn calculate_loops(
+        &self,
+        start: Point,
+        distance: Distance,
+        count: usize,
+        cost_model: Option<CostModel>,
+    ) -> Result<Vec<Loop>, anyhow::Error> {
+        if distance <= 0 {
+            return Err(RoutingError::invalid_request("Loop distance must be positive").into());
+        }
+        let cost_model = self.prepare_cost_model(cost_model)?;
+
+        // sides of an equilateral triangle, shrunk to account for streets not running straight
+        let side = distance as f64 / (3.0 * ROAD_DETOUR);
+
+        let mut loops: Vec<Loop> = vec![];
+        for i in 0..LOOP_BEARINGS {
+            let bearing = 360.0 * i as f64 / LOOP_BEARINGS as f64;
+            let via = [
+                start.haversine_destination(bearing, side),
+                start.haversine_destination(bearing + 60.0, side),
+            ];
+            let candidate = match self.calculate_loop(start, via, cost_model.clone()) {
+                Ok(candidate) => candidate,
+                // directions that can't be routed (e.g. out into the water) are simply skipped
+                Err(e) if is_unroutable(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            if (candidate.distance - distance).abs() as f64 <= distance as f64 * LENGTH_TOLERANCE
+                && candidate.overlap <= MAX_LOOP_OVERLAP
+            {
+                loops.push(candidate);
+            }
+        }
+
+        loops.sort_by(|a, b| a.avg_cost_factor.total_cmp(&b.avg_cost_factor));
+        loops.truncate(count);
+
+        if loops.is_empty() {
+            return Err(RoutingError::NoPathFound.into());
+        }
+        Ok(loops)
+    }
+
+    /// Routes start -> via[0] -> via[1] -> start, steering each leg off the segments of those before
+    fn calculate_loop(
+        &self,
+        start: Point,
+        via: [Point; 2],
+        cost_model: CostModel,
+    ) -> Result<Loop, anyhow::Error> {
+        let waypoints = [start, via[0], via[1], start]
+            .into_iter()
+            .map(|p| self.snap_waypoint(p))
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+        let mut route: Route = vec![];
+        let mut meta = RouteMetadata::empty();
+        let mut penalties = Penalties::new();
+        // segments ridden by the legs so far, and how far the loop has ridden back over them
+        let mut ridden: HashSet<(NodeId, NodeId)> = HashSet::new();
+        let mut reused: Distance = 0;
+
+        for (leg, pair) in waypoints.windows(2).enumerate() {
+            let (penalized, leg_meta, returned) =
+                self.route_penalized(&pair[0], &pair[1], cost_model.clone(), None, penalties)?;
+            penalties = returned;
+
+            let path = inner_path(&penalized);
+            penalize(&mut penalties, &path, REUSE_PENALTY);
+
+            // recost the leg as it would be without penalties
+            let leg_route =
+                self.rebuild_route(&pair[0], &pair[1], penalized[0].to.id, &path, &cost_model)?;
+            reused += leg_route
+                .iter()
+                .filter(|s| {
+                    ridden.contains(&(s.from.id, s.to.id)) || ridden.contains(&(s.to.id, s.from.id))
+                })
+                .map(|s| s.length)
+                .sum::<Distance>();
+            ridden.extend(leg_route.iter().map(|s| (s.from.id, s.to.id)));
+            append_leg(&mut route, leg_route, leg);
+            meta.merge(&leg_meta);
+        }
+
+        let distance = route.last().map(|s| s.distance_so_far).unwrap_or(0);
+        let weighted: Cost = route.iter().map(|s| s.cost_factor * s.length as Cost).sum();
+        let (avg_cost_factor, overlap) = if distance > 0 {
+            (weighted / distance as Cost, reused as f32 / distance as f32)
+        } else {
+            (0.0, 0.0)
+        };
+
+        Ok(Loop {
+            route,
+            meta,
+            distance,
+            avg_cost_factor,
+            overlap,
+        })
+    }
+}
+
+/// whether the error only means there's no loop in that direction, rather than that something
+/// went wrong which every other direction would run into too
+fn is_unroutable(e: &anyhow::Error) -> bool {
+    matches!(
+        e.downcast_ref::<RoutingError>(),
+        Some(
+            RoutingError::SnapFailed { .. }
+                | RoutingError::OutOfCoverage { .. }
+                | RoutingError::NoPathFound
+        )
+    )
+}
@@ -1,11 +1,32 @@
+mod alternatives;
+mod budget;
+mod contraction;
 mod core;
 mod cost;
+mod error;
 mod in_memory_repository;
+mod isochrone;
+mod landmarks;
+mod loops;
+mod profiles;
 mod repository;
+mod rules;
+mod snapshot;
+mod spatial;
 mod traversal;
 
+pub use alternatives::*;
+pub use budget::*;
+pub use contraction::*;
 pub use core::*;
 pub use cost::*;
+pub use error::*;
 pub use in_memory_repository::*;
+pub use isochrone::*;
+pub use landmarks::*;
+pub use loops::*;
+pub use profiles::*;
 pub use repository::*;
+pub use rules::*;
+pub use snapshot::*;
 pub use traversal::*;
@@ -0,0 +1,126 @@
+use super::{CostModel, MobileCostModel, RoutingError, Weight};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+/// The profiles bundled with the router, used unless PROFILES_PATH points at another file
+const BUNDLED_PROFILES: &str = include_str!("../../profiles.json");
+
+/// A profile as written in the config file. Its cost model is given either in full, or with the
+/// same few controls as the mobile client's.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProfileConfig {
+    name: String,
+    version: u32,
+    #[serde(default)]
+    description: String,
+    cost_model: Option<CostModel>,
+    mobile_cost_model: Option<MobileCostModel>,
+    heuristic_weight: Option<Weight>,
+}
+
+/// A named, versioned set of routing preferences, so that they can be tuned on the server
+/// without shipping a new client
+#[derive(Debug, Clone, Serialize)]
+pub struct Profile {
+    pub name: String,
+    pub version: u32,
+    pub description: String,
+    #[serde(skip)]
+    pub cost_model: CostModel,
+    /// the weight on straight-line distance, for searches without landmarks for this cost model
+    pub heuristic_weight: Option<Weight>,
+}
+
+impl Profile {
+    /// how requests refer to this profile
+    pub fn id(&self) -> String {
+        format!("{}@{}", self.name, self.version)
+    }
+}
+
+impl TryFrom<ProfileConfig> for Profile {
+    type Error = anyhow::Error;
+
+    fn try_from(config: ProfileConfig) -> Result<Self, Self::Error> {
+        let id = format!("{}@{}", config.name, config.version);
+        if config.name.is_empty() || config.name.contains('@') {
+            return Err(anyhow!("Profile {id} needs a name without an '@'"));
+        }
+        let cost_model = match (config.cost_model, config.mobile_cost_model) {
+            (Some(cost_model), None) => cost_model,
+            (None, Some(mobile)) => mobile.resolve(),
+            _ => {
+                return Err(anyhow!(
+                    "Profile {id} needs exactly one of cost_model or mobile_cost_model"
+                ))
+            }
+        };
+        cost_model
+            .validate()
+            .map_err(|e| anyhow!("Profile {id}: {e}"))?;
+        Ok(Self {
+            name: config.name,
+            version: config.version,
+            description: config.description,
+            cost_model,
+            heuristic_weight: config.heuristic_weight,
+        })
+    }
+}
+
+/// Every profile requests may route with, by name and version
+#[derive(Debug, Clone, Default)]
+pub struct ProfileRegistry {
+    profiles: Vec<Profile>,
+}
+
+impl ProfileRegistry {
+    /// Loads the profiles from the JSON file at PROFILES_PATH, or the bundled ones if it's unset
+    pub fn load() -> Result<Self, anyhow::Error> {
+        match env::var("PROFILES_PATH") {
+            Ok(path) => Self::from_json(&std::fs::read_to_string(path)?),
+            Err(_) => Self::from_json(BUNDLED_PROFILES),
+        }
+    }
+
+    /// Parses a JSON list of profiles, rejecting any that are invalid or listed twice
+    pub fn from_json(json: &str) -> Result<Self, anyhow::Error> {
+        let configs: Vec<ProfileConfig> = serde_json::from_str(json)?;
+        let mut profiles: Vec<Profile> = vec![];
+        for config in configs {
+            let profile = Profile::try_from(config)?;
+            if profiles
+                .iter()
+                .any(|p| p.name == profile.name && p.version == profile.version)
+            {
+                return Err(anyhow!("Profile {} is listed more than once", profile.id()));
+            }
+            profiles.push(profile);
+        }
+        profiles.sort_by(|a, b| a.name.cmp(&b.name).then(a.version.cmp(&b.version)));
+        Ok(Self { profiles })
+    }
+
+    /// every profile, ordered by name and then version
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    /// Finds the profile a request asks for, as "name@version", or just "name" for its latest
+    /// version
+    pub fn get(&self, id: &str) -> Result<&Profile, RoutingError> {
+        let unknown = || RoutingError::invalid_request(format!("Unknown profile {id}"));
+        match id.split_once('@') {
+            Some((name, version)) => {
+                let version: u32 = version.parse().map_err(|_| unknown())?;
+                self.profiles
+                    .iter()
+                    .find(|p| p.name == name && p.version == version)
+            }
+            None => self.profiles.iter().rev().find(|p| p.name == id),
+        }
+        .ok_or_else(unknown)
+    }
+}
@@ -1,8 +1,14 @@
+use super::{RoutingError, SegmentKey};
 use crate::db::{self, DBConnection};
-use crate::osm::{Distance, Neighbor, Node, NodeId, WayId, WayLabels};
+use crate::osm::{
+    Control, Distance, Neighbor, Node, NodeId, OpeningHours, Restriction, Road, ShapeId,
+    TurnRestriction, WayId, WayLabels,
+};
 use anyhow::anyhow;
+use chrono::NaiveDateTime;
 use geo::prelude::*;
 use geo::Point;
+use rusqlite::OptionalExtension;
 use std::collections::{HashMap, HashSet};
 use tracing::debug;
 
@@ -11,18 +17,55 @@ const SNAP_INCREMENT: f64 = 0.0002;
 
 /// SQLite abstraction for Graph operations
 pub trait GraphRepository {
-    fn get_snapped_neighbors(
+    /// Returns the Nodes of every Way whose bounding box comes within `radius` degrees of the
+    /// point, each paired with the Way it was found on
+    fn get_way_nodes_near(
         &self,
         center: Point,
-        snap_radius: Option<f64>,
-    ) -> Result<Vec<Neighbor>, anyhow::Error>;
+        radius: f64,
+    ) -> Result<Vec<(Node, WayId)>, anyhow::Error>;
     fn get_neighbors(&self, id: NodeId) -> Result<Vec<Neighbor>, anyhow::Error>;
     fn get_neighbors_with_labels(
         &self,
         id: NodeId,
     ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error>;
+    /// Returns the edges arriving *at* this Node, for searches that run backward from a target.
+    /// Each Neighbor is the source of the edge, labeled and weighed as if ridden toward `id`.
+    fn get_incoming_neighbors_with_labels(
+        &self,
+        id: NodeId,
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error>;
+    /// Like `get_neighbors_with_labels`, but with each chain of Segments along one Way, through
+    /// Nodes joining no other, merged into a single edge out to the chain's far end. Merged edges
+    /// carry the chain's summed distance and elevation, and its `shape`. Chains through any of the
+    /// `stops` are left as Segments, so that a search can still arrive at them.
+    ///
+    /// Repositories that don't compact their edges return them Segment by Segment.
+    fn get_compacted_neighbors_with_labels(
+        &self,
+        id: NodeId,
+        _stops: &[NodeId],
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        self.get_neighbors_with_labels(id)
+    }
+    /// The compacted counterpart of `get_incoming_neighbors_with_labels`
+    fn get_compacted_incoming_neighbors_with_labels(
+        &self,
+        id: NodeId,
+        _stops: &[NodeId],
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        self.get_incoming_neighbors_with_labels(id)
+    }
+    /// The Segments a compacted edge was merged from, in order, each as the Neighbor it leads to
+    fn get_shape(&self, shape: ShapeId) -> Result<Vec<Neighbor>, anyhow::Error> {
+        Err(anyhow!("no compacted edge has shape {shape}"))
+    }
     fn get_way_labels(&self, way: WayId) -> Result<WayLabels, anyhow::Error>;
     fn get_way_names(&self, way_ids: &[WayId]) -> Result<HashMap<WayId, String>, anyhow::Error>;
+    /// the names of the signed bike routes along each of the Ways that's part of any
+    fn get_route_names(&self, way_ids: &[WayId]) -> Result<HashMap<WayId, String>, anyhow::Error>;
+    /// the Ways whose street name contains the text, ignoring case
+    fn find_ways_named(&self, text: &str) -> Result<Vec<WayId>, anyhow::Error>;
     /// Given a set of source nodes and target nodes, return which source nodes
     /// have a direct edge to any target node in the Segments table.
     fn get_nodes_with_edge_to(
@@ -30,6 +73,168 @@ pub trait GraphRepository {
         from_nodes: &[NodeId],
         to_nodes: &[NodeId],
     ) -> Result<HashSet<NodeId>, anyhow::Error>;
+    /// Whether the Way (in either direction) is closed by a time-based restriction at the given
+    /// local time
+    fn is_closed(&self, way: WayId, at: NaiveDateTime) -> Result<bool, anyhow::Error>;
+    /// Whether the turn restrictions at the Node allow turning there from one (signed) Way onto
+    /// another
+    fn is_turn_allowed(
+        &self,
+        from_way: WayId,
+        via: NodeId,
+        to_way: WayId,
+    ) -> Result<bool, anyhow::Error>;
+    /// The traffic control device at the Node, if any
+    fn get_control(&self, node: NodeId) -> Result<Control, anyhow::Error>;
+
+    /// Projects the point onto every edge of the Ways near it, nearest first, optionally keeping
+    /// only edges of the given Road classes. The search area is widened until an edge is found.
+    ///
+    /// Implementation notes:
+    /// - each edge is considered once, in its OSM direction (ie: along the positive Way)
+    /// - Ways are found by bounding box, so an edge can be found even when neither of its Nodes
+    ///   are anywhere near the point
+    fn get_snap_candidates(
+        &self,
+        center: Point,
+        roads: Option<&[Road]>,
+    ) -> Result<Vec<SnapResult>, anyhow::Error> {
+        let mut radius = SNAP_INCREMENT;
+        loop {
+            let mut seen: HashSet<(NodeId, WayId)> = HashSet::new();
+            let mut candidates = vec![];
+
+            for (from, way) in self.get_way_nodes_near(center, radius)? {
+                // closed Ways list their first Node again at the end
+                if !seen.insert((from.id, way)) {
+                    continue;
+                }
+                for (edge, labels) in self.get_neighbors_with_labels(from.id)? {
+                    if edge.way != way || roads.is_some_and(|r| !r.contains(&labels.1)) {
+                        continue;
+                    }
+                    candidates.push(SnapResult::project(center, from, edge, labels));
+                }
+            }
+
+            if !candidates.is_empty() {
+                candidates.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+                debug!(
+                    "{} snap candidates within {radius}, nearest {:.1}m away",
+                    candidates.len(),
+                    candidates[0].distance
+                );
+                return Ok(candidates);
+            }
+
+            if radius >= MAX_SNAP_RADIUS {
+                return Err(RoutingError::SnapFailed {
+                    lon: center.x(),
+                    lat: center.y(),
+                }
+                .into());
+            }
+            debug!("Could not snap coords to graph, expanding");
+            radius += SNAP_INCREMENT;
+        }
+    }
+
+    /// Snaps the point to the nearest edge, optionally of the given Road classes only
+    fn snap(&self, center: Point, roads: Option<&[Road]>) -> Result<SnapResult, anyhow::Error> {
+        Ok(self.get_snap_candidates(center, roads)?.swap_remove(0))
+    }
+}
+
+/// Where a point lands when projected onto a graph edge
+#[derive(Debug, Clone)]
+pub struct SnapResult {
+    /// the projected point, on the edge
+    pub point: Point,
+    /// the Node the edge starts from
+    pub from: Node,
+    /// the edge, as ridden from `from` along its OSM direction
+    pub edge: Neighbor,
+    pub labels: WayLabels,
+    /// how far along the edge the point lands, from 0 at `from` to 1 at its other end
+    pub fraction: f64,
+    /// meters along the edge from `from` to the projected point
+    pub offset: Distance,
+    /// meters between the requested point and the projected point
+    pub distance: f64,
+}
+
+impl SnapResult {
+    /// Projects the point onto the edge, with longitude scaled down by latitude so that the
+    /// nearest point in degrees is also the nearest point on the ground
+    fn project(point: Point, from: Node, edge: Neighbor, labels: WayLabels) -> Self {
+        let (a, b) = (from.geometry, edge.node.geometry);
+        let scale = point.y().to_radians().cos();
+        let (dx, dy) = ((b.x() - a.x()) * scale, b.y() - a.y());
+        let length_squared = dx * dx + dy * dy;
+        let fraction = if length_squared > 0.0 {
+            let (px, py) = ((point.x() - a.x()) * scale, point.y() - a.y());
+            ((px * dx + py * dy) / length_squared).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let projected = Point::new(
+            a.x() + (b.x() - a.x()) * fraction,
+            a.y() + (b.y() - a.y()) * fraction,
+        );
+        Self {
+            point: projected,
+            from,
+            offset: (edge.distance as f64 * fraction).round() as Distance,
+            distance: point.haversine_distance(&projected),
+            edge,
+            labels,
+            fraction,
+        }
+    }
+
+    /// The graph Segment the point was snapped onto
+    pub fn segment(&self) -> SegmentKey {
+        (self.from.id, self.edge.node.id, self.edge.way)
+    }
+}
+
+/// Queries the Ways R*tree for the Nodes of every Way whose bounding box comes within `radius`
+/// degrees of the point
+pub(super) fn query_way_nodes_near(
+    conn: &DBConnection,
+    center: Point,
+    radius: f64,
+) -> Result<Vec<(Node, WayId)>, anyhow::Error> {
+    let mut stmt = conn.prepare_cached(
+        "
+        SELECT WayNodes.node, lon, lat, WayNodes.way
+        FROM Ways
+        JOIN WayNodes ON WayNodes.way=Ways.id
+        JOIN Nodes ON WayNodes.node=Nodes.id
+        WHERE minLon <= ?1
+          AND maxLon >= ?2
+          AND minLat <= ?3
+          AND maxLat >= ?4
+    ",
+    )?;
+
+    let results = stmt.query_map(
+        [
+            center.x() + radius,
+            center.x() - radius,
+            center.y() + radius,
+            center.y() - radius,
+        ],
+        |row| {
+            Ok((
+                Node::new(row.get(0)?, &Point::new(row.get(1)?, row.get(2)?)),
+                row.get(3)?,
+            ))
+        },
+    )?;
+
+    Ok(results.collect::<Result<_, _>>()?)
 }
 
 pub struct SqliteGraphRepository {
@@ -42,108 +247,47 @@ impl SqliteGraphRepository {
             conn: db::get_conn()?,
         })
     }
-}
 
-impl GraphRepository for SqliteGraphRepository {
-    /// Returns the closest Node(s) to the location provided
-    /// Creates a buffer around the location and searches a small square area
-    ///
-    /// Implementation notes:
-    /// - We cannot guarantee that the first Way returned from the R*tree query will be
-    ///   the closest Way, because of how R*Trees work
-    /// - TODO: this sometimes returns the wrong street (ie: the next one over)
-    ///   existing Node
-    /// - TODO: handle no Ways returned, empty case
-    fn get_snapped_neighbors(
+    /// Looks up a text column of WayLabels for each of the Ways
+    fn query_way_text(
         &self,
-        center: Point,
-        snap_radius: Option<f64>,
-    ) -> Result<Vec<Neighbor>, anyhow::Error> {
-        type Bearing = f64;
-        let snap_radius = snap_radius.unwrap_or(SNAP_INCREMENT);
-
-        let mut stmt = self.conn.prepare_cached(
-            "
-            SELECT WayNodes.node, lon, lat, WayNodes.way
-            FROM Ways
-            JOIN WayNodes ON WayNodes.way=Ways.id
-            JOIN Nodes ON WayNodes.node=Nodes.id
-            WHERE minLon <= ?1
-              AND maxLon >= ?2
-              AND minLat <= ?3
-              AND maxLat >= ?4
-        ",
-        )?;
-
-        let center_lon = center.x();
-        let center_lat = center.y();
-        let results = stmt.query_map(
-            [
-                center_lon + snap_radius,
-                center_lon - snap_radius,
-                center_lat + snap_radius,
-                center_lat - snap_radius,
-            ],
-            |row| {
-                let lon = row.get(1)?;
-                let lat = row.get(2)?;
-
-                let loc = Point::new(lon, lat);
-
-                // for each returned Node, calculate the distance from the center point
-                Ok((
-                    Neighbor {
-                        node: Node::new(row.get(0)?, &loc),
-                        way: row.get(3)?,
-                        distance: center.haversine_distance(&loc) as Distance,
-                        elevation_gain: 0,
-                        elevation_loss: 0,
-                    },
-                    center.haversine_bearing(loc),
-                ))
-            },
-        )?;
-
-        let mut results: Vec<(Neighbor, Bearing)> = results.map(|r| r.unwrap()).collect();
-
-        // if no results returned w/ this snap radius, expand it by a bit
-        if results.is_empty() {
-            if snap_radius >= MAX_SNAP_RADIUS {
-                return Err(anyhow!("Could not snap coords to graph"));
-            }
-
-            debug!("Could not snap coords to graph, expanding");
-            return self.get_snapped_neighbors(center, Some(snap_radius + SNAP_INCREMENT));
-        }
-
-        // sort these results by the total distance from the center point
-        results.sort_by(|(n1, _), (n2, _)| n1.distance.partial_cmp(&n2.distance).unwrap());
-
-        // at this point we have a sorted list of nodes by distance
-        // the first, closest node is clearly the best candidate
-        let mut results_iter = results.into_iter();
-        let (closest_neighbor, closest_bearing) = results_iter.next().unwrap();
-
-        // then, use the wayId + the bearing relationship to find
-        // the next node on the way on the other side of the center point
-        let mut next_closest: Option<Neighbor> = None;
-        for (neighbor, bearing) in results_iter {
-            // This Node is on the same Way as the `closest`
-            // so find the next segment that had a fairly different bearing than the closest
-            // TODO: better alg here than just more than "normal" to closest bearing
-            if closest_neighbor.way == neighbor.way && ((closest_bearing - bearing).abs() >= 90.) {
-                next_closest = Some(neighbor);
-                break;
-            }
+        column: &str,
+        way_ids: &[WayId],
+    ) -> Result<HashMap<WayId, String>, anyhow::Error> {
+        if way_ids.is_empty() {
+            return Ok(HashMap::new());
         }
 
-        let mut results = vec![closest_neighbor];
+        let placeholders: Vec<String> = way_ids.iter().map(|_| "?".to_string()).collect();
+        let sql = format!(
+            "SELECT id, {column} FROM WayLabels WHERE id IN ({})",
+            placeholders.join(",")
+        );
+        let mut stmt = self.conn.prepare(&sql)?;
+        let params: Vec<&dyn rusqlite::types::ToSql> = way_ids
+            .iter()
+            .map(|id| id as &dyn rusqlite::types::ToSql)
+            .collect();
+        let rows = stmt.query_map(params.as_slice(), |row| {
+            Ok((row.get::<_, WayId>(0)?, row.get::<_, String>(1)?))
+        })?;
 
-        if let Some(n) = next_closest {
-            results.push(n)
+        let mut result = HashMap::new();
+        for row in rows {
+            let (id, name) = row?;
+            result.insert(id, name);
         }
+        Ok(result)
+    }
+}
 
-        Ok(results)
+impl GraphRepository for SqliteGraphRepository {
+    fn get_way_nodes_near(
+        &self,
+        center: Point,
+        radius: f64,
+    ) -> Result<Vec<(Node, WayId)>, anyhow::Error> {
+        query_way_nodes_near(&self.conn, center, radius)
     }
 
     /// given a NodeId, gets the neighbors from the Segments table
@@ -164,6 +308,7 @@ impl GraphRepository for SqliteGraphRepository {
                 distance: row.get(4)?,
                 elevation_gain: row.get(5)?,
                 elevation_loss: row.get(6)?,
+                shape: None,
             })
         })?;
 
@@ -179,7 +324,7 @@ impl GraphRepository for SqliteGraphRepository {
         // flamegraphs show we spend 95%+ of our time in this query
         let mut stmt = self.conn.prepare_cached(
             "
-            SELECT way, n2, N2.lon, N2.lat, distance, WL.cycleway, WL.road, WL.salmon, S.elevation_gain, S.elevation_loss
+            SELECT way, n2, N2.lon, N2.lat, distance, WL.cycleway, WL.road, WL.salmon, WL.network, WL.surface, WL.lts, S.elevation_gain, S.elevation_loss
             FROM Segments S
             JOIN Nodes as N2 ON n2=N2.id
             JOIN WayLabels as WL ON way=WL.id
@@ -192,10 +337,56 @@ impl GraphRepository for SqliteGraphRepository {
                     way: row.get(0)?,
                     node: Node::new(row.get(1)?, &Point::new(row.get(2)?, row.get(3)?)),
                     distance: row.get(4)?,
-                    elevation_gain: row.get(8)?,
-                    elevation_loss: row.get(9)?,
+                    elevation_gain: row.get(11)?,
+                    elevation_loss: row.get(12)?,
+                    shape: None,
                 },
-                (row.get(5)?, row.get(6)?, row.get(7)?),
+                (
+                    row.get(5)?,
+                    row.get(6)?,
+                    row.get(7)?,
+                    row.get(8)?,
+                    row.get(9)?,
+                    row.get(10)?,
+                ),
+            ))
+        })?;
+
+        Ok(result.map(|r| r.unwrap()).collect())
+    }
+
+    /// given a NodeId, gets the Segments that end at it, along with the labels of those Segments' Ways
+    fn get_incoming_neighbors_with_labels(
+        &self,
+        id: NodeId,
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        let mut stmt = self.conn.prepare_cached(
+            "
+            SELECT way, n1, N1.lon, N1.lat, distance, WL.cycleway, WL.road, WL.salmon, WL.network, WL.surface, WL.lts, S.elevation_gain, S.elevation_loss
+            FROM Segments S
+            JOIN Nodes as N1 ON n1=N1.id
+            JOIN WayLabels as WL ON way=WL.id
+            WHERE n2 = ?1
+        ",
+        )?;
+        let result = stmt.query_map([id], |row| {
+            Ok((
+                Neighbor {
+                    way: row.get(0)?,
+                    node: Node::new(row.get(1)?, &Point::new(row.get(2)?, row.get(3)?)),
+                    distance: row.get(4)?,
+                    elevation_gain: row.get(11)?,
+                    elevation_loss: row.get(12)?,
+                    shape: None,
+                },
+                (
+                    row.get(5)?,
+                    row.get(6)?,
+                    row.get(7)?,
+                    row.get(8)?,
+                    row.get(9)?,
+                    row.get(10)?,
+                ),
             ))
         })?;
 
@@ -205,42 +396,42 @@ impl GraphRepository for SqliteGraphRepository {
     fn get_way_labels(&self, way: WayId) -> Result<WayLabels, anyhow::Error> {
         let mut stmt = self.conn.prepare_cached(
             "
-            SELECT cycleway, road, salmon
+            SELECT cycleway, road, salmon, network, surface, lts
             FROM WayLabels
             WHERE id = ?1
         ",
         )?;
 
-        let results = stmt.query_row([way], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        let results = stmt.query_row([way], |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            ))
+        })?;
 
         Ok(results)
     }
 
     fn get_way_names(&self, way_ids: &[WayId]) -> Result<HashMap<WayId, String>, anyhow::Error> {
-        if way_ids.is_empty() {
-            return Ok(HashMap::new());
-        }
+        self.query_way_text("name", way_ids)
+    }
 
-        let placeholders: Vec<String> = way_ids.iter().map(|_| "?".to_string()).collect();
-        let sql = format!(
-            "SELECT id, name FROM WayLabels WHERE id IN ({})",
-            placeholders.join(",")
-        );
-        let mut stmt = self.conn.prepare(&sql)?;
-        let params: Vec<&dyn rusqlite::types::ToSql> = way_ids
-            .iter()
-            .map(|id| id as &dyn rusqlite::types::ToSql)
-            .collect();
-        let rows = stmt.query_map(params.as_slice(), |row| {
-            Ok((row.get::<_, WayId>(0)?, row.get::<_, String>(1)?))
-        })?;
+    fn get_route_names(&self, way_ids: &[WayId]) -> Result<HashMap<WayId, String>, anyhow::Error> {
+        let mut names = self.query_way_text("route", way_ids)?;
+        names.retain(|_, name| !name.is_empty());
+        Ok(names)
+    }
 
-        let mut result = HashMap::new();
-        for row in rows {
-            let (id, name) = row?;
-            result.insert(id, name);
-        }
-        Ok(result)
+    fn find_ways_named(&self, text: &str) -> Result<Vec<WayId>, anyhow::Error> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT id FROM WayLabels WHERE instr(lower(name), lower(?1)) > 0")?;
+        let ways = stmt.query_map([text], |row| row.get(0))?;
+        Ok(ways.collect::<Result<_, _>>()?)
     }
 
     fn get_nodes_with_edge_to(
@@ -278,4 +469,146 @@ impl GraphRepository for SqliteGraphRepository {
         }
         Ok(result)
     }
+
+    fn is_closed(&self, way: WayId, at: NaiveDateTime) -> Result<bool, anyhow::Error> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT tag, open, hours FROM WayRestrictions WHERE way = ?1 ORDER BY rowid",
+        )?;
+        let restrictions = stmt
+            .query_map([way.abs()], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?))
+            })?
+            .map(|row| {
+                let (tag, open, hours) = row?;
+                Ok(Restriction {
+                    tag,
+                    open,
+                    hours: OpeningHours::parse(&hours)?,
+                })
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+        Ok(Restriction::is_closed(&restrictions, at))
+    }
+
+    fn is_turn_allowed(
+        &self,
+        from_way: WayId,
+        via: NodeId,
+        to_way: WayId,
+    ) -> Result<bool, anyhow::Error> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT from_way, to_way, only FROM TurnRestrictions WHERE via = ?1 AND from_way = ?2",
+        )?;
+        let restrictions = stmt
+            .query_map((via, from_way.abs()), |row| {
+                Ok(TurnRestriction {
+                    from: row.get(0)?,
+                    via,
+                    to: row.get(1)?,
+                    only: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(TurnRestriction::is_allowed(&restrictions, from_way, to_way))
+    }
+
+    fn get_control(&self, node: NodeId) -> Result<Control, anyhow::Error> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT control FROM NodeControls WHERE node = ?1")?;
+        Ok(stmt
+            .query_row([node], |row| row.get(0))
+            .optional()?
+            .unwrap_or_default())
+    }
+}
+
+/// Loads every Way's restrictions, keyed by the positive Way. Graphs built before restrictions
+/// were recorded simply have none.
+pub(super) fn load_restrictions(
+    conn: &DBConnection,
+) -> Result<HashMap<WayId, Vec<Restriction>>, anyhow::Error> {
+    let has_table: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'WayRestrictions'",
+        [],
+        |row| row.get(0),
+    )?;
+    let mut restrictions: HashMap<WayId, Vec<Restriction>> = HashMap::new();
+    if !has_table {
+        return Ok(restrictions);
+    }
+
+    // in the order they were recorded, which decides between restrictions on the same tag
+    let mut stmt =
+        conn.prepare("SELECT way, tag, open, hours FROM WayRestrictions ORDER BY rowid")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let hours: String = row.get(3)?;
+        restrictions
+            .entry(row.get(0)?)
+            .or_default()
+            .push(Restriction {
+                tag: row.get(1)?,
+                open: row.get(2)?,
+                hours: OpeningHours::parse(&hours)?,
+            });
+    }
+    Ok(restrictions)
+}
+
+/// Loads every turn restriction, keyed by the Node it applies at. As with Way restrictions, graphs
+/// built before they were recorded simply have none.
+pub(super) fn load_turn_restrictions(
+    conn: &DBConnection,
+) -> Result<HashMap<NodeId, Vec<TurnRestriction>>, anyhow::Error> {
+    let has_table: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'TurnRestrictions'",
+        [],
+        |row| row.get(0),
+    )?;
+    let mut restrictions: HashMap<NodeId, Vec<TurnRestriction>> = HashMap::new();
+    if !has_table {
+        return Ok(restrictions);
+    }
+
+    let mut stmt = conn.prepare("SELECT from_way, via, to_way, only FROM TurnRestrictions")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let restriction = TurnRestriction {
+            from: row.get(0)?,
+            via: row.get(1)?,
+            to: row.get(2)?,
+            only: row.get(3)?,
+        };
+        restrictions
+            .entry(restriction.via)
+            .or_default()
+            .push(restriction);
+    }
+    Ok(restrictions)
+}
+
+/// Loads the traffic control device at every Node with one. As with turn restrictions, graphs
+/// built before they were recorded simply have none.
+pub(super) fn load_controls(
+    conn: &DBConnection,
+) -> Result<HashMap<NodeId, Control>, anyhow::Error> {
+    let has_table: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'NodeControls'",
+        [],
+        |row| row.get(0),
+    )?;
+    let mut controls = HashMap::new();
+    if !has_table {
+        return Ok(controls);
+    }
+
+    let mut stmt = conn.prepare("SELECT node, control FROM NodeControls")?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        controls.insert(row.get(0)?, row.get(1)?);
+    }
+    Ok(controls)
 }
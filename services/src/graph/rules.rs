@@ -0,0 +1,244 @@
+use super::{Cost, GraphRepository, RoutingError};
+use crate::osm::{BikeNetwork, Cycleway, Lts, Road, Surface, WayId, WayLabels};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// One value to match, or any of a list of them
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum AnyOf<T> {
+    One(T),
+    Any(Vec<T>),
+}
+
+impl<T: PartialEq> AnyOf<T> {
+    fn matches(&self, value: &T) -> bool {
+        match self {
+            AnyOf::One(one) => one == value,
+            AnyOf::Any(any) => any.contains(value),
+        }
+    }
+}
+
+/// What a Way must be for a rule to apply to it. Every field given must match; a condition
+/// with none matches every Way.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleCondition {
+    pub cycleway: Option<AnyOf<Cycleway>>,
+    pub road: Option<AnyOf<Road>>,
+    /// whether the Way is ridden against its traffic
+    pub salmon: Option<bool>,
+    pub bike_network: Option<AnyOf<BikeNetwork>>,
+    pub surface: Option<AnyOf<Surface>>,
+    pub lts: Option<AnyOf<Lts>>,
+    /// text the street name contains, ignoring case
+    pub name: Option<String>,
+}
+
+impl RuleCondition {
+    /// whether Ways with these labels, ridden against traffic or not, meet the condition. Names
+    /// are matched separately, once per request.
+    fn matches_labels(&self, way_labels: &WayLabels, salmon: bool) -> bool {
+        let (cycleway, road, _, network, surface, lts) = way_labels;
+        self.cycleway.as_ref().is_none_or(|c| c.matches(cycleway))
+            && self.road.as_ref().is_none_or(|r| r.matches(road))
+            && self.salmon.is_none_or(|s| s == salmon)
+            && self
+                .bike_network
+                .as_ref()
+                .is_none_or(|n| n.matches(network))
+            && self.surface.as_ref().is_none_or(|s| s.matches(surface))
+            && self.lts.as_ref().is_none_or(|l| l.matches(lts))
+    }
+}
+
+/// Scales the cost of riding every Way meeting a condition, in the spirit of GraphHopper's
+/// custom models, e.g. `{"if": {"road": "Collector", "cycleway": "No"}, "multiply_by": 1.5}`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CostRule {
+    #[serde(rename = "if")]
+    pub condition: RuleCondition,
+    pub multiply_by: Cost,
+}
+
+/// The number of distinct WayLabels a rule can tell apart, counting the salmon flag
+const LABEL_COMBINATIONS: usize = 4 * 5 * 2 * 4 * 4 * 4;
+
+/// A CostModel's rules, compiled for the traversal hot path. Rules over labels alone are folded
+/// into one multiplier per combination of labels when the model is built; rules over names are
+/// resolved to the Ways they match once per request, with `resolve_names`.
+#[derive(Debug, Clone, Default)]
+pub struct CostRules {
+    rules: Vec<CostRule>,
+    /// the product of every label-only rule's multiplier, indexed by `label_index`. Empty when
+    /// there are no such rules.
+    multipliers: Arc<[Cost]>,
+    /// the name rules matching each Way, by unsigned WayId, once resolved
+    named_ways: Option<Arc<HashMap<WayId, Vec<usize>>>>,
+}
+
+/// Rules are compared as written, so a model compiled against the graph still equals the one
+/// it came from (and any contraction hierarchy or landmarks built for it).
+impl PartialEq for CostRules {
+    fn eq(&self, other: &Self) -> bool {
+        self.rules == other.rules
+    }
+}
+
+fn label_index(way_labels: &WayLabels, salmon: bool) -> usize {
+    let (cycleway, road, _, network, surface, lts) = way_labels;
+    let index = *cycleway as usize;
+    let index = index * 5 + *road as usize;
+    let index = index * 2 + salmon as usize;
+    let index = index * 4 + *network as usize;
+    let index = index * 4 + *surface as usize;
+    index * 4 + (*lts as usize - 1)
+}
+
+/// every combination of WayLabels, along with whether it's ridden against traffic, in
+/// `label_index` order
+fn all_labels() -> impl Iterator<Item = (WayLabels, bool)> {
+    use itertools::iproduct;
+    iproduct!(
+        [
+            Cycleway::No,
+            Cycleway::Shared,
+            Cycleway::Lane,
+            Cycleway::Track
+        ],
+        [
+            Road::Pedestrian,
+            Road::Bike,
+            Road::Local,
+            Road::Collector,
+            Road::Arterial
+        ],
+        [false, true],
+        [
+            BikeNetwork::None,
+            BikeNetwork::Local,
+            BikeNetwork::Regional,
+            BikeNetwork::National
+        ],
+        [
+            Surface::Paved,
+            Surface::Rough,
+            Surface::Cobbled,
+            Surface::Unpaved
+        ],
+        [Lts::Lts1, Lts::Lts2, Lts::Lts3, Lts::Lts4]
+    )
+    .map(|(cycleway, road, salmon, network, surface, lts)| {
+        ((cycleway, road, salmon, network, surface, lts), salmon)
+    })
+}
+
+impl CostRules {
+    pub fn new(rules: Vec<CostRule>) -> Self {
+        let label_rules: Vec<_> = rules
+            .iter()
+            .filter(|rule| rule.condition.name.is_none())
+            .collect();
+        let multipliers: Arc<[Cost]> = if label_rules.is_empty() {
+            Arc::from([])
+        } else {
+            all_labels()
+                .map(|(labels, salmon)| {
+                    label_rules
+                        .iter()
+                        .filter(|rule| rule.condition.matches_labels(&labels, salmon))
+                        .map(|rule| rule.multiply_by)
+                        .product()
+                })
+                .collect()
+        };
+        debug_assert!(multipliers.is_empty() || multipliers.len() == LABEL_COMBINATIONS);
+        Self {
+            rules,
+            multipliers,
+            named_ways: None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn validate(&self) -> Result<(), RoutingError> {
+        for (i, rule) in self.rules.iter().enumerate() {
+            // a free ride would leave the search nothing to tell routes apart by
+            if !rule.multiply_by.is_finite() || rule.multiply_by <= 0.0 {
+                return Err(RoutingError::InvalidCostModel {
+                    reason: format!(
+                        "rules[{i}].multiply_by must be a positive number, got {}",
+                        rule.multiply_by
+                    ),
+                });
+            }
+            if rule
+                .condition
+                .name
+                .as_ref()
+                .is_some_and(|name| name.trim().is_empty())
+            {
+                return Err(RoutingError::InvalidCostModel {
+                    reason: format!("rules[{i}].if.name must not be empty"),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the Ways each name rule applies to. Rules only need resolving once, however many
+    /// searches they're then used for.
+    pub fn resolve_names(&mut self, db: &dyn GraphRepository) -> Result<(), anyhow::Error> {
+        if self.named_ways.is_some() {
+            return Ok(());
+        }
+        let mut named_ways: HashMap<WayId, Vec<usize>> = HashMap::new();
+        for (i, rule) in self.rules.iter().enumerate() {
+            if let Some(name) = &rule.condition.name {
+                for way in db.find_ways_named(name.trim())? {
+                    let rules = named_ways.entry(way.abs()).or_default();
+                    if !rules.contains(&i) {
+                        rules.push(i);
+                    }
+                }
+            }
+        }
+        self.named_ways = Some(Arc::new(named_ways));
+        Ok(())
+    }
+
+    /// the multiplier for riding a Way with these labels, before any name rules
+    #[inline]
+    pub fn label_multiplier(&self, way_labels: &WayLabels, salmon: bool) -> Cost {
+        if self.multipliers.is_empty() {
+            1.0
+        } else {
+            self.multipliers[label_index(way_labels, salmon)]
+        }
+    }
+
+    /// the multiplier from the name rules matching the Way. Name rules that haven't been
+    /// resolved apply nowhere.
+    #[inline]
+    pub fn name_multiplier(&self, way: WayId, way_labels: &WayLabels, salmon: bool) -> Cost {
+        match self
+            .named_ways
+            .as_deref()
+            .and_then(|named| named.get(&way.abs()))
+        {
+            Some(rules) => rules
+                .iter()
+                .map(|i| &self.rules[*i])
+                .filter(|rule| rule.condition.matches_labels(way_labels, salmon))
+                .map(|rule| rule.multiply_by)
+                .product(),
+            None => 1.0,
+        }
+    }
+}
@@ -0,0 +1,896 @@
+/// A compact binary image of the routing graph, written once from the DB by `build-snapshot` and
+/// memory-mapped at startup, so that a cold start needn't load and index the whole graph before
+/// serving its first route. Pages of it are only read in as searches touch them.
+///
+/// Layout (all little-endian):
+///
+/// ```text
+/// magic (8 bytes) | version (u32) | section count (u32) | (offset u64, length u64) per section
+/// sections, in the order of `Section`
+/// ```
+///
+/// Nodes are numbered densely in order of their ids, and edges are stored in CSR form: the edges
+/// leaving Node `i` are those from `offsets[i]` up to `offsets[i + 1]`. The snapshot carries
+/// everything routing reads from the graph, including the spatial index used for snapping and
+/// the time and turn restrictions and traffic control devices, so that it can be deployed without the DB.
+use super::in_memory_repository::{InMemoryEdge, InMemoryGraphRepository};
+use super::repository::GraphRepository;
+use super::spatial::{overlaps, search_area, Grid};
+use crate::osm::{
+    BikeNetwork, Control, Cycleway, EdgeShape, Lts, Neighbor, Node, NodeId, OpeningHours,
+    Restriction, RestrictionTag, Road, ShapeId, Surface, TurnRestriction, WayId, WayLabels,
+};
+use anyhow::anyhow;
+use chrono::NaiveDateTime;
+use geo::{Point, Rect};
+use memmap2::Mmap;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+use tracing::info;
+
+const MAGIC: &[u8; 8] = b"RRGRAPH\0";
+/// bumped whenever the layout changes, so that stale snapshots are rejected rather than misread
+const VERSION: u32 = 6;
+
+/// marks a missing Node index or ShapeId
+const NONE: u32 = u32::MAX;
+/// marks labels missing for a Way, in place of its Cycleway
+const NO_LABELS: u8 = u8::MAX;
+
+/// node index (u32), way (i64), distance (i32), elevation gain and loss (i16 each), labels and
+/// the reverse Way's labels (4 bytes each)
+const EDGE_SIZE: usize = 28;
+/// an edge, then its ShapeId and the indices of the Nodes it first and last passes (u32 each)
+const COMPACTED_EDGE_SIZE: usize = EDGE_SIZE + 12;
+/// way (i64), then either labels (4 bytes) or a name's offset and length in the text (u32 each)
+const LABELS_SIZE: usize = 12;
+const NAME_SIZE: usize = 16;
+/// bounds (4 f64s), then the grid's columns and rows (u32 each)
+const GRID_SIZE: usize = 40;
+/// way (i64), then its bounding box (4 f64s)
+const BBOX_SIZE: usize = 40;
+/// way (i64), its RestrictionTag and whether it opens, then its hours' offset and length in the
+/// text (u32 each)
+const RESTRICTION_SIZE: usize = 24;
+/// from way, via node and to way (i64 each), then whether it's an only_* restriction (u32)
+const TURN_RESTRICTION_SIZE: usize = 28;
+/// node (i64), then its Control (u32)
+const CONTROL_SIZE: usize = 12;
+
+/// The sections of a snapshot, in the order they're written
+#[derive(Clone, Copy)]
+enum Section {
+    /// every Node's id (i64), sorted
+    NodeIds,
+    /// every Node's lon and lat (f64 each)
+    NodeCoords,
+    /// where each Node's Segments start in `Edges` (u32), and where the last ends
+    EdgeOffsets,
+    Edges,
+    /// where each Node's compacted edges start in `CompactedEdges` (u32). Nodes without any use
+    /// their Segments.
+    CompactedOffsets,
+    CompactedEdges,
+    /// where each chain starts in `ShapeEdges` (u32), and where the last ends
+    ShapeOffsets,
+    /// the Segments along each chain, as indices into `Edges` (u32)
+    ShapeEdges,
+    /// the ShapeId of each chain ridden the other way (u32)
+    ShapeReverse,
+    /// the ShapeIds of the chain through each Node, in either direction, or NONE (u32 each)
+    ShapesThrough,
+    /// every directed Way's labels, sorted by Way
+    WayLabels,
+    /// street names, sorted by Way, with their text
+    WayNames,
+    WayNameText,
+    /// signed bike route names, sorted by Way, with their text
+    RouteNames,
+    RouteNameText,
+    /// the grid Ways are bucketed into for snapping
+    SpatialGrid,
+    /// every Way's bounding box, sorted by Way
+    WayBounds,
+    /// where each Way's Nodes start in `WayNodes` (u32), and where the last ends
+    WayNodeOffsets,
+    /// the Nodes along each Way, in order, as Node indices (u32)
+    WayNodes,
+    /// where each grid cell's Ways start in `CellWays` (u32), and where the last ends
+    CellOffsets,
+    /// the Ways overlapping each cell, as indices into `WayBounds` (u32)
+    CellWays,
+    /// every Way's time restrictions, sorted by Way, with the text of their hours
+    Restrictions,
+    RestrictionText,
+    /// every turn restriction, sorted by the Node it's at
+    TurnRestrictions,
+    /// every Node's traffic control device, for the Nodes with one, sorted by Node
+    NodeControls,
+}
+
+const SECTION_COUNT: usize = Section::NodeControls as usize + 1;
+
+/// Tallies of what a snapshot holds
+#[derive(Debug)]
+pub struct SnapshotSummary {
+    pub nodes: usize,
+    pub edges: usize,
+    pub compacted_edges: usize,
+    pub shapes: usize,
+    pub bytes: usize,
+}
+
+/// Writes the graph, as loaded and compacted in memory, out to a snapshot at `path`
+pub fn write_snapshot(
+    graph: &InMemoryGraphRepository,
+    path: &Path,
+) -> Result<SnapshotSummary, anyhow::Error> {
+    let mut node_ids: Vec<NodeId> = graph.adjacency.keys().copied().collect();
+    let mut nodes: HashMap<NodeId, Node> = HashMap::new();
+    for edge in graph.adjacency.values().flatten() {
+        nodes.insert(edge.node.id, edge.node);
+    }
+    for node in &graph.spatial.nodes {
+        nodes.insert(node.id, *node);
+    }
+    node_ids.extend(nodes.keys().filter(|id| !graph.adjacency.contains_key(id)));
+    node_ids.sort_unstable();
+    let index: HashMap<NodeId, u32> = node_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (*id, i as u32))
+        .collect();
+
+    let mut sections: Vec<Vec<u8>> = vec![vec![]; SECTION_COUNT];
+    let mut put = |section: Section, bytes: &[u8]| sections[section as usize].extend(bytes);
+
+    for id in &node_ids {
+        put(Section::NodeIds, &id.to_le_bytes());
+        let node = nodes
+            .get(id)
+            .ok_or_else(|| anyhow!("Node {id} is never arrived at"))?;
+        put(Section::NodeCoords, &node.geometry.x().to_le_bytes());
+        put(Section::NodeCoords, &node.geometry.y().to_le_bytes());
+    }
+
+    // (from, to, way) → index into Edges, to refer to the Segments along chains by
+    let mut edge_indices: HashMap<(NodeId, NodeId, WayId), u32> = HashMap::new();
+    let mut edge_count = 0u32;
+    // ShapeId → the Node the chain sets off from
+    let mut shape_starts: HashMap<ShapeId, NodeId> = HashMap::new();
+    let mut compacted_count = 0u32;
+    for id in &node_ids {
+        put(Section::EdgeOffsets, &edge_count.to_le_bytes());
+        for edge in graph.adjacency.get(id).into_iter().flatten() {
+            put(Section::Edges, &encode_edge(graph, &index, edge));
+            edge_indices.insert((*id, edge.node.id, edge.way), edge_count);
+            edge_count += 1;
+        }
+
+        put(Section::CompactedOffsets, &compacted_count.to_le_bytes());
+        for edge in graph.compacted.edges.get(id).into_iter().flatten() {
+            put(Section::CompactedEdges, &encode_edge(graph, &index, edge));
+            let (shape, first, last) = match edge.shape {
+                Some(shape) => {
+                    shape_starts.insert(shape.id, *id);
+                    (shape.id, index[&shape.first.id], index[&shape.last.id])
+                }
+                None => (NONE, NONE, NONE),
+            };
+            for value in [shape, first, last] {
+                put(Section::CompactedEdges, &value.to_le_bytes());
+            }
+            compacted_count += 1;
+        }
+    }
+    put(Section::EdgeOffsets, &edge_count.to_le_bytes());
+    put(Section::CompactedOffsets, &compacted_count.to_le_bytes());
+
+    let mut shape_edge_count = 0u32;
+    for (id, shape) in graph.compacted.shapes.iter().enumerate() {
+        put(Section::ShapeOffsets, &shape_edge_count.to_le_bytes());
+        let mut from = shape_starts[&(id as ShapeId)];
+        for segment in &shape.segments {
+            let edge = edge_indices
+                .get(&(from, segment.node.id, segment.way))
+                .ok_or_else(|| anyhow!("Segment {from} -> {} missing", segment.node.id))?;
+            put(Section::ShapeEdges, &edge.to_le_bytes());
+            from = segment.node.id;
+            shape_edge_count += 1;
+        }
+        put(Section::ShapeReverse, &shape.reverse.to_le_bytes());
+    }
+    put(Section::ShapeOffsets, &shape_edge_count.to_le_bytes());
+
+    for id in &node_ids {
+        let through = graph
+            .compacted
+            .shapes_through
+            .get(id)
+            .copied()
+            .unwrap_or([NONE, NONE]);
+        for shape in through {
+            put(Section::ShapesThrough, &shape.to_le_bytes());
+        }
+    }
+
+    let mut way_labels: Vec<_> = graph.way_labels.iter().collect();
+    way_labels.sort_unstable_by_key(|(way, _)| **way);
+    for (way, labels) in way_labels {
+        put(Section::WayLabels, &way.to_le_bytes());
+        put(Section::WayLabels, &encode_labels(Some(*labels)));
+    }
+
+    for (names, section, text) in [
+        (&graph.way_names, Section::WayNames, Section::WayNameText),
+        (
+            &graph.route_names,
+            Section::RouteNames,
+            Section::RouteNameText,
+        ),
+    ] {
+        let mut names: Vec<_> = names.iter().collect();
+        names.sort_unstable_by_key(|(way, _)| **way);
+        let mut offset = 0u32;
+        for (way, name) in names {
+            put(section, &way.to_le_bytes());
+            put(section, &offset.to_le_bytes());
+            put(section, &(name.len() as u32).to_le_bytes());
+            put(text, name.as_bytes());
+            offset += name.len() as u32;
+        }
+    }
+
+    let spatial = &graph.spatial;
+    let bounds = spatial.grid.bounds;
+    for value in [
+        bounds.min().x,
+        bounds.min().y,
+        bounds.max().x,
+        bounds.max().y,
+    ] {
+        put(Section::SpatialGrid, &value.to_le_bytes());
+    }
+    put(Section::SpatialGrid, &spatial.grid.cols.to_le_bytes());
+    put(Section::SpatialGrid, &spatial.grid.rows.to_le_bytes());
+    for (way, bbox) in &spatial.ways {
+        put(Section::WayBounds, &way.to_le_bytes());
+        for value in [bbox.min().x, bbox.min().y, bbox.max().x, bbox.max().y] {
+            put(Section::WayBounds, &value.to_le_bytes());
+        }
+    }
+    for offset in &spatial.node_offsets {
+        put(Section::WayNodeOffsets, &offset.to_le_bytes());
+    }
+    for node in &spatial.nodes {
+        put(Section::WayNodes, &index[&node.id].to_le_bytes());
+    }
+    for offset in &spatial.cell_offsets {
+        put(Section::CellOffsets, &offset.to_le_bytes());
+    }
+    for way in &spatial.cell_ways {
+        put(Section::CellWays, &way.to_le_bytes());
+    }
+
+    let mut restrictions: Vec<_> = graph.restrictions.iter().collect();
+    restrictions.sort_unstable_by_key(|(way, _)| **way);
+    let mut offset = 0u32;
+    for (way, restriction) in restrictions
+        .into_iter()
+        .flat_map(|(way, all)| all.iter().map(move |r| (way, r)))
+    {
+        let hours = restriction.hours.as_str();
+        put(Section::Restrictions, &way.to_le_bytes());
+        put(
+            Section::Restrictions,
+            &(restriction.tag as u32).to_le_bytes(),
+        );
+        put(
+            Section::Restrictions,
+            &(restriction.open as u32).to_le_bytes(),
+        );
+        put(Section::Restrictions, &offset.to_le_bytes());
+        put(Section::Restrictions, &(hours.len() as u32).to_le_bytes());
+        put(Section::RestrictionText, hours.as_bytes());
+        offset += hours.len() as u32;
+    }
+
+    let mut turn_restrictions: Vec<_> = graph.turn_restrictions.iter().collect();
+    turn_restrictions.sort_unstable_by_key(|(via, _)| **via);
+    for restriction in turn_restrictions.into_iter().flat_map(|(_, all)| all) {
+        for value in [restriction.from, restriction.via, restriction.to] {
+            put(Section::TurnRestrictions, &value.to_le_bytes());
+        }
+        put(
+            Section::TurnRestrictions,
+            &(restriction.only as u32).to_le_bytes(),
+        );
+    }
+
+    let mut controls: Vec<_> = graph.controls.iter().collect();
+    controls.sort_unstable_by_key(|(node, _)| **node);
+    for (node, control) in controls {
+        put(Section::NodeControls, &node.to_le_bytes());
+        put(Section::NodeControls, &(*control as u32).to_le_bytes());
+    }
+
+    // written alongside and moved into place, as truncating a snapshot that's mapped elsewhere
+    // would pull it out from under that process
+    let partial = path.with_extension("partial");
+    let header_size = MAGIC.len() + 8 + SECTION_COUNT * 16;
+    let mut file = BufWriter::new(File::create(&partial)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&VERSION.to_le_bytes())?;
+    file.write_all(&(SECTION_COUNT as u32).to_le_bytes())?;
+    let mut offset = header_size as u64;
+    for section in &sections {
+        file.write_all(&offset.to_le_bytes())?;
+        file.write_all(&(section.len() as u64).to_le_bytes())?;
+        offset += section.len() as u64;
+    }
+    for section in &sections {
+        file.write_all(section)?;
+    }
+    file.flush()?;
+    drop(file);
+    std::fs::rename(&partial, path)?;
+
+    Ok(SnapshotSummary {
+        nodes: node_ids.len(),
+        edges: edge_count as usize,
+        compacted_edges: compacted_count as usize,
+        shapes: graph.compacted.shapes.len(),
+        bytes: offset as usize,
+    })
+}
+
+fn encode_edge(
+    graph: &InMemoryGraphRepository,
+    index: &HashMap<NodeId, u32>,
+    edge: &InMemoryEdge,
+) -> [u8; EDGE_SIZE] {
+    let mut bytes = [0; EDGE_SIZE];
+    bytes[0..4].copy_from_slice(&index[&edge.node.id].to_le_bytes());
+    bytes[4..12].copy_from_slice(&edge.way.to_le_bytes());
+    bytes[12..16].copy_from_slice(&edge.distance.to_le_bytes());
+    bytes[16..18].copy_from_slice(&edge.elevation_gain.to_le_bytes());
+    bytes[18..20].copy_from_slice(&edge.elevation_loss.to_le_bytes());
+    bytes[20..24].copy_from_slice(&encode_labels(Some(edge.labels)));
+    let reverse = graph.way_labels.get(&-edge.way).copied();
+    bytes[24..28].copy_from_slice(&encode_labels(reverse));
+    bytes
+}
+
+fn encode_labels(labels: Option<WayLabels>) -> [u8; 4] {
+    match labels {
+        // the salmoning flag and LTS, and the network and surface, each fit in half a byte
+        Some((cycleway, road, salmon, network, surface, lts)) => [
+            cycleway as u8,
+            road as u8,
+            salmon as u8 | (lts as u8) << 4,
+            network as u8 | (surface as u8) << 4,
+        ],
+        None => [NO_LABELS, 0, 0, 0],
+    }
+}
+
+fn decode_labels(bytes: &[u8]) -> Option<WayLabels> {
+    if bytes[0] == NO_LABELS {
+        return None;
+    }
+    // only ever written from valid labels, just as the DB's are read back
+    Some(unsafe {
+        (
+            std::mem::transmute::<u8, Cycleway>(bytes[0]),
+            std::mem::transmute::<u8, Road>(bytes[1]),
+            bytes[2] & 0xf != 0,
+            std::mem::transmute::<u8, BikeNetwork>(bytes[3] & 0xf),
+            std::mem::transmute::<u8, Surface>(bytes[3] >> 4),
+            std::mem::transmute::<u8, Lts>(bytes[2] >> 4),
+        )
+    })
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn read_i64(bytes: &[u8], at: usize) -> i64 {
+    i64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+fn read_f64(bytes: &[u8], at: usize) -> f64 {
+    f64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// Graph repository over a memory-mapped snapshot, needing no DB. The handful of time and turn
+/// restrictions are read out of it at startup; everything else is read in place.
+pub struct SnapshotGraphRepository {
+    snapshot: Mmap,
+    sections: [Range<usize>; SECTION_COUNT],
+    grid: Grid,
+    /// positive Way ID → the schedules on which it opens or closes, for the few Ways that have any
+    restrictions: HashMap<WayId, Vec<Restriction>>,
+    /// Node ID → the turn restrictions at it, for the few Nodes that have any
+    turn_restrictions: HashMap<NodeId, Vec<TurnRestriction>>,
+    /// Node ID → the traffic control device at it, for the few Nodes that have one
+    controls: HashMap<NodeId, Control>,
+}
+
+impl SnapshotGraphRepository {
+    /// Maps the snapshot at `path`
+    pub fn open(path: &Path) -> Result<Self, anyhow::Error> {
+        let file = File::open(path)?;
+        // the snapshot is only ever replaced whole by `write_snapshot`, never modified in place
+        let snapshot = unsafe { Mmap::map(&file)? };
+
+        if snapshot.len() < MAGIC.len() + 8 || &snapshot[..MAGIC.len()] != MAGIC {
+            return Err(anyhow!("{} is not a graph snapshot", path.display()));
+        }
+        let version = read_u32(&snapshot, MAGIC.len());
+        if version != VERSION {
+            return Err(anyhow!(
+                "{} is a version {version} graph snapshot, where version {VERSION} is needed; rebuild it with build-snapshot",
+                path.display()
+            ));
+        }
+        let count = read_u32(&snapshot, MAGIC.len() + 4) as usize;
+        if count != SECTION_COUNT || snapshot.len() < MAGIC.len() + 8 + count * 16 {
+            return Err(anyhow!("{} is truncated", path.display()));
+        }
+        let sections: [Range<usize>; SECTION_COUNT] = std::array::from_fn(|i| {
+            let at = MAGIC.len() + 8 + i * 16;
+            let offset = read_u64(&snapshot, at) as usize;
+            offset..offset + read_u64(&snapshot, at + 8) as usize
+        });
+        if sections.iter().any(|range| range.end > snapshot.len()) {
+            return Err(anyhow!("{} is truncated", path.display()));
+        }
+
+        let grid_bytes = &snapshot[sections[Section::SpatialGrid as usize].clone()];
+        if grid_bytes.len() != GRID_SIZE {
+            return Err(anyhow!("{} is truncated", path.display()));
+        }
+        let grid = Grid {
+            bounds: Rect::new(
+                (read_f64(grid_bytes, 0), read_f64(grid_bytes, 8)),
+                (read_f64(grid_bytes, 16), read_f64(grid_bytes, 24)),
+            ),
+            cols: read_u32(grid_bytes, 32),
+            rows: read_u32(grid_bytes, 36),
+        };
+
+        let mut repository = Self {
+            snapshot,
+            sections,
+            grid,
+            restrictions: HashMap::new(),
+            turn_restrictions: HashMap::new(),
+            controls: HashMap::new(),
+        };
+        repository.restrictions = repository.read_restrictions()?;
+        repository.turn_restrictions = repository.read_turn_restrictions();
+        repository.controls = repository.read_controls();
+        info!(
+            "Graph snapshot mapped: {} nodes, {} edges, {} restricted ways, {} restricted turns, {} controlled nodes",
+            repository.node_count(),
+            repository.section(Section::Edges).len() / EDGE_SIZE,
+            repository.restrictions.len(),
+            repository
+                .turn_restrictions
+                .values()
+                .map(Vec::len)
+                .sum::<usize>(),
+            repository.controls.len()
+        );
+        Ok(repository)
+    }
+
+    /// The union of every Way's bounding box
+    pub fn bounds(&self) -> Rect {
+        self.grid.bounds
+    }
+
+    fn read_restrictions(&self) -> Result<HashMap<WayId, Vec<Restriction>>, anyhow::Error> {
+        let text = self.section(Section::RestrictionText);
+        let mut restrictions: HashMap<WayId, Vec<Restriction>> = HashMap::new();
+        for entry in self
+            .section(Section::Restrictions)
+            .chunks_exact(RESTRICTION_SIZE)
+        {
+            let offset = read_u32(entry, 16) as usize;
+            let hours = &text[offset..offset + read_u32(entry, 20) as usize];
+            // only ever written from valid RestrictionTags, just as the DB's are read back
+            let tag = read_u32(entry, 8) as u8;
+            restrictions
+                .entry(read_i64(entry, 0))
+                .or_default()
+                .push(Restriction {
+                    tag: unsafe { std::mem::transmute::<u8, RestrictionTag>(tag) },
+                    open: read_u32(entry, 12) != 0,
+                    hours: OpeningHours::parse(std::str::from_utf8(hours)?)?,
+                });
+        }
+        Ok(restrictions)
+    }
+
+    fn read_turn_restrictions(&self) -> HashMap<NodeId, Vec<TurnRestriction>> {
+        let mut restrictions: HashMap<NodeId, Vec<TurnRestriction>> = HashMap::new();
+        for entry in self
+            .section(Section::TurnRestrictions)
+            .chunks_exact(TURN_RESTRICTION_SIZE)
+        {
+            let restriction = TurnRestriction {
+                from: read_i64(entry, 0),
+                via: read_i64(entry, 8),
+                to: read_i64(entry, 16),
+                only: read_u32(entry, 24) != 0,
+            };
+            restrictions
+                .entry(restriction.via)
+                .or_default()
+                .push(restriction);
+        }
+        restrictions
+    }
+
+    fn read_controls(&self) -> HashMap<NodeId, Control> {
+        self.section(Section::NodeControls)
+            .chunks_exact(CONTROL_SIZE)
+            // only ever written from valid Controls, just as the DB's are read back
+            .map(|entry| {
+                let control = read_u32(entry, 8) as u8;
+                (read_i64(entry, 0), unsafe {
+                    std::mem::transmute::<u8, Control>(control)
+                })
+            })
+            .collect()
+    }
+
+    fn section(&self, section: Section) -> &[u8] {
+        &self.snapshot[self.sections[section as usize].clone()]
+    }
+
+    fn node_count(&self) -> usize {
+        self.section(Section::NodeIds).len() / 8
+    }
+
+    /// The dense index of the Node, found by binary search over the sorted ids
+    fn node_index(&self, id: NodeId) -> Option<u32> {
+        let ids = self.section(Section::NodeIds);
+        let (mut low, mut high) = (0, self.node_count());
+        while low < high {
+            let mid = (low + high) / 2;
+            match read_i64(ids, mid * 8).cmp(&id) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(mid as u32),
+            }
+        }
+        None
+    }
+
+    fn node(&self, index: u32) -> Node {
+        let at = index as usize;
+        let coords = self.section(Section::NodeCoords);
+        Node::new(
+            read_i64(self.section(Section::NodeIds), at * 8),
+            &Point::new(read_f64(coords, at * 16), read_f64(coords, at * 16 + 8)),
+        )
+    }
+
+    /// The range of entries in a CSR section belonging to the item at `index`
+    fn csr_range(&self, offsets: Section, index: u32) -> Range<usize> {
+        let offsets = self.section(offsets);
+        let at = index as usize * 4;
+        read_u32(offsets, at) as usize..read_u32(offsets, at + 4) as usize
+    }
+
+    /// The edge at `at` in an edge section, with the reverse Way's labels
+    fn edge(&self, edges: Section, size: usize, at: usize) -> (InMemoryEdge, Option<WayLabels>) {
+        let bytes = &self.section(edges)[at * size..(at + 1) * size];
+        let shape =
+            (size == COMPACTED_EDGE_SIZE && read_u32(bytes, EDGE_SIZE) != NONE).then(|| {
+                EdgeShape {
+                    id: read_u32(bytes, EDGE_SIZE),
+                    first: self.node(read_u32(bytes, EDGE_SIZE + 4)),
+                    last: self.node(read_u32(bytes, EDGE_SIZE + 8)),
+                }
+            });
+        let edge = InMemoryEdge {
+            node: self.node(read_u32(bytes, 0)),
+            way: read_i64(bytes, 4),
+            distance: i32::from_le_bytes(bytes[12..16].try_into().unwrap()),
+            elevation_gain: i16::from_le_bytes(bytes[16..18].try_into().unwrap()),
+            elevation_loss: i16::from_le_bytes(bytes[18..20].try_into().unwrap()),
+            labels: decode_labels(&bytes[20..24]).expect("every Segment is labeled"),
+            shape,
+        };
+        (edge, decode_labels(&bytes[24..28]))
+    }
+
+    fn edges(&self, id: NodeId) -> Vec<(InMemoryEdge, Option<WayLabels>)> {
+        let Some(index) = self.node_index(id) else {
+            return vec![];
+        };
+        self.csr_range(Section::EdgeOffsets, index)
+            .map(|at| self.edge(Section::Edges, EDGE_SIZE, at))
+            .collect()
+    }
+
+    /// The compacted edges leaving the Node, or None if it starts no chain
+    fn compacted_edges(&self, id: NodeId) -> Option<Vec<(InMemoryEdge, Option<WayLabels>)>> {
+        let index = self.node_index(id)?;
+        let range = self.csr_range(Section::CompactedOffsets, index);
+        if range.is_empty() {
+            return None;
+        }
+        Some(
+            range
+                .map(|at| self.edge(Section::CompactedEdges, COMPACTED_EDGE_SIZE, at))
+                .collect(),
+        )
+    }
+
+    /// The first Segment along a chain
+    fn first_segment(&self, shape: ShapeId) -> (InMemoryEdge, Option<WayLabels>) {
+        let at = self.csr_range(Section::ShapeOffsets, shape).start;
+        let edge = read_u32(self.section(Section::ShapeEdges), at * 4);
+        self.edge(Section::Edges, EDGE_SIZE, edge as usize)
+    }
+
+    /// The chains through any of the Nodes
+    fn shapes_through_any(&self, nodes: &[NodeId]) -> Vec<ShapeId> {
+        let through = self.section(Section::ShapesThrough);
+        nodes
+            .iter()
+            .filter_map(|id| self.node_index(*id))
+            .flat_map(|index| {
+                let at = index as usize * 8;
+                [read_u32(through, at), read_u32(through, at + 4)]
+            })
+            .filter(|shape| *shape != NONE)
+            .collect()
+    }
+
+    /// Looks up the Way in a section sorted by Way, returning its entry
+    fn find_way(&self, section: Section, size: usize, way: WayId) -> Option<&[u8]> {
+        let entries = self.section(section);
+        let (mut low, mut high) = (0, entries.len() / size);
+        while low < high {
+            let mid = (low + high) / 2;
+            match read_i64(entries, mid * size).cmp(&way) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(&entries[mid * size..(mid + 1) * size]),
+            }
+        }
+        None
+    }
+
+    fn get_names(
+        &self,
+        section: Section,
+        text: Section,
+        way_ids: &[WayId],
+    ) -> HashMap<WayId, String> {
+        way_ids
+            .iter()
+            .filter_map(|way| {
+                let entry = self.find_way(section, NAME_SIZE, *way)?;
+                let offset = read_u32(entry, 8) as usize;
+                let name = &self.section(text)[offset..offset + read_u32(entry, 12) as usize];
+                Some((*way, String::from_utf8_lossy(name).into_owned()))
+            })
+            .collect()
+    }
+}
+
+impl GraphRepository for SnapshotGraphRepository {
+    fn get_way_nodes_near(
+        &self,
+        center: Point,
+        radius: f64,
+    ) -> Result<Vec<(Node, WayId)>, anyhow::Error> {
+        let area = search_area(center, radius);
+        let (bounds, cell_ways) = (
+            self.section(Section::WayBounds),
+            self.section(Section::CellWays),
+        );
+        let mut found: Vec<u32> = self
+            .grid
+            .cells(area)
+            .flat_map(|cell| self.csr_range(Section::CellOffsets, cell as u32))
+            .map(|at| read_u32(cell_ways, at * 4))
+            .filter(|i| {
+                let at = *i as usize * BBOX_SIZE;
+                let bbox = Rect::new(
+                    (read_f64(bounds, at + 8), read_f64(bounds, at + 16)),
+                    (read_f64(bounds, at + 24), read_f64(bounds, at + 32)),
+                );
+                overlaps(&bbox, &area)
+            })
+            .collect();
+        // Ways spanning several cells are found in each
+        found.sort_unstable();
+        found.dedup();
+
+        let way_nodes = self.section(Section::WayNodes);
+        Ok(found
+            .into_iter()
+            .flat_map(|i| {
+                let way = read_i64(bounds, i as usize * BBOX_SIZE);
+                self.csr_range(Section::WayNodeOffsets, i)
+                    .map(move |at| (self.node(read_u32(way_nodes, at * 4)), way))
+            })
+            .collect())
+    }
+
+    fn get_neighbors(&self, id: NodeId) -> Result<Vec<Neighbor>, anyhow::Error> {
+        Ok(self.edges(id).iter().map(|(e, _)| e.neighbor()).collect())
+    }
+
+    fn get_neighbors_with_labels(
+        &self,
+        id: NodeId,
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        Ok(self
+            .edges(id)
+            .iter()
+            .map(|(e, _)| (e.neighbor(), e.labels))
+            .collect())
+    }
+
+    fn get_incoming_neighbors_with_labels(
+        &self,
+        id: NodeId,
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        Ok(self
+            .edges(id)
+            .iter()
+            .filter_map(|(e, reverse)| Some((e.mirrored(None), (*reverse)?)))
+            .collect())
+    }
+
+    fn get_compacted_neighbors_with_labels(
+        &self,
+        id: NodeId,
+        stops: &[NodeId],
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        let Some(edges) = self.compacted_edges(id) else {
+            return self.get_neighbors_with_labels(id);
+        };
+        let open = self.shapes_through_any(stops);
+        Ok(edges
+            .iter()
+            .map(|(e, _)| match e.shape {
+                // a chain through a stop is set off along Segment by Segment instead
+                Some(shape) if open.contains(&shape.id) => {
+                    (self.first_segment(shape.id).0.neighbor(), e.labels)
+                }
+                _ => (e.neighbor(), e.labels),
+            })
+            .collect())
+    }
+
+    fn get_compacted_incoming_neighbors_with_labels(
+        &self,
+        id: NodeId,
+        stops: &[NodeId],
+    ) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+        let Some(edges) = self.compacted_edges(id) else {
+            return self.get_incoming_neighbors_with_labels(id);
+        };
+        let open = self.shapes_through_any(stops);
+        let reverse_shapes = self.section(Section::ShapeReverse);
+        Ok(edges
+            .iter()
+            .filter_map(|(e, reverse)| {
+                let neighbor = match e.shape {
+                    Some(shape) if open.contains(&shape.id) => {
+                        self.first_segment(shape.id).0.mirrored(None)
+                    }
+                    Some(shape) => e.mirrored(Some(EdgeShape {
+                        id: read_u32(reverse_shapes, shape.id as usize * 4),
+                        first: shape.last,
+                        last: shape.first,
+                    })),
+                    None => e.mirrored(None),
+                };
+                Some((neighbor, (*reverse)?))
+            })
+            .collect())
+    }
+
+    fn get_shape(&self, shape: ShapeId) -> Result<Vec<Neighbor>, anyhow::Error> {
+        if shape as usize >= self.section(Section::ShapeReverse).len() / 4 {
+            return Err(anyhow!("no compacted edge has shape {shape}"));
+        }
+        let shape_edges = self.section(Section::ShapeEdges);
+        Ok(self
+            .csr_range(Section::ShapeOffsets, shape)
+            .map(|at| {
+                let edge = read_u32(shape_edges, at * 4) as usize;
+                self.edge(Section::Edges, EDGE_SIZE, edge).0.neighbor()
+            })
+            .collect())
+    }
+
+    fn get_way_labels(&self, way: WayId) -> Result<WayLabels, anyhow::Error> {
+        self.find_way(Section::WayLabels, LABELS_SIZE, way)
+            .and_then(|entry| decode_labels(&entry[8..12]))
+            .ok_or_else(|| anyhow!("Way {way} has no labels"))
+    }
+
+    fn get_way_names(&self, way_ids: &[WayId]) -> Result<HashMap<WayId, String>, anyhow::Error> {
+        Ok(self.get_names(Section::WayNames, Section::WayNameText, way_ids))
+    }
+
+    fn get_route_names(&self, way_ids: &[WayId]) -> Result<HashMap<WayId, String>, anyhow::Error> {
+        Ok(self.get_names(Section::RouteNames, Section::RouteNameText, way_ids))
+    }
+
+    fn find_ways_named(&self, text: &str) -> Result<Vec<WayId>, anyhow::Error> {
+        let text = text.to_lowercase();
+        let (entries, names) = (
+            self.section(Section::WayNames),
+            self.section(Section::WayNameText),
+        );
+        Ok(entries
+            .chunks_exact(NAME_SIZE)
+            .filter(|entry| {
+                let offset = read_u32(entry, 8) as usize;
+                let name = &names[offset..offset + read_u32(entry, 12) as usize];
+                String::from_utf8_lossy(name).to_lowercase().contains(&text)
+            })
+            .map(|entry| read_i64(entry, 0))
+            .collect())
+    }
+
+    fn get_nodes_with_edge_to(
+        &self,
+        from_nodes: &[NodeId],
+        to_nodes: &[NodeId],
+    ) -> Result<HashSet<NodeId>, anyhow::Error> {
+        let to_set: HashSet<NodeId> = to_nodes.iter().copied().collect();
+        Ok(from_nodes
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.edges(*id)
+                    .iter()
+                    .any(|(e, _)| to_set.contains(&e.node.id))
+            })
+            .collect())
+    }
+
+    fn is_closed(&self, way: WayId, at: NaiveDateTime) -> Result<bool, anyhow::Error> {
+        Ok(self
+            .restrictions
+            .get(&way.abs())
+            .is_some_and(|restrictions| Restriction::is_closed(restrictions, at)))
+    }
+
+    fn is_turn_allowed(
+        &self,
+        from_way: WayId,
+        via: NodeId,
+        to_way: WayId,
+    ) -> Result<bool, anyhow::Error> {
+        Ok(self
+            .turn_restrictions
+            .get(&via)
+            .is_none_or(|restrictions| TurnRestriction::is_allowed(restrictions, from_way, to_way)))
+    }
+
+    fn get_control(&self, node: NodeId) -> Result<Control, anyhow::Error> {
+        Ok(self.controls.get(&node).copied().unwrap_or_default())
+    }
+}
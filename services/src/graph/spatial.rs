@@ -0,0 +1,200 @@
+/// An in-process spatial index over Way bounding boxes, standing in for SQLite's Ways R*Tree so
+/// that snapping can be answered from memory. Ways are bucketed into a uniform grid of cells
+/// over the graph's extent; a query visits the cells its box overlaps, then checks each Way's
+/// bounding box just as the R*Tree query would.
+use crate::db::DBConnection;
+use crate::osm::{Node, WayId};
+use geo::{Point, Rect};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+/// degrees along each side of a grid cell: a few blocks across, at NYC's latitude
+const CELL_SIZE: f64 = 0.005;
+
+/// A uniform grid of cells over the graph's extent, numbered row by row from the south-west
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Grid {
+    /// the union of every Way's bounding box
+    pub(super) bounds: Rect,
+    pub(super) cols: u32,
+    pub(super) rows: u32,
+}
+
+impl Grid {
+    fn covering(bounds: Rect) -> Self {
+        let cells = |extent: f64| ((extent / CELL_SIZE).ceil() as u32).max(1);
+        Self {
+            bounds,
+            cols: cells(bounds.width()),
+            rows: cells(bounds.height()),
+        }
+    }
+
+    fn cell_count(&self) -> usize {
+        (self.cols * self.rows) as usize
+    }
+
+    /// The columns or rows spanning `low..=high`, clamped to the grid
+    fn span(low: f64, high: f64, min: f64, count: u32) -> RangeInclusive<u32> {
+        let at = |coord: f64| (((coord - min) / CELL_SIZE).floor().max(0.0) as u32).min(count - 1);
+        at(low)..=at(high)
+    }
+
+    /// The indices of the cells overlapping the box. Boxes off the edge of the grid are clamped
+    /// onto the cells along it, which their Ways' bounding boxes are then checked against.
+    pub(super) fn cells(&self, area: Rect) -> impl Iterator<Item = usize> {
+        let (min, max) = (area.min(), area.max());
+        let cols = Self::span(min.x, max.x, self.bounds.min().x, self.cols);
+        let rows = Self::span(min.y, max.y, self.bounds.min().y, self.rows);
+        let width = self.cols as usize;
+        rows.flat_map(move |row| {
+            cols.clone()
+                .map(move |col| row as usize * width + col as usize)
+        })
+    }
+}
+
+/// The box `radius` degrees out from the point in each direction
+pub(super) fn search_area(center: Point, radius: f64) -> Rect {
+    Rect::new(
+        (center.x() - radius, center.y() - radius),
+        (center.x() + radius, center.y() + radius),
+    )
+}
+
+/// Whether two boxes overlap, edges included, as in the R*Tree query
+pub(super) fn overlaps(a: &Rect, b: &Rect) -> bool {
+    a.min().x <= b.max().x
+        && a.max().x >= b.min().x
+        && a.min().y <= b.max().y
+        && a.max().y >= b.min().y
+}
+
+/// Every Way's bounding box and Nodes, bucketed into a grid
+pub(super) struct SpatialIndex {
+    pub(super) grid: Grid,
+    /// every Way's id and bounding box, sorted by id
+    pub(super) ways: Vec<(WayId, Rect)>,
+    /// where each Way's Nodes start in `nodes`, and where the last ends
+    pub(super) node_offsets: Vec<u32>,
+    /// the Nodes along each Way, in order
+    pub(super) nodes: Vec<Node>,
+    /// where each cell's Ways start in `cell_ways`, and where the last ends
+    pub(super) cell_offsets: Vec<u32>,
+    /// indices into `ways` of the Ways whose bounding box overlaps each cell
+    pub(super) cell_ways: Vec<u32>,
+}
+
+impl SpatialIndex {
+    /// Reads every Way's bounding box out of the R*Tree, with its Nodes, and buckets them
+    pub(super) fn load(conn: &DBConnection) -> Result<Self, anyhow::Error> {
+        let mut stmt =
+            conn.prepare("SELECT id, minLon, minLat, maxLon, maxLat FROM Ways ORDER BY id")?;
+        let ways: Vec<(WayId, Rect)> = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    Rect::new(
+                        (row.get::<_, f64>(1)?, row.get::<_, f64>(2)?),
+                        (row.get::<_, f64>(3)?, row.get::<_, f64>(4)?),
+                    ),
+                ))
+            })?
+            .collect::<Result<_, _>>()?;
+        let index: HashMap<WayId, usize> = ways
+            .iter()
+            .enumerate()
+            .map(|(i, (way, _))| (*way, i))
+            .collect();
+
+        let mut way_nodes: Vec<Vec<Node>> = vec![vec![]; ways.len()];
+        let mut stmt = conn.prepare(
+            "
+            SELECT WayNodes.way, WayNodes.node, lon, lat
+            FROM WayNodes
+            JOIN Nodes ON WayNodes.node = Nodes.id
+            ORDER BY WayNodes.way, WayNodes.pos
+            ",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(&i) = index.get(&row.get::<_, WayId>(0)?) {
+                way_nodes[i].push(Node::new(
+                    row.get(1)?,
+                    &Point::new(row.get(2)?, row.get(3)?),
+                ));
+            }
+        }
+
+        let mut node_offsets = Vec::with_capacity(ways.len() + 1);
+        let mut nodes = vec![];
+        for way in way_nodes {
+            node_offsets.push(nodes.len() as u32);
+            nodes.extend(way);
+        }
+        node_offsets.push(nodes.len() as u32);
+
+        let bounds = ways
+            .iter()
+            .map(|(_, bbox)| *bbox)
+            .reduce(|a, b| {
+                Rect::new(
+                    (a.min().x.min(b.min().x), a.min().y.min(b.min().y)),
+                    (a.max().x.max(b.max().x), a.max().y.max(b.max().y)),
+                )
+            })
+            .unwrap_or_else(|| Rect::new((0., 0.), (0., 0.)));
+        let grid = Grid::covering(bounds);
+
+        let mut cells: Vec<Vec<u32>> = vec![vec![]; grid.cell_count()];
+        for (i, (_, bbox)) in ways.iter().enumerate() {
+            for cell in grid.cells(*bbox) {
+                cells[cell].push(i as u32);
+            }
+        }
+        let mut cell_offsets = Vec::with_capacity(cells.len() + 1);
+        let mut cell_ways = vec![];
+        for cell in cells {
+            cell_offsets.push(cell_ways.len() as u32);
+            cell_ways.extend(cell);
+        }
+        cell_offsets.push(cell_ways.len() as u32);
+
+        Ok(Self {
+            grid,
+            ways,
+            node_offsets,
+            nodes,
+            cell_offsets,
+            cell_ways,
+        })
+    }
+
+    /// The Nodes of every Way whose bounding box comes within `radius` degrees of the point, each
+    /// paired with the Way it was found on
+    pub(super) fn way_nodes_near(&self, center: Point, radius: f64) -> Vec<(Node, WayId)> {
+        let area = search_area(center, radius);
+        let mut found: Vec<u32> = self
+            .grid
+            .cells(area)
+            .flat_map(|cell| {
+                let range = self.cell_offsets[cell] as usize..self.cell_offsets[cell + 1] as usize;
+                self.cell_ways[range].iter().copied()
+            })
+            .filter(|i| overlaps(&self.ways[*i as usize].1, &area))
+            .collect();
+        // Ways spanning several cells are found in each
+        found.sort_unstable();
+        found.dedup();
+
+        found
+            .into_iter()
+            .flat_map(|i| {
+                let i = i as usize;
+                let way = self.ways[i].0;
+                let range = self.node_offsets[i] as usize..self.node_offsets[i + 1] as usize;
+                self.nodes[range].iter().map(move |node| (*node, way))
+            })
+            .collect()
+    }
+}
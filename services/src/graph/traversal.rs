@@ -1,25 +1,100 @@
-use super::{serialize_as_int, serialize_float_rounded, Cost, CostModel, Graph, Weight};
+use super::core::trace_back;
+use super::{
+    serialize_as_int, serialize_float_rounded, BudgetLimit, BudgetTracker, Cost, CostModel, Graph,
+    GraphRepository, Landmarks, RoutingError, SegmentKey, Turn, Weight,
+};
 use crate::osm::{
-    serialize_node_simple, Cycleway, Distance, Neighbor, Node, NodeId, Road, WayId, WayLabels,
+    serialize_node_simple, BikeNetwork, Control, Cycleway, Distance, EdgeShape, Lts, Neighbor,
+    Node, NodeId, Road, Surface, WayId, WayLabels,
 };
-use anyhow::anyhow;
-use geo::{HaversineDistance, Line, Point};
-use serde::Serialize;
+use chrono::{NaiveDateTime, TimeDelta};
+use geo::{HaversineBearing, HaversineDistance, Line, Point};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap};
+use std::sync::Arc;
 
 pub const START_NODE_ID: NodeId = -1;
 pub const END_NODE_ID: NodeId = -2;
 
+/// Estimated average cycling speed in m/s (~15 km/h)
+pub const AVG_CYCLING_SPEED_MPS: f64 = 4.2;
+
 pub type Depth = usize;
 pub type Route = Vec<TraversalSegment>;
 pub type Traversal = Vec<TraversalSegment>;
+/// a directed segment, as its (from, to) Nodes
+pub type EdgeKey = (NodeId, NodeId);
+/// cost multipliers keyed by a segment's (from, to) Nodes
+pub type Penalties = HashMap<EdgeKey, Cost>;
+
+/// How `calculate_route` searches for a path between two points
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum SearchMode {
+    /// A* outward from the start until the end is reached
+    #[default]
+    Unidirectional,
+    /// A* outward from both the start and the end, stopping once the two searches meet
+    Bidirectional,
+}
+
+/// A requested stop along a route, projected onto the nearest street. The projection splits the
+/// segment it lands on in two, giving partial edges out to, and in from, the Nodes at either end.
+#[derive(Clone, Debug)]
+pub struct Waypoint {
+    /// the projected location, on the street
+    pub point: Point,
+    /// the segment the point was projected onto
+    pub segment: SegmentKey,
+    /// the whole of `segment`, as the edge from its first Node to its second
+    pub edge: Neighbor,
+    /// how far along `segment` the point lies, from 0.0 (its first Node) to 1.0 (its second)
+    pub fraction: f64,
+    /// partial edges leaving the point for the Nodes at either end of its segment
+    pub departures: Vec<(Neighbor, WayLabels)>,
+    /// partial edges arriving at the point from the Nodes at either end of its segment, with
+    /// each Neighbor being the source of the edge
+    pub arrivals: Vec<(Neighbor, WayLabels)>,
+}
+
+/// A rider-meaningful measure of how far a traversal has progressed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraversalMetric {
+    /// meters travelled
+    Distance,
+    /// accumulated cost, under the traversal's cost model
+    Cost,
+    /// estimated seconds spent riding, at `AVG_CYCLING_SPEED_MPS`
+    Time,
+}
+
+impl TraversalMetric {
+    /// this metric's value upon arriving at the end of the segment
+    pub fn measure(&self, segment: &TraversalSegment) -> f64 {
+        match self {
+            TraversalMetric::Distance => segment.distance_so_far as f64,
+            TraversalMetric::Cost => segment.cost as f64,
+            TraversalMetric::Time => segment.distance_so_far as f64 / AVG_CYCLING_SPEED_MPS,
+        }
+    }
+
+    /// this metric's value upon leaving the start of the segment
+    pub fn measure_before(&self, segment: &TraversalSegment) -> f64 {
+        let distance_before = (segment.distance_so_far - segment.length) as f64;
+        match self {
+            TraversalMetric::Distance => distance_before,
+            TraversalMetric::Cost => segment.cost_so_far as f64,
+            TraversalMetric::Time => distance_before / AVG_CYCLING_SPEED_MPS,
+        }
+    }
+}
 
 pub trait Traversable {
     fn initialize_traversal(
         &self,
-        start: &Point,
-        cost_model: Option<CostModel>,
+        start: &Waypoint,
+        cost_model: CostModel,
         heuristic_weight: Option<Weight>,
     ) -> Result<TraversalContext, anyhow::Error>;
     fn traverse_from(
@@ -27,12 +102,31 @@ pub trait Traversable {
         context: &mut TraversalContext,
         max_depth: usize,
     ) -> Result<(), anyhow::Error>;
+    fn traverse_within(
+        &self,
+        context: &mut TraversalContext,
+        metric: TraversalMetric,
+        limit: f64,
+    ) -> Result<(), anyhow::Error>;
     fn traverse_between(
         &self,
         context: &mut TraversalContext,
-        target_neighbor_node_ids: &[NodeId],
+        arrivals: &[(Neighbor, WayLabels)],
         end_node: &Node,
     ) -> Result<(), anyhow::Error>;
+    fn initialize_reverse_traversal(
+        &self,
+        end: &Waypoint,
+        cost_model: CostModel,
+        heuristic_weight: Option<Weight>,
+    ) -> Result<TraversalContext, anyhow::Error>;
+    fn traverse_bidirectional(
+        &self,
+        forward: &mut TraversalContext,
+        backward: &mut TraversalContext,
+        start_node: &Node,
+        end_node: &Node,
+    ) -> Result<NodeId, anyhow::Error>;
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -51,6 +145,8 @@ pub struct TraversalSegment {
     pub length: Distance,
     pub distance_so_far: Distance,
     pub labels: WayLabels,
+    /// index of the leg (the stretch between two consecutive waypoints) this segment belongs to
+    pub leg: usize,
 
     #[serde(serialize_with = "serialize_float_rounded")]
     pub cost: Cost,
@@ -64,6 +160,14 @@ pub struct TraversalSegment {
 
     pub elevation_gain: i16,
     pub elevation_loss: i16,
+
+    /// estimated (local) time of reaching the `to` Node, for searches given a departure time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arrival: Option<NaiveDateTime>,
+
+    /// where the segment bends, when it was compacted from a chain of Segments
+    #[serde(skip)]
+    pub shape: Option<EdgeShape>,
 }
 
 /// TraversalSegments are equivalent when they connect the same points along the same way
@@ -92,6 +196,18 @@ impl Ord for TraversalSegment {
 }
 
 impl TraversalSegment {
+    /// compass bearing of the segment as it arrives at `to`, in degrees clockwise from north
+    pub fn bearing(&self) -> f64 {
+        self.before_to()
+            .geometry
+            .haversine_bearing(self.to.geometry)
+    }
+
+    /// the Node passed just before arriving at `to`: `from`, unless the segment bends
+    pub fn before_to(&self) -> Node {
+        self.shape.map_or(self.from, |shape| shape.last)
+    }
+
     pub fn build_to_neighbor(from: &Node, to: &Neighbor) -> TraversalSegmentBuilder {
         TraversalSegmentBuilder::new_from_neighbor(from, to)
     }
@@ -99,6 +215,11 @@ impl TraversalSegment {
     pub fn build_to_node(from: &Node, to: &Node, way: WayId) -> TraversalSegmentBuilder {
         TraversalSegmentBuilder::new_from_node(from, to, way)
     }
+
+    /// for backward searches, where the Neighbor is the _source_ of the edge
+    pub fn build_from_neighbor(from: &Neighbor, to: &Node) -> TraversalSegmentBuilder {
+        TraversalSegmentBuilder::new_to_node(from, to)
+    }
 }
 
 pub struct TraversalSegmentBuilder {
@@ -114,10 +235,13 @@ pub struct TraversalSegmentBuilder {
     labels: WayLabels,
     cost_factor: Cost,
     elevation_cost: Cost,
+    penalty: Cost,
     cost_so_far: Cost,
     heuristic: Weight,
     elevation_gain: i16,
     elevation_loss: i16,
+    arrival: Option<NaiveDateTime>,
+    shape: Option<EdgeShape>,
 }
 
 impl TraversalSegmentBuilder {
@@ -132,13 +256,23 @@ impl TraversalSegmentBuilder {
             depth: 0,
             length: to.distance,
             distance_so_far: to.distance,
-            labels: (Cycleway::Shared, Road::Collector, false),
+            labels: (
+                Cycleway::Shared,
+                Road::Collector,
+                false,
+                BikeNetwork::None,
+                Surface::Paved,
+                Lts::Lts1,
+            ),
             cost_factor: 0.0,
             elevation_cost: 0.0,
+            penalty: 1.0,
             cost_so_far: 0.0,
             heuristic: 0.0,
             elevation_gain: 0,
             elevation_loss: 0,
+            arrival: None,
+            shape: to.shape,
         }
     }
 
@@ -154,13 +288,54 @@ impl TraversalSegmentBuilder {
             depth: 0,
             length,
             distance_so_far: length,
-            labels: (Cycleway::Shared, Road::Collector, false),
+            labels: (
+                Cycleway::Shared,
+                Road::Collector,
+                false,
+                BikeNetwork::None,
+                Surface::Paved,
+                Lts::Lts1,
+            ),
+            cost_factor: 0.0,
+            elevation_cost: 0.0,
+            penalty: 1.0,
+            cost_so_far: 0.0,
+            heuristic: 0.0,
+            elevation_gain: 0,
+            elevation_loss: 0,
+            arrival: None,
+            shape: None,
+        }
+    }
+
+    pub fn new_to_node(from: &Neighbor, to: &Node) -> Self {
+        Self {
+            from: from.node,
+            to: *to,
+            way: from.way,
+
+            geometry: Line::new(from.node.geometry, to.geometry),
+
+            depth: 0,
+            length: from.distance,
+            distance_so_far: from.distance,
+            labels: (
+                Cycleway::Shared,
+                Road::Collector,
+                false,
+                BikeNetwork::None,
+                Surface::Paved,
+                Lts::Lts1,
+            ),
             cost_factor: 0.0,
             elevation_cost: 0.0,
+            penalty: 1.0,
             cost_so_far: 0.0,
             heuristic: 0.0,
             elevation_gain: 0,
             elevation_loss: 0,
+            arrival: None,
+            shape: from.shape,
         }
     }
 
@@ -184,7 +359,7 @@ impl TraversalSegmentBuilder {
         elevation_loss: i16,
         cost_so_far: Cost,
     ) -> Self {
-        self.cost_factor = cost_model.calculate_cost(way_labels);
+        self.cost_factor = cost_model.calculate_way_cost(self.way, way_labels);
         self.elevation_cost =
             cost_model.calculate_elevation_multiplier(elevation_gain, elevation_loss, self.length);
         self.cost_so_far = cost_so_far;
@@ -194,10 +369,39 @@ impl TraversalSegmentBuilder {
         self
     }
 
-    /// add the distance to end node heuristic
-    fn with_heuristic(mut self, end_node: &Node, heuristic_weight: &Weight) -> Self {
-        self.heuristic =
-            heuristic_weight * self.to.geometry.haversine_distance(&end_node.geometry) as f32;
+    /// scales this segment's cost by the multiplier recorded for it, if any. A compacted segment
+    /// takes the multiplier of the first Segment in its chain, which a penalized route can only
+    /// have followed through to its end.
+    fn with_penalty(mut self, penalties: &Penalties) -> Self {
+        let next = self.shape.map_or(self.to, |shape| shape.first);
+        if let Some(penalty) = penalties.get(&(self.from.id, next.id)) {
+            self.penalty *= *penalty;
+        }
+        self
+    }
+
+    /// scales this segment's cost by the multiplier for riding it while closed (1.0 if it's open)
+    fn with_closed_penalty(mut self, closed_penalty: Cost) -> Self {
+        self.penalty *= closed_penalty;
+        self
+    }
+
+    /// estimates the time of arrival at the end of this segment, for searches setting off at
+    /// `departure`; must follow `with_prev_distance`
+    fn with_departure(mut self, departure: Option<NaiveDateTime>) -> Self {
+        self.arrival = departure.map(|departure| arrival_after(departure, self.distance_so_far));
+        self
+    }
+
+    /// add the estimated cost on to the end node
+    fn with_heuristic(mut self, end_node: &Node, context: &TraversalContext) -> Self {
+        self.heuristic = context.estimate_to(&self.to, end_node);
+        self
+    }
+
+    /// add the estimated cost back to the start node, for backward searches
+    fn with_reverse_heuristic(mut self, start_node: &Node, context: &TraversalContext) -> Self {
+        self.heuristic = context.estimate_from(start_node, &self.from);
         self
     }
 
@@ -205,8 +409,10 @@ impl TraversalSegmentBuilder {
         // Segment cost = base road/cycleway cost × elevation multiplier + accumulated cost.
         // elevation_cost here is a dimensionless multiplier offset (0.0 when flat/disabled),
         // so hills amplify the existing road preference rather than competing with it.
+        // penalty is a search-only multiplier (1.0 unless steering away from a known path)
         let cost =
-            self.cost_factor * self.length as f32 * (1.0 + self.elevation_cost) + self.cost_so_far;
+            self.cost_factor * self.length as f32 * (1.0 + self.elevation_cost) * self.penalty
+                + self.cost_so_far;
 
         TraversalSegment {
             from: self.from,
@@ -219,12 +425,15 @@ impl TraversalSegmentBuilder {
             depth: self.depth,
             distance_so_far: self.distance_so_far,
             labels: self.labels,
+            leg: 0,
             cost_factor: self.cost_factor,
             cost_so_far: self.cost_so_far,
             heuristic: self.heuristic,
             cost,
             elevation_gain: self.elevation_gain,
             elevation_loss: self.elevation_loss,
+            arrival: self.arrival,
+            shape: self.shape,
         }
     }
 }
@@ -237,6 +446,8 @@ pub struct HeapEntry {
     pub priority: Cost,
     /// which node this entry routes to
     pub to_node_id: NodeId,
+    /// which node this entry routes from, identifying the edge in edge-based searches
+    pub from_node_id: NodeId,
     /// g-value: total cost to reach to_node_id (used for lazy-deletion stale check)
     pub cost_at_node: Cost,
 }
@@ -256,132 +467,442 @@ impl Ord for HeapEntry {
     }
 }
 
+/// How a search estimates the cost remaining to its target
+#[derive(Clone)]
+pub enum Heuristic {
+    /// straight-line distance, scaled by the context's heuristic weight
+    Haversine,
+    /// lower bounds from precomputed landmarks. The `anchors` are the Nodes the search must pass
+    /// through to reach its target: those arriving at the end point when searching forward, or
+    /// departing the start point when searching backward.
+    Landmarks {
+        landmarks: Arc<Landmarks>,
+        anchors: Vec<NodeId>,
+    },
+}
+
+/// Which heuristic guided a route's search, ordered from least to most informed
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeuristicKind {
+    /// no estimate at all, as in Dijkstra and contraction hierarchy searches
+    None,
+    Haversine,
+    Landmarks,
+}
+
 /// Context object representing the state of a single routing or traversal operation
 pub struct TraversalContext {
     pub queue: BinaryHeap<HeapEntry>,
     pub came_from: HashMap<NodeId, TraversalSegment>,
     pub cost_model: CostModel,
     pub heuristic_weight: Weight,
+    pub heuristic: Heuristic,
+    /// used to push a search off a known path
+    pub penalties: Penalties,
+
+    /// For edge-based searches (when the cost model has turn costs), the cheapest segment found
+    /// along each edge. A Node can be worth arriving at more than once, from whichever direction
+    /// makes for the cheaper turn out of it, so `came_from` is only filled in once the search ends.
+    pub came_from_edge: HashMap<EdgeKey, TraversalSegment>,
+    /// the edge preceding each edge in `came_from_edge`
+    pub edge_parents: HashMap<EdgeKey, EdgeKey>,
+
+    /// how much more work the search may do before giving up
+    pub budget: BudgetTracker,
+
+    /// whether the search runs over compacted edges, skipping along chains of Segments rather than
+    /// stopping at every Node on them. Routes found that way are expanded with `Graph::expand_route`.
+    pub compacted: bool,
+    /// the Nodes a compacted search must still be able to stop at, such as those the end point
+    /// can be reached from
+    pub stops: Vec<NodeId>,
+
+    /// the (local) time the rider sets off, when Ways closed at the time they'd be reached are
+    /// to be avoided. Only forward searches track arrival times.
+    pub departure: Option<NaiveDateTime>,
 
     pub max_depth: Depth,
     pub cost_range: (Cost, Cost),
 }
 
 impl TraversalContext {
-    pub fn new(cost_model: Option<CostModel>, heuristic_weight: Option<Weight>) -> Self {
+    pub fn new(cost_model: CostModel, heuristic_weight: Option<Weight>) -> Self {
         Self {
             queue: BinaryHeap::new(),
             came_from: HashMap::with_capacity(4096),
-            cost_model: cost_model.unwrap_or_default(),
+            cost_model,
             heuristic_weight: heuristic_weight.unwrap_or(0.75),
+            heuristic: Heuristic::Haversine,
+            penalties: HashMap::new(),
+
+            came_from_edge: HashMap::new(),
+            edge_parents: HashMap::new(),
+
+            budget: BudgetTracker::start(Default::default()),
+
+            compacted: false,
+            stops: vec![],
+
+            departure: None,
 
             max_depth: 0,
             cost_range: (f32::MAX, f32::MIN),
         }
     }
+
+    /// whether searches over this context must track the edge each Node was arrived by
+    pub fn is_edge_based(&self) -> bool {
+        self.cost_model.has_turn_costs()
+    }
+
+    /// Sets the search off at `departure`, stamping the segments it was seeded with. The partial
+    /// edges out of the start point are never closed to the rider, who is already on them.
+    pub fn depart_at(&mut self, departure: Option<NaiveDateTime>) {
+        self.departure = departure;
+        for segment in self
+            .came_from
+            .values_mut()
+            .chain(self.came_from_edge.values_mut())
+        {
+            segment.arrival =
+                departure.map(|departure| arrival_after(departure, segment.distance_so_far));
+        }
+    }
+
+    /// which heuristic this context's searches are guided by
+    pub fn heuristic_kind(&self) -> HeuristicKind {
+        match self.heuristic {
+            Heuristic::Landmarks { .. } => HeuristicKind::Landmarks,
+            Heuristic::Haversine if self.heuristic_weight > 0.0 => HeuristicKind::Haversine,
+            Heuristic::Haversine => HeuristicKind::None,
+        }
+    }
+
+    /// estimated cost from `node` on to the `end_node`
+    pub fn estimate_to(&self, node: &Node, end_node: &Node) -> Cost {
+        match &self.heuristic {
+            Heuristic::Haversine => {
+                self.heuristic_weight * node.geometry.haversine_distance(&end_node.geometry) as f32
+            }
+            Heuristic::Landmarks { landmarks, anchors } => anchors
+                .iter()
+                .map(|anchor| landmarks.lower_bound(node.id, *anchor))
+                .fold(Cost::INFINITY, Cost::min),
+        }
+    }
+
+    /// estimated cost from the `start_node` to `node`, for backward searches
+    pub fn estimate_from(&self, start_node: &Node, node: &Node) -> Cost {
+        match &self.heuristic {
+            Heuristic::Haversine => {
+                self.heuristic_weight
+                    * node.geometry.haversine_distance(&start_node.geometry) as f32
+            }
+            Heuristic::Landmarks { landmarks, anchors } => anchors
+                .iter()
+                .map(|anchor| landmarks.lower_bound(*anchor, node.id))
+                .fold(Cost::INFINITY, Cost::min),
+        }
+    }
 }
 
 impl Traversable for Graph {
-    /// initializes the context and structures required to perform a traversal
-    /// TODO: be able to create a "virtual" node location _midway_ along a Way, rather than starting
-    ///       from the clicked `start location
+    /// initializes the context and structures required to perform a traversal, seeded with the
+    /// partial edges leaving the start point for either end of its segment. The cost model is
+    /// expected to have been prepared with `prepare_cost_model`.
     fn initialize_traversal(
         &self,
-        start: &Point,
-        cost_model: Option<CostModel>,
+        start: &Waypoint,
+        cost_model: CostModel,
         heuristic_weight: Option<Weight>,
     ) -> Result<TraversalContext, anyhow::Error> {
-        let start_node = Node::new(START_NODE_ID, start);
-        let starting_neighbors = self.db.get_snapped_neighbors(*start, None)?;
+        let start_node = Node::new(START_NODE_ID, &start.point);
 
         let mut context = TraversalContext::new(cost_model, heuristic_weight);
+        context.budget = BudgetTracker::start(self.budget.clone());
 
-        for neighbor in starting_neighbors {
-            let segment = TraversalSegment::build_to_neighbor(&start_node, &neighbor).build();
+        for (neighbor, way_labels) in &start.departures {
+            let segment = TraversalSegment::build_to_neighbor(&start_node, neighbor)
+                .with_cost(
+                    &context.cost_model,
+                    way_labels,
+                    neighbor.elevation_gain,
+                    neighbor.elevation_loss,
+                    0.0,
+                )
+                .build();
             context.queue.push(HeapEntry {
                 priority: segment.cost + segment.heuristic,
                 to_node_id: neighbor.node.id,
+                from_node_id: START_NODE_ID,
                 cost_at_node: segment.cost,
             });
+            if context.is_edge_based() {
+                context
+                    .came_from_edge
+                    .insert((START_NODE_ID, neighbor.node.id), segment.clone());
+            }
             context.came_from.insert(neighbor.node.id, segment);
         }
 
         Ok(context)
     }
 
+    /// Explores outward from the start along the cheapest paths, until every Node within `limit`
+    /// (as measured by `metric` along those paths) has been settled. Segments that cross the limit
+    /// are recorded but never expanded from.
+    fn traverse_within(
+        &self,
+        context: &mut TraversalContext,
+        metric: TraversalMetric,
+        limit: f64,
+    ) -> Result<(), anyhow::Error> {
+        // there's no destination to steer toward, so this is a plain Dijkstra search
+        context.heuristic_weight = 0.0;
+        context.heuristic = Heuristic::Haversine;
+
+        if context.is_edge_based() {
+            while let Some(entry) = context.queue.pop() {
+                if is_stale_edge(context, &entry) {
+                    continue;
+                }
+                spend_budget(context, entry.cost_at_node, None)?;
+
+                let key = (entry.from_node_id, entry.to_node_id);
+                let current = context.came_from_edge.get(&key).unwrap();
+                if metric.measure(current) > limit {
+                    continue;
+                }
+                let here = current.to;
+
+                expand_edge(&*self.db, context, key, &here, &[])?;
+            }
+
+            settle_nodes(context);
+            return Ok(());
+        }
+
+        while let Some(entry) = context.queue.pop() {
+            if is_stale(context, &entry) {
+                continue;
+            }
+            spend_budget(context, entry.cost_at_node, None)?;
+
+            let current = context.came_from.get(&entry.to_node_id).unwrap();
+            if metric.measure(current) > limit {
+                continue;
+            }
+            // with a zero heuristic weight, the "end" Node handed to the expansion goes unused
+            let here = current.to;
+
+            expand_forward(&*self.db, context, entry.to_node_id, &here, |_, _| Ok(()))?;
+        }
+
+        Ok(())
+    }
+
     /// Generates a collection of all TraversalSegments examined while routing between the start and
     /// end Points. TraversalSegments will be decorated with both the depth of the traversal and
     /// the cost assigned, given the designated cost model
     fn traverse_between(
         &self,
         context: &mut TraversalContext,
-        target_neighbor_node_ids: &[NodeId],
+        arrivals: &[(Neighbor, WayLabels)],
         end_node: &Node,
     ) -> Result<(), anyhow::Error> {
+        // only the Nodes along the end point's segment need stopping at
+        context.compacted = true;
+        context.stops = arrivals.iter().map(|(n, _)| n.node.id).collect();
+
+        if context.is_edge_based() {
+            return traverse_edges_between(&*self.db, context, arrivals, end_node);
+        }
+
         while let Some(entry) = context.queue.pop() {
-            // Lazy deletion: skip stale heap entries where we already found a cheaper path
-            let current_cost = context
-                .came_from
-                .get(&entry.to_node_id)
-                .map(|s| s.cost)
-                .unwrap_or(f32::MAX);
-            if current_cost < entry.cost_at_node {
+            if is_stale(context, &entry) {
                 continue;
             }
 
-            if target_neighbor_node_ids.contains(&entry.to_node_id) {
-                // Reached the target — reconstruct the final segment to the virtual end node
-                let current = context.came_from.get(&entry.to_node_id).unwrap();
-                let segment = TraversalSegment::build_to_node(&current.to, end_node, current.way)
-                    .with_depth(current.depth + 1)
-                    .with_prev_distance(current.distance_so_far)
-                    .build();
-                context.came_from.insert(END_NODE_ID, segment);
+            // the virtual end node is only settled once no cheaper way into it can remain
+            if entry.to_node_id == END_NODE_ID {
                 return Ok(());
             }
+            spend_budget(context, entry.cost_at_node, Some(end_node))?;
 
-            // Extract what we need before the mutable came_from borrows below
-            let (current_to, current_cost, current_depth, current_distance) = {
-                let seg = context.came_from.get(&entry.to_node_id).unwrap();
-                (seg.to, seg.cost, seg.depth, seg.distance_so_far)
-            };
-
-            let edges = self.db.get_neighbors_with_labels(entry.to_node_id)?;
+            expand_forward(
+                &*self.db,
+                context,
+                entry.to_node_id,
+                end_node,
+                |_, _| Ok(()),
+            )?;
 
-            for (neighbor, way_labels) in edges {
-                let segment = TraversalSegment::build_to_neighbor(&current_to, &neighbor)
-                    .with_depth(current_depth + 1)
-                    .with_prev_distance(current_distance)
+            // from a Node at either end of the end point's segment, the partial edge in is an option too
+            for (neighbor, way_labels) in arrivals
+                .iter()
+                .filter(|(n, _)| n.node.id == entry.to_node_id)
+            {
+                let current = context.came_from.get(&entry.to_node_id).unwrap();
+                if !self
+                    .db
+                    .is_turn_allowed(current.way, entry.to_node_id, neighbor.way)?
+                {
+                    continue;
+                }
+                let segment = TraversalSegment::build_from_neighbor(neighbor, end_node)
+                    .with_depth(current.depth + 1)
+                    .with_prev_distance(current.distance_so_far)
                     .with_cost(
                         &context.cost_model,
-                        &way_labels,
+                        way_labels,
                         neighbor.elevation_gain,
                         neighbor.elevation_loss,
-                        current_cost,
+                        current.cost,
                     )
-                    .with_heuristic(end_node, &context.heuristic_weight)
+                    .with_penalty(&context.penalties)
+                    .with_departure(context.departure)
                     .build();
-                context.cost_range.0 = context.cost_range.0.min(segment.cost_factor);
-                context.cost_range.1 = context.cost_range.1.max(segment.cost_factor);
-                context.max_depth = context.max_depth.max(segment.depth);
-
-                let should_push = context
-                    .came_from
-                    .get(&neighbor.node.id)
-                    .is_none_or(|existing| segment.cost < existing.cost);
-
-                if should_push {
-                    context.queue.push(HeapEntry {
-                        priority: segment.cost + segment.heuristic,
-                        to_node_id: neighbor.node.id,
-                        cost_at_node: segment.cost,
-                    });
-                    context.came_from.insert(neighbor.node.id, segment);
+                record_if_cheaper(context, END_NODE_ID, segment, &mut |_, _| Ok(()))?;
+            }
+        }
+
+        Err(RoutingError::NoPathFound.into())
+    }
+
+    /// initializes a context for searching backward from the end point: the snapped Nodes are
+    /// seeded with their partial edge into the virtual end node, mirroring `initialize_traversal`
+    fn initialize_reverse_traversal(
+        &self,
+        end: &Waypoint,
+        cost_model: CostModel,
+        heuristic_weight: Option<Weight>,
+    ) -> Result<TraversalContext, anyhow::Error> {
+        let end_node = Node::new(END_NODE_ID, &end.point);
+
+        let mut context = TraversalContext::new(cost_model, heuristic_weight);
+        context.budget = BudgetTracker::start(self.budget.clone());
+
+        for (neighbor, way_labels) in &end.arrivals {
+            let segment = TraversalSegment::build_from_neighbor(neighbor, &end_node)
+                .with_cost(
+                    &context.cost_model,
+                    way_labels,
+                    neighbor.elevation_gain,
+                    neighbor.elevation_loss,
+                    0.0,
+                )
+                .build();
+            context.queue.push(HeapEntry {
+                priority: segment.cost + segment.heuristic,
+                to_node_id: neighbor.node.id,
+                from_node_id: END_NODE_ID,
+                cost_at_node: segment.cost,
+            });
+            context.came_from.insert(neighbor.node.id, segment);
+        }
+
+        Ok(context)
+    }
+
+    /// Runs the `forward` and `backward` searches against each other until their frontiers meet,
+    /// returning the Node at which the cheapest complete path crosses from one to the other.
+    ///
+    /// In `backward.came_from`, each Node maps to the segment leading _out_ of it toward the end,
+    /// with `cost` holding the cost from that Node to the end. The backward half never sets
+    /// `reverse_salmon`: the incoming edges it relaxes are labelled as the Way is ridden toward
+    /// the end (`-way`, with gain and loss swapped), so salmon and climbs cost the same as forward.
+    fn traverse_bidirectional(
+        &self,
+        forward: &mut TraversalContext,
+        backward: &mut TraversalContext,
+        start_node: &Node,
+        end_node: &Node,
+    ) -> Result<NodeId, anyhow::Error> {
+        // each side is seeded from the Nodes along its own point's segment, which the other side
+        // has to stop at rather than skip past along a compacted chain, or the two can't meet there
+        forward.compacted = true;
+        backward.compacted = true;
+        forward.stops = backward.came_from.keys().copied().collect();
+        backward.stops = forward.came_from.keys().copied().collect();
+
+        // cheapest complete path seen so far, as (total cost, meeting node)
+        let mut best: Option<(Cost, NodeId)> = None;
+
+        // the start and end may snap onto the same Nodes
+        for (node_id, segment) in &forward.came_from {
+            if let Some(other) = backward.came_from.get(node_id) {
+                if self.db.is_turn_allowed(segment.way, *node_id, other.way)? {
+                    consider_meeting(&mut best, segment.cost + other.cost, *node_id);
+                }
+            }
+        }
+
+        // once one side runs dry, everything it can reach has already been checked against the
+        // other side, so no cheaper meeting can appear
+        while let (Some(forward_top), Some(backward_top)) = (
+            forward.queue.peek().map(|e| e.priority),
+            backward.queue.peek().map(|e| e.priority),
+        ) {
+            // every path not yet seen must pass through both frontiers, and costs at least
+            // as much as either frontier's cheapest estimate
+            if let Some((best_cost, _)) = best {
+                if forward_top.max(backward_top) >= best_cost {
+                    break;
+                }
+            }
+
+            // grow whichever frontier is smaller
+            if forward.queue.len() <= backward.queue.len() {
+                let entry = forward.queue.pop().unwrap();
+                if is_stale(forward, &entry) {
+                    continue;
+                }
+                spend_budget(forward, entry.cost_at_node, Some(end_node))?;
+                expand_forward(
+                    &*self.db,
+                    forward,
+                    entry.to_node_id,
+                    end_node,
+                    |node_id, segment| {
+                        if let Some(other) = backward.came_from.get(&node_id) {
+                            if self.db.is_turn_allowed(segment.way, node_id, other.way)? {
+                                consider_meeting(&mut best, segment.cost + other.cost, node_id);
+                            }
+                        }
+                        Ok(())
+                    },
+                )?;
+            } else {
+                let entry = backward.queue.pop().unwrap();
+                if is_stale(backward, &entry) {
+                    continue;
+                }
+                // whichever side runs out, the forward side holds the path toward the end
+                if let Some(limit) = backward.budget.spend(entry.cost_at_node) {
+                    return Err(
+                        budget_exceeded(limit, &backward.budget, forward, Some(end_node)).into(),
+                    );
                 }
+                expand_backward(
+                    &*self.db,
+                    backward,
+                    entry.to_node_id,
+                    start_node,
+                    |node_id, segment| {
+                        if let Some(other) = forward.came_from.get(&node_id) {
+                            if self.db.is_turn_allowed(other.way, node_id, segment.way)? {
+                                consider_meeting(&mut best, segment.cost + other.cost, node_id);
+                            }
+                        }
+                        Ok(())
+                    },
+                )?;
             }
         }
 
-        Err(anyhow!("Traversal failed"))
+        best.map(|(_, node_id)| node_id)
+            .ok_or_else(|| RoutingError::NoPathFound.into())
     }
 
     /// Return a collection of TraversalSegments from traversing the Graph from the start point to
@@ -392,6 +913,8 @@ impl Traversable for Graph {
         max_depth: usize,
     ) -> Result<(), anyhow::Error> {
         while let Some(entry) = context.queue.pop() {
+            spend_budget(context, entry.cost_at_node, None)?;
+
             // Extract what we need before any mutable borrows of came_from
             let (current_depth, current_to, current_cost, current_distance) = {
                 let seg = context.came_from.get(&entry.to_node_id).unwrap();
@@ -406,7 +929,9 @@ impl Traversable for Graph {
             let edges = self.db.get_neighbors_with_labels(entry.to_node_id)?;
 
             for (neighbor, way_labels) in edges {
-                if context.came_from.contains_key(&neighbor.node.id) {
+                if context.came_from.contains_key(&neighbor.node.id)
+                    || !context.cost_model.allows(&way_labels)
+                {
                     continue;
                 }
                 let segment = TraversalSegment::build_to_neighbor(&current_to, &neighbor)
@@ -425,6 +950,7 @@ impl Traversable for Graph {
                 context.queue.push(HeapEntry {
                     priority: segment.cost,
                     to_node_id: neighbor.node.id,
+                    from_node_id: entry.to_node_id,
                     cost_at_node: segment.cost,
                 });
                 context.came_from.insert(neighbor.node.id, segment);
@@ -434,3 +960,442 @@ impl Traversable for Graph {
         Ok(())
     }
 }
+
+/// Lazy deletion: a heap entry is stale when we have since found a cheaper path to its Node
+fn is_stale(context: &TraversalContext, entry: &HeapEntry) -> bool {
+    let current_cost = context
+        .came_from
+        .get(&entry.to_node_id)
+        .map(|s| s.cost)
+        .unwrap_or(f32::MAX);
+    current_cost < entry.cost_at_node
+}
+
+fn is_stale_edge(context: &TraversalContext, entry: &HeapEntry) -> bool {
+    let current_cost = context
+        .came_from_edge
+        .get(&(entry.from_node_id, entry.to_node_id))
+        .map(|s| s.cost)
+        .unwrap_or(f32::MAX);
+    current_cost < entry.cost_at_node
+}
+
+/// Counts an expansion from a Node reached at `cost` against the search's budget, failing once
+/// any of its limits is hit
+fn spend_budget(
+    context: &mut TraversalContext,
+    cost: Cost,
+    target: Option<&Node>,
+) -> Result<(), RoutingError> {
+    match context.budget.spend(cost) {
+        Some(limit) => Err(budget_exceeded(limit, &context.budget, context, target)),
+        None => Ok(()),
+    }
+}
+
+/// Reports how far a search got before hitting `limit`, along with the path from `context` to
+/// wherever it got closest to `target`
+fn budget_exceeded(
+    limit: BudgetLimit,
+    budget: &BudgetTracker,
+    context: &TraversalContext,
+    target: Option<&Node>,
+) -> RoutingError {
+    let partial = target.and_then(|target| {
+        context
+            .came_from
+            .values()
+            .chain(context.came_from_edge.values())
+            .filter(|s| s.to.id != END_NODE_ID)
+            .min_by(|a, b| {
+                let a = a.to.geometry.haversine_distance(&target.geometry);
+                let b = b.to.geometry.haversine_distance(&target.geometry);
+                a.total_cmp(&b)
+            })
+            .map(|closest| trace_back(context, closest.to.id))
+    });
+
+    RoutingError::BudgetExceeded {
+        limit,
+        expanded: budget.expanded,
+        cost_reached: budget.cost_reached,
+        partial,
+    }
+}
+
+/// The edge-based counterpart of `traverse_between`, where each settled state is the segment
+/// arriving at a Node rather than the Node itself
+fn traverse_edges_between(
+    db: &dyn GraphRepository,
+    context: &mut TraversalContext,
+    arrivals: &[(Neighbor, WayLabels)],
+    end_node: &Node,
+) -> Result<(), anyhow::Error> {
+    while let Some(entry) = context.queue.pop() {
+        if is_stale_edge(context, &entry) {
+            continue;
+        }
+
+        if entry.to_node_id == END_NODE_ID {
+            settle_nodes(context);
+            return Ok(());
+        }
+        spend_budget(context, entry.cost_at_node, Some(end_node))?;
+
+        let key = (entry.from_node_id, entry.to_node_id);
+        expand_edge(db, context, key, end_node, arrivals)?;
+    }
+
+    Err(RoutingError::NoPathFound.into())
+}
+
+/// Relaxes every edge leaving the end of the edge `key`, charging for the turn onto each.
+/// Any of the `arrivals` leaving that Node are relaxed into `end_node` as well.
+fn expand_edge(
+    db: &dyn GraphRepository,
+    context: &mut TraversalContext,
+    key: EdgeKey,
+    end_node: &Node,
+    arrivals: &[(Neighbor, WayLabels)],
+) -> Result<(), anyhow::Error> {
+    let current = context.came_from_edge.get(&key).unwrap().clone();
+    let edges = outgoing_edges(db, context, key.1)?;
+    let control = db.get_control(key.1)?;
+
+    for (neighbor, way_labels) in arrivals.iter().filter(|(n, _)| n.node.id == key.1) {
+        if !db.is_turn_allowed(current.way, key.1, neighbor.way)? {
+            continue;
+        }
+        // the partial edge ends at the virtual end node, so that's where the turn is made toward
+        let toward_end = Neighbor {
+            node: *end_node,
+            ..*neighbor
+        };
+        let turn_cost = turn_cost(
+            &context.cost_model,
+            &current,
+            &toward_end,
+            way_labels,
+            &edges,
+            control,
+        );
+        let segment = TraversalSegment::build_from_neighbor(neighbor, end_node)
+            .with_depth(current.depth + 1)
+            .with_prev_distance(current.distance_so_far)
+            .with_cost(
+                &context.cost_model,
+                way_labels,
+                neighbor.elevation_gain,
+                neighbor.elevation_loss,
+                current.cost + turn_cost,
+            )
+            .with_penalty(&context.penalties)
+            .with_departure(context.departure)
+            .build();
+
+        record_edge_if_cheaper(context, key, segment);
+    }
+
+    for (neighbor, way_labels) in &edges {
+        if !context.cost_model.allows(way_labels)
+            || !db.is_turn_allowed(current.way, key.1, neighbor.way)?
+        {
+            continue;
+        }
+        let Some(closed_penalty) = closed_penalty(db, context, neighbor.way, current.arrival)?
+        else {
+            continue;
+        };
+        let turn_cost = turn_cost(
+            &context.cost_model,
+            &current,
+            neighbor,
+            way_labels,
+            &edges,
+            control,
+        );
+        let segment = TraversalSegment::build_to_neighbor(&current.to, neighbor)
+            .with_depth(current.depth + 1)
+            .with_prev_distance(current.distance_so_far)
+            .with_cost(
+                &context.cost_model,
+                way_labels,
+                neighbor.elevation_gain,
+                neighbor.elevation_loss,
+                current.cost + turn_cost,
+            )
+            .with_penalty(&context.penalties)
+            .with_closed_penalty(closed_penalty)
+            .with_departure(context.departure)
+            .with_heuristic(end_node, context)
+            .build();
+
+        record_edge_if_cheaper(context, key, segment);
+    }
+
+    Ok(())
+}
+
+fn record_edge_if_cheaper(
+    context: &mut TraversalContext,
+    parent: EdgeKey,
+    segment: TraversalSegment,
+) {
+    context.cost_range.0 = context.cost_range.0.min(segment.cost_factor);
+    context.cost_range.1 = context.cost_range.1.max(segment.cost_factor);
+    context.max_depth = context.max_depth.max(segment.depth);
+
+    let key = (segment.from.id, segment.to.id);
+    let should_push = context
+        .came_from_edge
+        .get(&key)
+        .is_none_or(|existing| segment.cost < existing.cost);
+
+    if should_push {
+        context.queue.push(HeapEntry {
+            priority: segment.cost + segment.heuristic,
+            to_node_id: segment.to.id,
+            from_node_id: segment.from.id,
+            cost_at_node: segment.cost,
+        });
+        context.edge_parents.insert(key, parent);
+        context.came_from_edge.insert(key, segment);
+    }
+}
+
+/// Fills in `came_from` from an edge-based search, with the cheapest arrival at each Node
+fn settle_nodes(context: &mut TraversalContext) {
+    for segment in context.came_from_edge.values() {
+        context
+            .came_from
+            .entry(segment.to.id)
+            .and_modify(|existing| {
+                if segment.cost < existing.cost {
+                    *existing = segment.clone();
+                }
+            })
+            .or_insert_with(|| segment.clone());
+    }
+}
+
+/// The edges leaving `node_id`, compacted if the search runs over compacted edges
+fn outgoing_edges(
+    db: &dyn GraphRepository,
+    context: &TraversalContext,
+    node_id: NodeId,
+) -> Result<Vec<(Neighbor, WayLabels)>, anyhow::Error> {
+    if context.compacted {
+        db.get_compacted_neighbors_with_labels(node_id, &context.stops)
+    } else {
+        db.get_neighbors_with_labels(node_id)
+    }
+}
+
+/// The cost of the maneuver from the `incoming` segment onto the `outgoing` Neighbor, given
+/// every edge leaving the Node between them and the traffic control device at it, including any
+/// wait there
+pub(super) fn turn_cost(
+    cost_model: &CostModel,
+    incoming: &TraversalSegment,
+    outgoing: &Neighbor,
+    outgoing_labels: &WayLabels,
+    intersection: &[(Neighbor, WayLabels)],
+    control: Control,
+) -> Cost {
+    if !cost_model.has_turn_costs() {
+        return 0.0;
+    }
+
+    // compacted edges bend, so the turn is between the Segments on either side of the Node
+    let next = outgoing.shape.map_or(outgoing.node, |shape| shape.first);
+    let turn = if next.id == incoming.before_to().id {
+        Turn::UTurn
+    } else if outgoing.way.abs() == incoming.way.abs() {
+        // following the same Way around a bend isn't a turn
+        Turn::Straight
+    } else {
+        let outgoing_bearing = incoming.to.geometry.haversine_bearing(next.geometry);
+        Turn::from_bearings(incoming.bearing(), outgoing_bearing)
+    };
+
+    let through_road = (incoming.labels.1 as u8).max(outgoing_labels.1 as u8);
+    let crossed_road = intersection
+        .iter()
+        .filter(|(neighbor, _)| {
+            neighbor.way.abs() != incoming.way.abs() && neighbor.way.abs() != outgoing.way.abs()
+        })
+        .map(|(_, (_, road, ..))| *road as u8)
+        .max();
+    let crosses_major = crossed_road.is_some_and(|road| road > through_road);
+    let crosses_arterial = crosses_major && crossed_road == Some(Road::Arterial as u8);
+
+    cost_model.calculate_turn_cost(turn, crosses_major)
+        + cost_model.calculate_intersection_delay(control, turn, crosses_arterial)
+}
+
+/// The multiplier for riding along `way` when setting off along it `at`: 1.0 unless a restriction
+/// has it closed then, in which case it's the cost model's closed penalty, or None if closed Ways
+/// are to be avoided altogether
+fn closed_penalty(
+    db: &dyn GraphRepository,
+    context: &TraversalContext,
+    way: WayId,
+    at: Option<NaiveDateTime>,
+) -> Result<Option<Cost>, anyhow::Error> {
+    match at {
+        Some(at) if db.is_closed(way, at)? => Ok(context.cost_model.closed_penalty()),
+        _ => Ok(Some(1.0)),
+    }
+}
+
+/// Estimated time of arrival after riding `distance` meters from `departure`
+pub fn arrival_after(departure: NaiveDateTime, distance: Distance) -> NaiveDateTime {
+    departure + riding_time(distance)
+}
+
+/// Estimated time to ride `distance` meters
+pub fn riding_time(distance: Distance) -> TimeDelta {
+    let seconds = distance as f64 / AVG_CYCLING_SPEED_MPS;
+    TimeDelta::milliseconds((seconds * 1000.0) as i64)
+}
+
+fn consider_meeting(best: &mut Option<(Cost, NodeId)>, cost: Cost, node_id: NodeId) {
+    if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+        *best = Some((cost, node_id));
+    }
+}
+
+/// Relaxes every edge leaving `node_id`, recording and queueing any Node reached more cheaply
+/// than before. `on_improved` is called with each such Node and its new segment.
+///
+/// Turns forbidden by a restriction, and Ways more stressful than the cost model allows, are
+/// skipped. Since only the cheapest way into each Node is
+/// kept, a restricted turn can hide a route through the Node that arrives along another Way;
+/// edge-based searches don't have that blind spot.
+fn expand_forward(
+    db: &dyn GraphRepository,
+    context: &mut TraversalContext,
+    node_id: NodeId,
+    end_node: &Node,
+    mut on_improved: impl FnMut(NodeId, &TraversalSegment) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    // Extract what we need before the mutable came_from borrows below
+    let (current_to, current_way, current_cost, current_depth, current_distance, current_arrival) = {
+        let seg = context.came_from.get(&node_id).unwrap();
+        (
+            seg.to,
+            seg.way,
+            seg.cost,
+            seg.depth,
+            seg.distance_so_far,
+            seg.arrival,
+        )
+    };
+
+    let edges = outgoing_edges(db, context, node_id)?;
+
+    for (neighbor, way_labels) in edges {
+        if !context.cost_model.allows(&way_labels)
+            || !db.is_turn_allowed(current_way, node_id, neighbor.way)?
+        {
+            continue;
+        }
+        let Some(closed_penalty) = closed_penalty(db, context, neighbor.way, current_arrival)?
+        else {
+            continue;
+        };
+        let segment = TraversalSegment::build_to_neighbor(&current_to, &neighbor)
+            .with_depth(current_depth + 1)
+            .with_prev_distance(current_distance)
+            .with_cost(
+                &context.cost_model,
+                &way_labels,
+                neighbor.elevation_gain,
+                neighbor.elevation_loss,
+                current_cost,
+            )
+            .with_penalty(&context.penalties)
+            .with_closed_penalty(closed_penalty)
+            .with_departure(context.departure)
+            .with_heuristic(end_node, context)
+            .build();
+
+        record_if_cheaper(context, neighbor.node.id, segment, &mut on_improved)?;
+    }
+
+    Ok(())
+}
+
+/// The backward counterpart of `expand_forward`: relaxes every edge _arriving_ at `node_id`,
+/// so costs accumulate from the end of the route toward the start
+fn expand_backward(
+    db: &dyn GraphRepository,
+    context: &mut TraversalContext,
+    node_id: NodeId,
+    start_node: &Node,
+    mut on_improved: impl FnMut(NodeId, &TraversalSegment) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    let (current_from, current_way, current_cost, current_depth, current_distance) = {
+        let seg = context.came_from.get(&node_id).unwrap();
+        (seg.from, seg.way, seg.cost, seg.depth, seg.distance_so_far)
+    };
+
+    let edges = if context.compacted {
+        db.get_compacted_incoming_neighbors_with_labels(node_id, &context.stops)?
+    } else {
+        db.get_incoming_neighbors_with_labels(node_id)?
+    };
+
+    for (neighbor, way_labels) in edges {
+        if !context.cost_model.allows(&way_labels)
+            || !db.is_turn_allowed(neighbor.way, node_id, current_way)?
+        {
+            continue;
+        }
+        let segment = TraversalSegment::build_from_neighbor(&neighbor, &current_from)
+            .with_depth(current_depth + 1)
+            .with_prev_distance(current_distance)
+            .with_cost(
+                &context.cost_model,
+                &way_labels,
+                neighbor.elevation_gain,
+                neighbor.elevation_loss,
+                current_cost,
+            )
+            .with_penalty(&context.penalties)
+            .with_reverse_heuristic(start_node, context)
+            .build();
+
+        record_if_cheaper(context, neighbor.node.id, segment, &mut on_improved)?;
+    }
+
+    Ok(())
+}
+
+fn record_if_cheaper(
+    context: &mut TraversalContext,
+    node_id: NodeId,
+    segment: TraversalSegment,
+    on_improved: &mut impl FnMut(NodeId, &TraversalSegment) -> Result<(), anyhow::Error>,
+) -> Result<(), anyhow::Error> {
+    context.cost_range.0 = context.cost_range.0.min(segment.cost_factor);
+    context.cost_range.1 = context.cost_range.1.max(segment.cost_factor);
+    context.max_depth = context.max_depth.max(segment.depth);
+
+    let should_push = context
+        .came_from
+        .get(&node_id)
+        .is_none_or(|existing| segment.cost < existing.cost);
+
+    if should_push {
+        context.queue.push(HeapEntry {
+            priority: segment.cost + segment.heuristic,
+            to_node_id: node_id,
+            from_node_id: segment.from.id,
+            cost_at_node: segment.cost,
+        });
+        on_improved(node_id, &segment)?;
+        context.came_from.insert(node_id, segment);
+    }
+    Ok(())
+}
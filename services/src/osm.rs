@@ -4,11 +4,17 @@ use geojson::ser::serialize_geometry;
 use rusqlite::types::FromSql;
 use serde::{Deserialize, Serialize, Serializer};
 
+mod restrictions;
+
+pub use restrictions::*;
+
 pub type NodeId = i64;
 pub type WayId = i64;
 pub type Distance = i32;
+/// index of a compacted edge's chain of Segments, as held by the graph repository
+pub type ShapeId = u32;
 
-pub type WayLabels = (Cycleway, Road, Salmoning);
+pub type WayLabels = (Cycleway, Road, Salmoning, BikeNetwork, Surface, Lts);
 
 /// Whether we are swimming against the stream (of traffic)
 pub type Salmoning = bool;
@@ -43,6 +49,95 @@ pub enum Road {
     Arterial,
 }
 
+/// The most prominent network of signed bike routes (OSM `route=bicycle` relations) a Way is
+/// part of
+#[derive(
+    Debug, Default, Copy, Clone, Hash, Eq, PartialEq, PartialOrd, Ord, Deserialize, Serialize,
+)]
+#[repr(u8)]
+pub enum BikeNetwork {
+    /// Not part of any signed route
+    #[default]
+    None = 0,
+    /// A local route (`lcn`), e.g. a neighborhood greenway
+    Local,
+    /// A regional route (`rcn`), e.g. the Hudson River Greenway
+    Regional,
+    /// A national or international route (`ncn` / `icn`), e.g. the East Coast Greenway
+    National,
+}
+
+/// How rough a Way rides, from its OSM `smoothness`, `surface` and `tracktype` tags
+#[derive(
+    Debug, Default, Copy, Clone, Hash, Eq, PartialEq, PartialOrd, Ord, Deserialize, Serialize,
+)]
+#[repr(u8)]
+pub enum Surface {
+    /// Asphalt or concrete, or anything untagged
+    #[default]
+    Paved = 0,
+    /// Paving stones, bricks, or compacted gravel
+    Rough,
+    /// Setts and cobblestones, e.g. the streets of DUMBO
+    Cobbled,
+    /// Loose gravel, dirt, grass or sand
+    Unpaved,
+}
+
+/// Level of Traffic Stress, after Mekuria, Furth & Nixon (2012): the least-confident kind of rider
+/// who would be comfortable riding this direction of a Way. Goes over the wire as its number.
+#[derive(
+    Debug, Default, Copy, Clone, Hash, Eq, PartialEq, PartialOrd, Ord, Deserialize, Serialize,
+)]
+#[serde(try_from = "u8", into = "u8")]
+#[repr(u8)]
+pub enum Lts {
+    /// Separated from traffic, or on quiet streets: suitable for children
+    #[default]
+    Lts1 = 1,
+    /// Little traffic stress: suitable for most adults
+    Lts2,
+    /// Moderate traffic stress: for riders who are "enthused and confident"
+    Lts3,
+    /// High traffic stress: for the "strong and fearless" only
+    Lts4,
+}
+
+/// The traffic control device at a Node, from its OSM `highway` and `crossing` tags
+#[derive(Debug, Default, Copy, Clone, Hash, Eq, PartialEq, Deserialize, Serialize)]
+#[repr(u8)]
+pub enum Control {
+    /// Nothing to stop for
+    #[default]
+    None = 0,
+    /// Traffic signals, whether at an intersection or a signalized crosswalk
+    Signals,
+    /// A stop sign
+    Stop,
+    /// A crosswalk without signals
+    Crossing,
+}
+
+impl TryFrom<u8> for Lts {
+    type Error = String;
+
+    fn try_from(level: u8) -> Result<Self, Self::Error> {
+        match level {
+            1 => Ok(Lts::Lts1),
+            2 => Ok(Lts::Lts2),
+            3 => Ok(Lts::Lts3),
+            4 => Ok(Lts::Lts4),
+            _ => Err(format!("LTS must be between 1 and 4, got {level}")),
+        }
+    }
+}
+
+impl From<Lts> for u8 {
+    fn from(lts: Lts) -> u8 {
+        lts as u8
+    }
+}
+
 // Allow interpretation of SQLite stored ints as enums again
 impl FromSql for Cycleway {
     fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
@@ -57,6 +152,41 @@ impl FromSql for Road {
     }
 }
 
+// Allow interpretation of SQLite stored ints as enums again
+impl FromSql for BikeNetwork {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        Ok(unsafe { ::std::mem::transmute::<u8, BikeNetwork>(value.as_i64()? as u8) })
+    }
+}
+
+// Allow interpretation of SQLite stored ints as enums again
+impl FromSql for Surface {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        Ok(unsafe { ::std::mem::transmute::<u8, Surface>(value.as_i64()? as u8) })
+    }
+}
+
+// Allow interpretation of SQLite stored ints as enums again
+impl FromSql for Control {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        Ok(unsafe { ::std::mem::transmute::<u8, Control>(value.as_i64()? as u8) })
+    }
+}
+
+// Allow interpretation of SQLite stored ints as enums again
+impl FromSql for RestrictionTag {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        Ok(unsafe { ::std::mem::transmute::<u8, RestrictionTag>(value.as_i64()? as u8) })
+    }
+}
+
+// Allow interpretation of SQLite stored ints as enums again
+impl FromSql for Lts {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        Ok(unsafe { ::std::mem::transmute::<u8, Lts>(value.as_i64()? as u8) })
+    }
+}
+
 // TODO: this needs context about who it's a neighbor TO!
 // at which point...is this just an Edge / Segment?
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -66,7 +196,18 @@ pub struct Neighbor {
     pub distance: Distance,
     pub elevation_gain: i16,
     pub elevation_loss: i16,
-    // TODO: bearing?
+    /// for an edge compacted from a chain of Segments, where it bends; None for a single Segment
+    pub shape: Option<EdgeShape>,
+}
+
+/// The bends of an edge compacted from a chain of Segments along one Way, through Nodes joining no
+/// other. `first` and `last` are the Nodes just after setting off and just before arriving, which
+/// the edge's bearings are taken from; the rest of the chain is held by the graph repository.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EdgeShape {
+    pub id: ShapeId,
+    pub first: Node,
+    pub last: Node,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
@@ -0,0 +1,418 @@
+/// Time-based access restrictions on Ways, from the OSM `opening_hours` and `*:conditional` tags.
+/// Only the subset of the opening_hours syntax that shows up on streets and paths is understood:
+/// month and weekday selectors, time spans (including those running past midnight), `24/7`,
+/// `sunrise` / `sunset` and the `off` / `closed` modifiers. Public holidays can't be known, so
+/// rules that only apply on them are never in effect.
+///
+/// Also the turn restrictions between Ways, from OSM `type=restriction` relations.
+use anyhow::{anyhow, bail};
+use chrono::{Datelike, Days, NaiveDateTime, Timelike};
+
+use super::{NodeId, WayId};
+
+/// minutes after midnight
+type Minute = u16;
+
+const MINUTES_PER_DAY: Minute = 24 * 60;
+/// stand-ins for the sun's actual schedule, which would need a location and an almanac
+const SUNRISE: Minute = 6 * 60;
+const SUNSET: Minute = 20 * 60;
+
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The tag a Restriction was read from, in order of precedence: for a bike, `bicycle` overrides
+/// `access`, which overrides `opening_hours`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum RestrictionTag {
+    OpeningHours = 0,
+    Access,
+    Bicycle,
+}
+
+impl RestrictionTag {
+    /// the tag for the base access value or its `:conditional` counterpart, if it's one we read
+    pub fn parse(tag: &str) -> Option<Self> {
+        match tag.strip_suffix(":conditional").unwrap_or(tag) {
+            "opening_hours" => Some(RestrictionTag::OpeningHours),
+            "access" => Some(RestrictionTag::Access),
+            "bicycle" => Some(RestrictionTag::Bicycle),
+            _ => None,
+        }
+    }
+}
+
+/// A schedule of when a Way may be ridden, or when it may not
+#[derive(Debug, Clone, PartialEq)]
+pub struct Restriction {
+    pub tag: RestrictionTag,
+    /// whether the Way is open (rather than closed) while the schedule is in effect
+    pub open: bool,
+    pub hours: OpeningHours,
+}
+
+impl Restriction {
+    /// Reads the restrictions out of an `opening_hours` value, which opens the Way only while in
+    /// effect, and an `access:conditional` or `bicycle:conditional` value, each of which lists
+    /// access values that apply under conditions, e.g. `no @ (Mo-Fr 22:00-06:00); yes @ Su`
+    pub fn parse(tag: &str, value: &str) -> Result<Vec<Self>, anyhow::Error> {
+        let restriction_tag =
+            RestrictionTag::parse(tag).ok_or_else(|| anyhow!("unexpected tag {tag:?}"))?;
+        if restriction_tag == RestrictionTag::OpeningHours {
+            return Ok(vec![Restriction {
+                tag: restriction_tag,
+                open: true,
+                hours: OpeningHours::parse(value)?,
+            }]);
+        }
+
+        split_outside_parens(value, ';')
+            .into_iter()
+            .map(|conditional| {
+                let (access, condition) = conditional
+                    .split_once('@')
+                    .ok_or_else(|| anyhow!("missing condition in {conditional:?}"))?;
+                let condition = condition.trim();
+                let condition = condition
+                    .strip_prefix('(')
+                    .and_then(|c| c.strip_suffix(')'))
+                    .unwrap_or(condition);
+                Ok(Restriction {
+                    tag: restriction_tag,
+                    open: parse_access(access)?,
+                    hours: OpeningHours::parse(condition)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Reads a plain `access` or `bicycle` value as a restriction in effect at all times, which
+    /// that tag's conditions override while they apply
+    pub fn parse_base(tag: &str, value: &str) -> Result<Self, anyhow::Error> {
+        Ok(Restriction {
+            tag: RestrictionTag::parse(tag)
+                .filter(|tag| *tag != RestrictionTag::OpeningHours)
+                .ok_or_else(|| anyhow!("unexpected tag {tag:?}"))?,
+            open: parse_access(value)?,
+            hours: OpeningHours::parse("24/7")?,
+        })
+    }
+
+    /// Whether a Way under all of these restrictions is closed at the given (local) time. The most
+    /// specific tag with a restriction in effect decides, and within a tag, the last one in effect
+    /// (so a condition overrides the base value before it). Failing either, the Way is closed
+    /// only outside its opening hours, if it has any.
+    pub fn is_closed(restrictions: &[Restriction], at: NaiveDateTime) -> bool {
+        for tag in [RestrictionTag::Bicycle, RestrictionTag::Access] {
+            if let Some(restriction) = restrictions
+                .iter()
+                .rev()
+                .find(|r| r.tag == tag && r.hours.is_open(at))
+            {
+                return !restriction.open;
+            }
+        }
+
+        let mut opening = restrictions
+            .iter()
+            .filter(|r| r.tag == RestrictionTag::OpeningHours)
+            .peekable();
+        opening.peek().is_some() && !opening.any(|r| r.hours.is_open(at))
+    }
+}
+
+/// Whether an access value lets a bike in
+fn parse_access(value: &str) -> Result<bool, anyhow::Error> {
+    match value.trim() {
+        "yes" | "designated" | "permissive" | "destination" => Ok(true),
+        "no" | "private" => Ok(false),
+        other => bail!("unexpected access value {other:?}"),
+    }
+}
+
+/// A turn restriction from an OSM `type=restriction` relation, limiting which Ways a rider
+/// arriving on one Way may turn onto at a Node. Relations with several `from` or `to` Ways are
+/// stored as one of these per pair.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TurnRestriction {
+    pub from: WayId,
+    pub via: NodeId,
+    pub to: WayId,
+    /// `only_*` restrictions allow nothing but the turns onto their `to` Ways, where `no_*`
+    /// restrictions forbid just those
+    pub only: bool,
+}
+
+impl TurnRestriction {
+    /// Reads whether a `restriction` value (e.g. `no_left_turn`, `only_straight_on`) is an `only_*`
+    /// restriction, rejecting values that are neither
+    pub fn parse_kind(value: &str) -> Result<bool, anyhow::Error> {
+        match value.split_once('_') {
+            Some(("only", _)) => Ok(true),
+            Some(("no", _)) => Ok(false),
+            _ => bail!("unexpected restriction value {value:?}"),
+        }
+    }
+
+    /// Whether the restrictions at a Node allow turning from one Way onto another there. Way ids
+    /// are signed by direction of travel, so that a `no_u_turn` (whose `from` and `to` are the same
+    /// Way) only forbids doubling back, not riding straight through.
+    pub fn is_allowed(restrictions: &[TurnRestriction], from_way: WayId, to_way: WayId) -> bool {
+        let turns_onto =
+            |r: &&TurnRestriction| r.to == to_way.abs() && (r.from != r.to || from_way == -to_way);
+        let (only, no): (Vec<_>, Vec<_>) = restrictions
+            .iter()
+            .filter(|r| r.from == from_way.abs())
+            .partition(|r| r.only);
+
+        (only.is_empty() || only.iter().any(turns_onto)) && !no.iter().any(turns_onto)
+    }
+}
+
+/// A parsed `opening_hours` value
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpeningHours {
+    raw: String,
+    rules: Vec<Rule>,
+}
+
+/// One rule of an opening_hours value: the days it covers, and the times within them
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    /// indexed by `month0`; all true when no months were given
+    months: [bool; 12],
+    /// indexed by days from Monday; all true when no weekdays were given
+    weekdays: [bool; 7],
+    /// empty when the rule covers whole days
+    spans: Vec<(Minute, Minute)>,
+    /// whether the rule closes (rather than opens) the times it covers
+    off: bool,
+    /// rules following a comma add to, rather than replace, earlier rules for the same days
+    additional: bool,
+}
+
+impl OpeningHours {
+    pub fn parse(value: &str) -> Result<Self, anyhow::Error> {
+        let mut rules = vec![];
+        for sequence in split_outside_parens(value, ';') {
+            for (i, rule) in split_rules(sequence).into_iter().enumerate() {
+                rules.push(Rule::parse(rule, i > 0)?);
+            }
+        }
+        if rules.is_empty() {
+            bail!("no rules in {value:?}");
+        }
+
+        Ok(Self {
+            raw: value.trim().to_owned(),
+            rules,
+        })
+    }
+
+    /// The value this was parsed from, as stored in the DB
+    pub fn as_str(&self) -> &str {
+        &self.raw
+    }
+
+    /// Whether the schedule is in effect at the given (local) time. Later rules override earlier
+    /// ones for the days they cover, and a span running past midnight carries over into the
+    /// early hours of the next day.
+    pub fn is_open(&self, at: NaiveDateTime) -> bool {
+        let date = at.date();
+        let minute = (at.hour() * 60 + at.minute()) as Minute;
+
+        let mut open = false;
+        for rule in self.rules.iter().filter(|r| r.covers_day(date)) {
+            let within = rule.within(minute);
+            if !rule.additional {
+                open = within && !rule.off;
+            } else if within {
+                open = !rule.off;
+            }
+        }
+        if open {
+            return true;
+        }
+
+        let Some(yesterday) = date.checked_sub_days(Days::new(1)) else {
+            return false;
+        };
+        self.rules.iter().any(|rule| {
+            !rule.off
+                && rule.covers_day(yesterday)
+                && rule
+                    .spans
+                    .iter()
+                    .any(|(start, end)| end <= start && minute < *end)
+        })
+    }
+}
+
+impl Rule {
+    fn parse(rule: &str, additional: bool) -> Result<Self, anyhow::Error> {
+        let mut parsed = Rule {
+            months: [true; 12],
+            weekdays: [true; 7],
+            spans: vec![],
+            off: false,
+            additional,
+        };
+
+        for token in rule.split_whitespace() {
+            match token {
+                "24/7" => {}
+                "off" | "closed" => parsed.off = true,
+                "open" => parsed.off = false,
+                _ if token.starts_with(|c: char| c.is_ascii_digit())
+                    || token.starts_with("sun") =>
+                {
+                    parsed.spans.extend(parse_spans(token)?);
+                }
+                _ if MONTHS.iter().any(|m| token.starts_with(m)) => {
+                    parsed.months = parse_selector(token, &MONTHS)?;
+                }
+                _ => parsed.weekdays = parse_weekdays(token)?,
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn covers_day(&self, date: chrono::NaiveDate) -> bool {
+        self.months[date.month0() as usize]
+            && self.weekdays[date.weekday().num_days_from_monday() as usize]
+    }
+
+    /// whether the minute falls within one of this rule's spans on a day it covers, leaving the
+    /// part of a span past midnight to the following day
+    fn within(&self, minute: Minute) -> bool {
+        self.spans.is_empty()
+            || self.spans.iter().any(|(start, end)| {
+                if end > start {
+                    (*start..*end).contains(&minute)
+                } else {
+                    minute >= *start
+                }
+            })
+    }
+}
+
+/// Parses a weekday selector such as `Mo-Fr`, `Sa,Su` or `Fr-Mo`. Holidays are accepted, but
+/// never selected.
+fn parse_weekdays(token: &str) -> Result<[bool; 7], anyhow::Error> {
+    let (holidays, days): (Vec<&str>, Vec<&str>) = token
+        .split(',')
+        .partition(|part| matches!(*part, "PH" | "SH"));
+    if days.is_empty() {
+        if holidays.is_empty() {
+            bail!("empty weekday selector");
+        }
+        return Ok([false; 7]);
+    }
+    parse_selector(&days.join(","), &WEEKDAYS)
+}
+
+/// Parses a comma-separated list of names and (possibly wrapping) ranges of them
+fn parse_selector<const N: usize>(
+    token: &str,
+    names: &[&str; N],
+) -> Result<[bool; N], anyhow::Error> {
+    let index = |name: &str| {
+        names
+            .iter()
+            .position(|n| *n == name)
+            .ok_or_else(|| anyhow!("unexpected selector {name:?}"))
+    };
+
+    let mut selected = [false; N];
+    for part in token.split(',') {
+        match part.split_once('-') {
+            Some((from, to)) => {
+                let (from, to) = (index(from)?, index(to)?);
+                let mut i = from;
+                loop {
+                    selected[i] = true;
+                    if i == to {
+                        break;
+                    }
+                    i = (i + 1) % N;
+                }
+            }
+            None => selected[index(part)?] = true,
+        }
+    }
+    Ok(selected)
+}
+
+/// Parses comma-separated time spans, e.g. `06:00-22:00,23:00-01:00` or `sunrise-sunset`. An
+/// open-ended span like `18:00+` runs until midnight.
+fn parse_spans(token: &str) -> Result<Vec<(Minute, Minute)>, anyhow::Error> {
+    token
+        .split(',')
+        .map(|span| {
+            if let Some(start) = span.strip_suffix('+') {
+                return Ok((parse_time(start)?, MINUTES_PER_DAY));
+            }
+            let (start, end) = span
+                .split_once('-')
+                .ok_or_else(|| anyhow!("unexpected time span {span:?}"))?;
+            Ok((parse_time(start)?, parse_time(end)?))
+        })
+        .collect()
+}
+
+fn parse_time(time: &str) -> Result<Minute, anyhow::Error> {
+    match time {
+        "sunrise" | "dawn" => return Ok(SUNRISE),
+        "sunset" | "dusk" => return Ok(SUNSET),
+        _ => {}
+    }
+    let (hours, minutes) = time
+        .split_once(':')
+        .ok_or_else(|| anyhow!("unexpected time {time:?}"))?;
+    let (hours, minutes): (Minute, Minute) = (hours.parse()?, minutes.parse()?);
+    if hours > 24 || minutes >= 60 {
+        bail!("unexpected time {time:?}");
+    }
+    Ok((hours * 60 + minutes).min(MINUTES_PER_DAY))
+}
+
+/// Splits a rule sequence into rules at each comma that precedes a selector rather than another
+/// time span or weekday, e.g. `Mo-Fr 08:00-12:00, Sa 10:00-12:00`
+fn split_rules(sequence: &str) -> Vec<&str> {
+    let mut rules = vec![];
+    let mut start = 0;
+    for (i, _) in sequence.match_indices(", ") {
+        let next = sequence[i + 2..].trim_start();
+        if next.starts_with(|c: char| c.is_ascii_alphabetic()) && !next.starts_with("sun") {
+            rules.push(sequence[start..i].trim());
+            start = i + 2;
+        }
+    }
+    rules.push(sequence[start..].trim());
+    rules.retain(|rule| !rule.is_empty());
+    rules
+}
+
+/// Splits on a separator, except where it's enclosed in parentheses
+fn split_outside_parens(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                parts.push(value[start..i].trim());
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(value[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::graph::{Graph, MAX_OVERLAP, MAX_STRETCH};
+use std::collections::HashSet;
+use std::path::Path;
+
+#[test]
+fn alternatives_are_distinct_and_loopless() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("alternatives", Path::new("tests/grid.osm"))?;
+    let graph = Graph::new()?;
+
+    // across the grid from corner to corner, where many routes are about as good
+    let corners = (Point::new(-73.9995, 40.7000), Point::new(-73.9965, 40.7040));
+    // from eastbound Row 1 St onto Col 1 Ave northbound, where left turns and riding against
+    // traffic are so dear that the cheapest way there is around the block, through the corner
+    // twice. Only an edge-based search can find that path, and it's no alternative.
+    let around_the_block = (Point::new(-73.9985, 40.7020), Point::new(-73.9980, 40.7028));
+    let no_lefts = common::cost_model(
+        r#""turn_costs": {"left": 5000, "u_turn": 5000},
+            "rules": [{"if": {"salmon": true}, "multiply_by": 50}]"#,
+    )?;
+
+    for ((start, end), cost_model) in [
+        (corners, None),
+        (corners, Some(no_lefts.clone())),
+        (around_the_block, Some(no_lefts)),
+    ] {
+        let alternatives = graph.calculate_alternatives(start, end, 3, cost_model, None)?;
+        assert!(!alternatives.is_empty());
+
+        let best = alternatives[0].cost;
+        for alternative in &alternatives {
+            assert!(alternative.overlap <= MAX_OVERLAP);
+            assert!(alternative.cost <= best * MAX_STRETCH);
+
+            let mut visited = HashSet::new();
+            for segment in &alternative.route[1..] {
+                assert!(visited.insert(segment.from.id), "{:?}", segment.from);
+            }
+        }
+    }
+    Ok(())
+}
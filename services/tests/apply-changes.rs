@@ -0,0 +1,47 @@
+mod common;
+
+use common::TestDb;
+use rusty_router::db;
+use std::path::Path;
+
+#[test]
+fn osm_changes_update_ways_in_place() -> Result<(), anyhow::Error> {
+    let _db = TestDb::new("apply-changes")?;
+    let conn = db::get_conn()?;
+
+    let summary = db::apply_changes(Path::new("tests/changes.osc"))?;
+    assert_eq!(summary.ways_upserted, 3);
+    assert_eq!(summary.ways_deleted, 0);
+    assert_eq!(summary.nodes_moved, 1);
+
+    let label = |id: i64| -> Result<(i64, String), anyhow::Error> {
+        Ok(conn.query_row(
+            "SELECT cycleway, name FROM WayLabels WHERE id = ?1",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?)
+    };
+    assert_eq!(label(10)?, (2, "Bleecker & Bowery".to_owned()));
+    assert_eq!(label(13)?.0, 3);
+    assert!(label(11).is_ok());
+
+    // the moved Node lengthens the Way, and the new one starts there
+    let distance = |n1: i64, n2: i64| -> Result<i64, anyhow::Error> {
+        Ok(conn.query_row(
+            "SELECT distance FROM Segments WHERE n1 = ?1 AND n2 = ?2",
+            [n1, n2],
+            |row| row.get(0),
+        )?)
+    };
+    assert_eq!(distance(2, 3)?, 223);
+    assert_eq!(distance(3, 4)?, distance(4, 3)?);
+
+    // the restriction on the modified Way survives
+    assert!(
+        !conn.query_row("SELECT COUNT(*) = 0 FROM TurnRestrictions", [], |row| row
+            .get::<_, bool>(
+            0
+        ))?
+    );
+    Ok(())
+}
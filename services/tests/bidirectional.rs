@@ -0,0 +1,51 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::db;
+use rusty_router::graph::{Graph, MobileCostModel, Route, SearchMode};
+use rusty_router::osm::{NodeId, WayId};
+use std::path::Path;
+
+fn steps(route: &Route) -> Vec<(NodeId, NodeId, WayId)> {
+    route.iter().map(|s| (s.from.id, s.to.id, s.way)).collect()
+}
+
+#[test]
+fn bidirectional_matches_unidirectional() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("bidirectional", Path::new("tests/grid.osm"))?;
+    // a climb up Col 0 Ave from Row 0 St toward Row 1 St, which is a descent the other way
+    db::get_conn()?.execute_batch(
+        "UPDATE Segments SET elevation_gain = 12 WHERE n1 = 300 AND n2 = 110;
+         UPDATE Segments SET elevation_loss = 12 WHERE n1 = 110 AND n2 = 300;",
+    )?;
+    let graph = Graph::new()?;
+    let hilly: MobileCostModel =
+        serde_json::from_str(r#"{"priority": 0.5, "hill_penalty": 2, "salmon_penalty": 1}"#)?;
+    let hilly = hilly.resolve();
+
+    for (start, end, against_traffic) in [
+        // west along Row 1 St, against its traffic
+        ((-73.9975, 40.702), (-73.9995, 40.702), true),
+        // up the climb, and back down it
+        ((-74.0, 40.7005), (-74.0, 40.7035), false),
+        ((-74.0, 40.7035), (-74.0, 40.7005), false),
+        // across the grid
+        ((-73.9995, 40.704), (-73.9965, 40.7), false),
+    ] {
+        let points = [Point::new(start.0, start.1), Point::new(end.0, end.1)];
+        for cost_model in [None, Some(hilly.clone())] {
+            let route = |mode: SearchMode| {
+                graph.calculate_route(&points, false, cost_model.clone(), None, Some(mode), None)
+            };
+            let (forward, _, _) = route(SearchMode::Unidirectional)?;
+            let (both_ways, _, _) = route(SearchMode::Bidirectional)?;
+
+            assert_eq!(steps(&both_ways), steps(&forward));
+            let (cost, expected) = (both_ways.last().unwrap().cost, forward.last().unwrap().cost);
+            assert!((cost - expected).abs() < 1e-3, "{cost} vs {expected}");
+            assert_eq!(forward.iter().any(|s| s.way == -1001), against_traffic);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,110 @@
+mod common;
+
+use common::TestDb;
+use rusty_router::db::{self, Element};
+use rusty_router::graph::CostModel;
+use rusty_router::osm::{BikeNetwork, Cycleway, Lts, Road, Surface};
+use std::path::Path;
+
+#[test]
+fn bike_route_relations() -> Result<(), anyhow::Error> {
+    let relation: Element = serde_json::from_str(
+        r#"{"type": "relation", "id": 1, "tags": {
+            "type": "route", "route": "bicycle", "network": "rcn", "name": "Hudson River Greenway"
+        }, "members": [
+            {"type": "way", "ref": 10, "role": ""},
+            {"type": "way", "ref": 20, "role": "forward"},
+            {"type": "way", "ref": 30, "role": "backward"},
+            {"type": "node", "ref": 100, "role": ""}
+        ]}"#,
+    )?;
+    let route = relation.get_bike_route().unwrap();
+    assert_eq!(route.network, BikeNetwork::Regional);
+    assert_eq!(route.name, "Hudson River Greenway");
+    assert_eq!(route.ways, vec![10, -10, 20, -30]);
+
+    let hiking: Element = serde_json::from_str(
+        r#"{"type": "relation", "id": 2, "tags": {"type": "route", "route": "hiking"}}"#,
+    )?;
+    assert!(hiking.get_bike_route().is_none());
+    Ok(())
+}
+
+#[test]
+fn shared_ways_record_each_route_once() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("bike-routes", Path::new("tests/grid.osm"))?;
+    let mut conn = db::get_conn()?;
+
+    // Row 0 St is on both routes, one of whose names is part of the other's, and is listed
+    // twice by the second
+    let tx = conn.transaction()?;
+    for relation in [
+        r#"{"type": "relation", "id": 1, "tags": {
+            "type": "route", "route": "bicycle", "network": "rcn", "name": "Hudson River Greenway"
+        }, "members": [{"type": "way", "ref": 1000, "role": ""}]}"#,
+        r#"{"type": "relation", "id": 2, "tags": {
+            "type": "route", "route": "bicycle", "network": "rcn", "name": "Hudson River"
+        }, "members": [
+            {"type": "way", "ref": 1000, "role": ""},
+            {"type": "way", "ref": 1000, "role": ""}
+        ]}"#,
+    ] {
+        db::insert_relation_element(&tx, serde_json::from_str(relation)?)?;
+    }
+    tx.commit()?;
+
+    let (network, route): (u8, String) = conn.query_row(
+        "SELECT network, route FROM WayLabels WHERE id = 1000",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    assert_eq!(network, BikeNetwork::Regional as u8);
+    assert_eq!(route, "Hudson River Greenway; Hudson River");
+    Ok(())
+}
+
+#[test]
+fn signed_routes_are_discounted() -> Result<(), anyhow::Error> {
+    let cost_model = common::cost_model(r#""bike_route_coefficient": 0.25"#)?;
+    cost_model.validate()?;
+
+    let off_route = cost_model.calculate_cost(&(
+        Cycleway::Lane,
+        Road::Local,
+        false,
+        BikeNetwork::None,
+        Surface::Paved,
+        Lts::Lts2,
+    ));
+    let on_route = cost_model.calculate_cost(&(
+        Cycleway::Lane,
+        Road::Local,
+        false,
+        BikeNetwork::Local,
+        Surface::Paved,
+        Lts::Lts2,
+    ));
+    assert!((on_route - off_route * 0.75).abs() < 1e-6);
+
+    // the default model leaves routes alone
+    let labels = (
+        Cycleway::Lane,
+        Road::Local,
+        false,
+        BikeNetwork::National,
+        Surface::Paved,
+        Lts::Lts2,
+    );
+    assert_eq!(
+        CostModel::default().calculate_cost(&labels),
+        CostModel::default().calculate_cost(&(
+            labels.0,
+            labels.1,
+            labels.2,
+            BikeNetwork::None,
+            labels.4,
+            labels.5,
+        ))
+    );
+    Ok(())
+}
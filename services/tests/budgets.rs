@@ -0,0 +1,67 @@
+mod common;
+
+use common::TestDb;
+use geo::{HaversineDistance, Point};
+use rusty_router::graph::{
+    BudgetLimit, CancellationToken, Graph, RoutingError, SearchBudget, SearchMode, START_NODE_ID,
+};
+use std::path::Path;
+
+#[test]
+fn exhausted_budgets_report_partial_routes() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("budgets", Path::new("tests/grid.osm"))?;
+    // from one corner of the grid to the other, which takes more than a few expansions
+    let (start, end) = (Point::new(-73.9995, 40.7), Point::new(-73.9965, 40.704));
+
+    for search_mode in [SearchMode::Unidirectional, SearchMode::Bidirectional] {
+        let graph = Graph::new()?.with_budget(SearchBudget::default().with_max_expanded(3));
+        let e = graph
+            .calculate_route(&[start, end], false, None, None, Some(search_mode), None)
+            .unwrap_err();
+        let Some(RoutingError::BudgetExceeded {
+            limit,
+            expanded,
+            partial: Some(partial),
+            ..
+        }) = e.downcast_ref::<RoutingError>()
+        else {
+            panic!("expected a partial route, got {e:?}");
+        };
+        assert_eq!(*limit, BudgetLimit::Expanded);
+        assert_eq!(*expanded, 4);
+
+        // the partial route sets off from the start, and gets the rider some way toward the end
+        assert_eq!(partial.first().unwrap().from.id, START_NODE_ID);
+        for pair in partial.windows(2) {
+            assert_eq!(pair[1].from.id, pair[0].to.id);
+        }
+        let reached = partial.last().unwrap().to.geometry;
+        assert!(reached.haversine_distance(&end) < start.haversine_distance(&end));
+    }
+
+    for (budget, expected) in [
+        (
+            SearchBudget::default().with_max_cost(100.0),
+            BudgetLimit::Cost,
+        ),
+        (
+            SearchBudget::default().with_cancellation({
+                let cancellation = CancellationToken::new();
+                cancellation.cancel();
+                cancellation
+            }),
+            BudgetLimit::Cancelled,
+        ),
+    ] {
+        let graph = Graph::new()?.with_budget(budget);
+        let e = graph
+            .calculate_route(&[start, end], false, None, None, None, None)
+            .unwrap_err();
+        let limit = match e.downcast_ref::<RoutingError>() {
+            Some(RoutingError::BudgetExceeded { limit, .. }) => Some(*limit),
+            _ => None,
+        };
+        assert_eq!(limit, Some(expected));
+    }
+    Ok(())
+}
@@ -0,0 +1,89 @@
+//! Fixtures shared between the integration tests
+#![allow(dead_code)]
+
+use rusty_router::db;
+use rusty_router::graph::CostModel;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+/// The fields every CostModel needs, as JSON object members for a test to add its own to
+pub const BASE_COST_MODEL: &str = r#""cycleway_coefficient": 0.3, "road_coefficient": 0.4, "salmon_coefficient": 1.3,
+    "cycleway_weights": {"No": 1.7, "Shared": 1.5, "Lane": 1.0, "Track": 0.5},
+    "road_weights": {"Pedestrian": 1.2, "Bike": 0.5, "Local": 1.2, "Collector": 1.4, "Arterial": 2.0}"#;
+
+/// A CostModel object in JSON, with the base fields and any `extra` members after them
+pub fn cost_model_json(extra: &str) -> String {
+    if extra.is_empty() {
+        format!("{{{BASE_COST_MODEL}}}")
+    } else {
+        format!("{{{BASE_COST_MODEL}, {extra}}}")
+    }
+}
+
+/// Parses a CostModel from the base fields and any `extra` members
+pub fn cost_model(extra: &str) -> Result<CostModel, serde_json::Error> {
+    serde_json::from_str(&cost_model_json(extra))
+}
+
+/// DB_PATH and GRAPH_PATH are read from the environment, which every test in a binary shares
+static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+/// A database populated from an extract, at a temporary DB_PATH of its own. Tests using one run
+/// one at a time, and its files are removed when it's dropped, whether or not the test passed.
+pub struct TestDb {
+    name: String,
+    pub path: PathBuf,
+    scratch: Vec<PathBuf>,
+    _environment: MutexGuard<'static, ()>,
+}
+
+impl TestDb {
+    /// a database of tests/extract.osm
+    pub fn new(name: &str) -> Result<Self, anyhow::Error> {
+        Self::from_extract(name, Path::new("tests/extract.osm"))
+    }
+
+    pub fn from_extract(name: &str, extract: &Path) -> Result<Self, anyhow::Error> {
+        // a test failing while holding the lock leaves nothing the next one depends on
+        let environment = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::remove_var("GRAPH_PATH");
+        let test_db = Self {
+            name: name.to_owned(),
+            path: temp_path(name, "db3"),
+            scratch: vec![],
+            _environment: environment,
+        };
+        // left over from a run that was killed
+        let _ = std::fs::remove_file(&test_db.path);
+        std::env::set_var("DB_PATH", &test_db.path);
+        let conn = db::get_conn()?;
+        db::init_tables(&conn)?;
+        db::populate_from_extract(extract)?;
+        Ok(test_db)
+    }
+
+    /// A path for another temporary file, removed along with the database
+    pub fn scratch_file(&mut self, extension: &str) -> PathBuf {
+        let path = temp_path(&format!("{}-{}", self.name, self.scratch.len()), extension);
+        self.scratch.push(path.clone());
+        path
+    }
+}
+
+impl Drop for TestDb {
+    fn drop(&mut self) {
+        std::env::remove_var("DB_PATH");
+        std::env::remove_var("GRAPH_PATH");
+        for path in self.scratch.iter().chain([&self.path]) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// unique to the test binary, so binaries run side by side don't share files
+fn temp_path(name: &str, extension: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "rusty-router-{name}-{}.{extension}",
+        std::process::id()
+    ))
+}
@@ -0,0 +1,34 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::api::geojson::serialize_route_geom;
+use rusty_router::graph::{Graph, SqliteGraphRepository};
+use std::path::Path;
+
+#[test]
+fn compacted_routes_keep_their_geometry() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("compaction", Path::new("tests/grid.osm"))?;
+    // the in-memory graph compacts each block into one edge, which the DB's doesn't
+    let compacted = Graph::new()?;
+    let mut uncompacted = Graph::new()?;
+    uncompacted.db = Box::new(SqliteGraphRepository::new()?);
+
+    for points in [
+        // along Row 0 St and up Col 2 Ave, past the Nodes halfway along each block
+        [Point::new(-73.9995, 40.7), Point::new(-73.996, 40.7035)],
+        // ending halfway between a block's corner and its midpoint, where the search has to be
+        // able to stop partway along the chain
+        [Point::new(-73.9965, 40.7), Point::new(-74.0, 40.7015)],
+    ] {
+        let (route, _, _) = compacted.calculate_route(&points, false, None, None, None, None)?;
+        let (expected, _, _) =
+            uncompacted.calculate_route(&points, false, None, None, None, None)?;
+        assert!(route.iter().any(|segment| segment.to.id >= 200));
+        assert_eq!(
+            serialize_route_geom(&route)?,
+            serialize_route_geom(&expected)?
+        );
+    }
+    Ok(())
+}
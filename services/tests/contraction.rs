@@ -0,0 +1,111 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::db;
+use rusty_router::graph::{
+    ContractionHierarchy, CostModel, Graph, Route, RouteMetadata, END_NODE_ID, START_NODE_ID,
+};
+use std::path::Path;
+
+fn contract(name: &str) -> Result<(), anyhow::Error> {
+    let mut conn = db::get_conn()?;
+    db::init_contraction_tables(&conn)?;
+    ContractionHierarchy::build(&conn, name, CostModel::default())?.save(&mut conn)?;
+    Ok(())
+}
+
+type Routed = (Route, RouteMetadata);
+
+/// routes with the hierarchy, and then again with a unidirectional search
+fn route_both_ways(graph: &mut Graph, points: &[Point]) -> Result<(Routed, Routed), anyhow::Error> {
+    assert_eq!(graph.hierarchies.len(), 1);
+    let (contracted, _, contracted_meta) =
+        graph.calculate_route(points, false, None, None, None, None)?;
+    let hierarchies = std::mem::take(&mut graph.hierarchies);
+    let (searched, _, searched_meta) =
+        graph.calculate_route(points, false, None, None, None, None)?;
+    graph.hierarchies = hierarchies;
+    Ok(((contracted, contracted_meta), (searched, searched_meta)))
+}
+
+fn node_ids(route: &Route) -> Vec<i64> {
+    route.iter().map(|segment| segment.to.id).collect()
+}
+
+fn heuristic(meta: &RouteMetadata) -> Result<String, anyhow::Error> {
+    Ok(serde_json::to_value(meta)?["heuristic"]
+        .as_str()
+        .unwrap_or_default()
+        .to_owned())
+}
+
+/// every segment leaves from where the one before it arrived, from the start to the end point
+fn assert_chained(route: &Route) {
+    assert_eq!(route.first().unwrap().from.id, START_NODE_ID);
+    assert_eq!(route.last().unwrap().to.id, END_NODE_ID);
+    let mut distance = 0;
+    for (i, segment) in route.iter().enumerate() {
+        if i > 0 {
+            assert_eq!(segment.from.id, route[i - 1].to.id);
+            assert_eq!(segment.from.geometry, route[i - 1].to.geometry);
+            assert!(segment.cost_so_far >= route[i - 1].cost_so_far);
+        }
+        distance += segment.length;
+        assert_eq!(segment.distance_so_far, distance);
+    }
+}
+
+#[test]
+fn hierarchy_matches_search() -> Result<(), anyhow::Error> {
+    let _db = TestDb::new("contraction")?;
+    contract("default")?;
+
+    let mut graph = Graph::new()?;
+    let points = [Point::new(-73.999, 40.7001), Point::new(-73.999, 40.7019)];
+    let ((contracted, meta), (searched, _)) = route_both_ways(&mut graph, &points)?;
+    assert_eq!(heuristic(&meta)?, "none");
+    assert_eq!(node_ids(&contracted), node_ids(&searched));
+    assert_eq!(
+        contracted.last().unwrap().cost,
+        searched.last().unwrap().cost
+    );
+    assert_chained(&contracted);
+    Ok(())
+}
+
+#[test]
+fn hierarchy_unpacks_shortcuts_and_obeys_restrictions() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("contraction-grid", Path::new("tests/grid.osm"))?;
+    contract("default")?;
+
+    // north up Col 0 Ave, then right onto Row 2 St. The Nodes halfway along each block are
+    // contracted first, leaving shortcuts past them to be unpacked.
+    let points = [Point::new(-74.0, 40.7005), Point::new(-73.9995, 40.704)];
+    let mut graph = Graph::new()?;
+    let ((contracted, meta), (searched, _)) = route_both_ways(&mut graph, &points)?;
+    assert_eq!(heuristic(&meta)?, "none");
+    assert_eq!(node_ids(&contracted), node_ids(&searched));
+    assert_eq!(node_ids(&contracted), [300, 110, 310, 120, END_NODE_ID]);
+    assert!(
+        (contracted.last().unwrap().cost - searched.last().unwrap().cost).abs() < 1e-3,
+        "{} vs {}",
+        contracted.last().unwrap().cost,
+        searched.last().unwrap().cost
+    );
+    assert_chained(&contracted);
+
+    // the hierarchy knows nothing of turn restrictions, so the route it finds once that right
+    // turn is banned has to be searched for again
+    db::get_conn()?.execute(
+        "INSERT INTO TurnRestrictions (from_way, via, to_way, only) VALUES (2000, 120, 1002, 0)",
+        [],
+    )?;
+    let mut graph = Graph::new()?;
+    let ((detour, meta), (searched, _)) = route_both_ways(&mut graph, &points)?;
+    assert_ne!(heuristic(&meta)?, "none");
+    assert!(!node_ids(&detour).contains(&310));
+    assert_eq!(node_ids(&detour), node_ids(&searched));
+    assert_chained(&detour);
+    Ok(())
+}
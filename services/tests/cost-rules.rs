@@ -0,0 +1,129 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::graph::{
+    CostModel, Graph, GraphRepository, InMemoryGraphRepository, MobileCostModel,
+    SqliteGraphRepository,
+};
+use rusty_router::osm::{BikeNetwork, Cycleway, Lts, Road, Surface, WayLabels};
+
+fn with_rules(rules: &str) -> Result<CostModel, serde_json::Error> {
+    common::cost_model(&format!(r#""rules": {rules}"#))
+}
+
+fn labels(cycleway: Cycleway, road: Road, lts: Lts) -> WayLabels {
+    (
+        cycleway,
+        road,
+        false,
+        BikeNetwork::None,
+        Surface::Paved,
+        lts,
+    )
+}
+
+#[test]
+fn label_rules() -> Result<(), anyhow::Error> {
+    let plain = with_rules("[]")?;
+    let custom = with_rules(
+        r#"[{"if": {"road": "Collector", "cycleway": "No"}, "multiply_by": 1.5},
+            {"if": {"lts": [3, 4]}, "multiply_by": 2.0},
+            {"if": {"salmon": true}, "multiply_by": 3.0}]"#,
+    )?;
+    custom.validate()?;
+
+    let ratio = |labels: &WayLabels| custom.calculate_cost(labels) / plain.calculate_cost(labels);
+    assert_eq!(
+        ratio(&labels(Cycleway::No, Road::Collector, Lts::Lts1)),
+        1.5
+    );
+    assert_eq!(
+        ratio(&labels(Cycleway::Lane, Road::Collector, Lts::Lts1)),
+        1.0
+    );
+    // every rule a Way meets applies
+    assert_eq!(
+        ratio(&labels(Cycleway::No, Road::Collector, Lts::Lts4)),
+        3.0
+    );
+    let (cycleway, road, _, network, surface, lts) = labels(Cycleway::Lane, Road::Local, Lts::Lts1);
+    assert_eq!(ratio(&(cycleway, road, true, network, surface, lts)), 3.0);
+
+    // models differing only in their rules aren't answered by each other's preprocessing
+    assert_ne!(custom, plain);
+    assert_eq!(with_rules("[]")?, plain);
+
+    let mobile: MobileCostModel = serde_json::from_str(
+        r#"{"priority": 0.5, "hill_penalty": 0, "salmon_penalty": 1,
+            "rules": [{"if": {"surface": "Cobbled"}, "multiply_by": 4}]}"#,
+    )?;
+    let cobbled = (
+        Cycleway::No,
+        Road::Local,
+        false,
+        BikeNetwork::None,
+        Surface::Cobbled,
+        Lts::Lts1,
+    );
+    let with_cobbles = mobile.resolve().calculate_cost(&cobbled);
+    let without: MobileCostModel =
+        serde_json::from_str(r#"{"priority": 0.5, "hill_penalty": 0, "salmon_penalty": 1}"#)?;
+    assert!((with_cobbles / without.resolve().calculate_cost(&cobbled) - 4.0).abs() < 1e-6);
+    Ok(())
+}
+
+#[test]
+fn invalid_rules() -> Result<(), anyhow::Error> {
+    assert!(
+        with_rules(r#"[{"if": {"road": "Collector"}, "multiply_by": 0}]"#)?
+            .validate()
+            .is_err()
+    );
+    assert!(
+        with_rules(r#"[{"if": {"name": " "}, "multiply_by": 0.5}]"#)?
+            .validate()
+            .is_err()
+    );
+    // misspelt fields and values are turned away rather than matching everything
+    assert!(with_rules(r#"[{"if": {"raod": "Collector"}, "multiply_by": 2}]"#).is_err());
+    assert!(with_rules(r#"[{"if": {"road": "Highway"}, "multiply_by": 2}]"#).is_err());
+    assert!(with_rules(r#"[{"if": {"lts": 5}, "multiply_by": 2}]"#).is_err());
+    assert!(with_rules(r#"[{"when": {"road": "Collector"}, "multiply_by": 2}]"#).is_err());
+    Ok(())
+}
+
+#[test]
+fn name_rules() -> Result<(), anyhow::Error> {
+    let _db = TestDb::new("cost-rules")?;
+
+    let sqlite = SqliteGraphRepository::new()?;
+    let in_memory = InMemoryGraphRepository::new()?;
+    for repository in [&sqlite as &dyn GraphRepository, &in_memory] {
+        let mut found = repository.find_ways_named("bleecker")?;
+        found.sort();
+        assert!(!found.is_empty());
+        assert!(found.iter().all(|way| way.abs() == 10));
+        assert!(repository.find_ways_named("Ocean Pkwy")?.is_empty());
+    }
+
+    let graph = Graph::new()?;
+    let points = [Point::new(-73.999, 40.7001), Point::new(-73.999, 40.7019)];
+    let cost_of = |cost_model: CostModel| -> Result<f32, anyhow::Error> {
+        let (route, _, _) =
+            graph.calculate_route(&points, false, Some(cost_model), None, None, None)?;
+        assert!(route.iter().any(|segment| segment.way.abs() == 10));
+        Ok(route.last().unwrap().cost)
+    };
+    let plain = cost_of(with_rules("[]")?)?;
+    let named = cost_of(with_rules(
+        r#"[{"if": {"name": "Bleecker"}, "multiply_by": 0.5}]"#,
+    )?)?;
+    assert!((named / plain - 0.5).abs() < 1e-3, "{named} vs {plain}");
+    // a name rule only applies to the Ways also meeting the rest of its condition
+    let elsewhere = cost_of(with_rules(
+        r#"[{"if": {"name": "Bleecker", "road": "Arterial"}, "multiply_by": 0.5}]"#,
+    )?)?;
+    assert_eq!(elsewhere, plain);
+    Ok(())
+}
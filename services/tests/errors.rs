@@ -0,0 +1,84 @@
+mod common;
+
+use anyhow::anyhow;
+use common::TestDb;
+use geo::Point;
+use rusty_router::api::error::serialize_error;
+use rusty_router::db;
+use rusty_router::graph::{Graph, SearchBudget};
+use std::path::Path;
+
+#[test]
+fn routing_errors_map_to_statuses() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("errors", Path::new("tests/grid.osm"))?;
+    // tear out the middle of the grid, leaving a hole in it too far from any street to snap
+    // from, and cut off the top right corner's block from the rest
+    db::get_conn()?.execute_batch(
+        "DELETE FROM Segments WHERE abs(way) IN (1001, 2001);
+         DELETE FROM Segments WHERE (n1, n2) IN (VALUES (121, 221), (221, 121), (312, 122), (122, 312));",
+    )?;
+    let graph = Graph::new()?;
+    let start = Point::new(-74.0, 40.7005);
+    let invalid_cost_model = common::cost_model(r#""turn_costs": {"left": -1}"#)?;
+
+    for (points, cost_model, status, code) in [
+        (vec![start], None, 400, "invalid_request"),
+        (
+            vec![start, Point::new(-73.9965, 40.7)],
+            Some(invalid_cost_model),
+            400,
+            "invalid_cost_model",
+        ),
+        (
+            vec![start, Point::new(-73.9965, 40.704)],
+            None,
+            404,
+            "no_path_found",
+        ),
+        (
+            vec![start, Point::new(-73.998, 40.702)],
+            None,
+            422,
+            "snap_failed",
+        ),
+        (
+            vec![start, Point::new(-73.9, 40.8)],
+            None,
+            422,
+            "out_of_coverage",
+        ),
+    ] {
+        let e = graph
+            .calculate_route(&points, false, cost_model, None, None, None)
+            .unwrap_err();
+        let (actual_status, body) = serialize_error(&e)?;
+        assert_eq!(
+            (actual_status, body["error"]["code"].as_str()),
+            (status, Some(code))
+        );
+        assert_eq!(body["error"]["message"], e.to_string());
+        assert!(body["error"].get("partial_route").is_none());
+    }
+
+    // a search that gives up reports how far it got
+    let graph = graph.with_budget(SearchBudget::default().with_max_expanded(2));
+    let points = [start, Point::new(-73.9965, 40.7)];
+    let e = graph
+        .calculate_route(&points, false, None, None, None, None)
+        .unwrap_err();
+    let (status, body) = serialize_error(&e)?;
+    assert_eq!(
+        (status, body["error"]["code"].as_str()),
+        (503, Some("budget_exceeded"))
+    );
+    assert_eq!(body["error"]["partial_route"]["type"], "FeatureCollection");
+
+    // anything else is the server's fault, and isn't explained
+    let (status, body) = serialize_error(&anyhow!("Traversal failed"))?;
+    assert_eq!(
+        (status, body["error"]["code"].as_str()),
+        (500, Some("internal"))
+    );
+    assert_eq!(body["error"]["message"], "Internal server error");
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use rusty_router::db::{read_extract, Element};
+use std::path::Path;
+
+#[test]
+fn xml_extract_elements() -> Result<(), anyhow::Error> {
+    let mut elements: Vec<Element> = vec![];
+    read_extract(Path::new("tests/extract.osm"), |element| {
+        elements.push(element);
+        Ok(())
+    })?;
+    assert_eq!(elements.len(), 7);
+
+    let signal = &elements[1];
+    assert_eq!((signal.r#type.as_str(), signal.id), ("node", 2));
+    assert_eq!((signal.lat, signal.lon), (Some(40.701), Some(-73.999)));
+    assert_eq!(signal.tags["highway"], "traffic_signals");
+
+    let street = &elements[3];
+    assert_eq!(street.nodes, Some(vec![1, 2, 3]));
+    assert_eq!(street.tags["name"], "Bleecker & Bowery");
+    assert!(street.is_routable_way());
+    assert!(!elements[4].is_routable_way());
+    assert!(!elements[5].is_routable_way());
+
+    let restriction = &elements[6];
+    assert_eq!(restriction.get_turn_restrictions().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn pbf_extract_elements() -> Result<(), anyhow::Error> {
+    let read = |path: &str| -> Result<Vec<Element>, anyhow::Error> {
+        let mut elements: Vec<Element> = vec![];
+        read_extract(Path::new(path), |element| {
+            elements.push(element);
+            Ok(())
+        })?;
+        Ok(elements)
+    };
+    let xml = read("tests/extract.osm")?;
+    let pbf = read("tests/extract.osm.pbf")?;
+    assert_eq!(pbf.len(), xml.len());
+
+    let close = |a: Option<f64>, b: Option<f64>| match (a, b) {
+        (Some(a), Some(b)) => (a - b).abs() < 1e-7,
+        (a, b) => a == b,
+    };
+    for (pbf, xml) in pbf.iter().zip(&xml) {
+        assert_eq!((&pbf.r#type, pbf.id), (&xml.r#type, xml.id));
+        assert_eq!(pbf.tags, xml.tags);
+        assert!(
+            close(pbf.lat, xml.lat) && close(pbf.lon, xml.lon),
+            "{pbf:?}"
+        );
+        assert_eq!(pbf.nodes, xml.nodes);
+        let members = |element: &Element| {
+            element.members.as_ref().map(|members| {
+                members
+                    .iter()
+                    .map(|m| (m.r#type.clone(), m.r#ref, m.role.clone()))
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(members(pbf), members(xml));
+    }
+    Ok(())
+}
+
+#[test]
+fn unknown_extract_format() {
+    assert!(read_extract(Path::new("tests/route-request.json"), |_| Ok(())).is_err());
+}
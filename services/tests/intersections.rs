@@ -0,0 +1,103 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::db::Element;
+use rusty_router::graph::{
+    CostModel, Graph, GraphRepository, InMemoryGraphRepository, MobileCostModel,
+    SqliteGraphRepository, Turn,
+};
+use rusty_router::osm::Control;
+
+fn control_of(tags: &str) -> Result<Control, anyhow::Error> {
+    let element: Element =
+        serde_json::from_str(&format!(r#"{{"type": "node", "id": 1, "tags": {tags}}}"#))?;
+    Ok(element.get_control())
+}
+
+#[test]
+fn control_tags() -> Result<(), anyhow::Error> {
+    assert_eq!(control_of(r#"{}"#)?, Control::None);
+    assert_eq!(
+        control_of(r#"{"highway": "traffic_signals"}"#)?,
+        Control::Signals
+    );
+    assert_eq!(
+        control_of(r#"{"highway": "crossing", "crossing": "traffic_signals"}"#)?,
+        Control::Signals
+    );
+    assert_eq!(control_of(r#"{"highway": "stop"}"#)?, Control::Stop);
+    assert_eq!(
+        control_of(r#"{"crossing": "uncontrolled"}"#)?,
+        Control::Crossing
+    );
+    // somewhere people are known not to cross
+    assert_eq!(control_of(r#"{"crossing": "no"}"#)?, Control::None);
+    Ok(())
+}
+
+#[test]
+fn intersection_delays() -> Result<(), anyhow::Error> {
+    // by default, nobody waits anywhere
+    let cost_model = CostModel::default();
+    assert!(!cost_model.has_turn_costs());
+    assert_eq!(
+        cost_model.calculate_intersection_delay(Control::Signals, Turn::Straight, false),
+        0.0
+    );
+
+    let cost_model = common::cost_model(
+        r#""intersection_delays": {"signal": 30, "stop": 10, "arterial_crossing": 50}"#,
+    )?;
+    cost_model.validate()?;
+    assert!(cost_model.has_turn_costs());
+    // signals stop the cross traffic, so there's no gap to wait for
+    assert_eq!(
+        cost_model.calculate_intersection_delay(Control::Signals, Turn::Left, true),
+        30.0
+    );
+    assert_eq!(
+        cost_model.calculate_intersection_delay(Control::Stop, Turn::Straight, true),
+        60.0
+    );
+    // turning right onto the Arterial doesn't cross its traffic
+    assert_eq!(
+        cost_model.calculate_intersection_delay(Control::Stop, Turn::Right, true),
+        10.0
+    );
+    assert_eq!(
+        cost_model.calculate_intersection_delay(Control::None, Turn::Straight, false),
+        0.0
+    );
+
+    let negative = common::cost_model(r#""intersection_delays": {"signal": -1}"#)?;
+    assert!(negative.validate().is_err());
+
+    // riders who avoid turns also avoid waiting at lights
+    let mobile: MobileCostModel = serde_json::from_str(
+        r#"{"priority": 0.5, "hill_penalty": 0, "salmon_penalty": 1, "turn_penalty": 1}"#,
+    )?;
+    let cost_model = mobile.resolve();
+    assert!(cost_model.calculate_intersection_delay(Control::Signals, Turn::Straight, false) > 0.0);
+    Ok(())
+}
+
+#[test]
+fn signals_along_route() -> Result<(), anyhow::Error> {
+    let _db = TestDb::new("intersections")?;
+
+    let sqlite = SqliteGraphRepository::new()?;
+    let in_memory = InMemoryGraphRepository::new()?;
+    for repository in [&sqlite as &dyn GraphRepository, &in_memory] {
+        assert_eq!(repository.get_control(2)?, Control::Signals);
+        assert_eq!(repository.get_control(1)?, Control::None);
+    }
+
+    let graph = Graph::new()?;
+    let points = [Point::new(-73.999, 40.7001), Point::new(-73.999, 40.7019)];
+    let (route, _, meta) = graph.calculate_route(&points, false, None, None, None, None)?;
+    assert!(route.iter().any(|segment| segment.to.id == 2));
+    assert_eq!(meta.signals(), 1);
+    assert_eq!(serde_json::to_value(&meta)?["signals"], 1);
+    Ok(())
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use common::TestDb;
+use geo::{Area, Contains, Intersects, Point};
+use rusty_router::graph::{Graph, RoutingError, TraversalMetric};
+use std::path::Path;
+
+#[test]
+fn isochrone_bands_are_nested() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("isochrones", Path::new("tests/grid.osm"))?;
+    let graph = Graph::new()?;
+    let start = Point::new(-73.998, 40.7015);
+
+    for metric in [TraversalMetric::Distance, TraversalMetric::Time] {
+        // bands may be given in any order, but come back smallest first
+        let bands = match metric {
+            TraversalMetric::Time => [120.0, 30.0, 60.0],
+            _ => [600.0, 150.0, 300.0],
+        };
+        let isochrones = graph.calculate_isochrones(start, metric, &bands, None)?;
+        let mut thresholds: Vec<f64> = isochrones.iter().map(|i| i.threshold).collect();
+        thresholds.dedup();
+        assert_eq!(thresholds.len(), 3);
+        assert!(thresholds.is_sorted());
+
+        // the start is on a street, which may well run along the hull's edge
+        assert!(isochrones[0].geometry.intersects(&start));
+        for pair in isochrones.windows(2) {
+            assert!(pair[0].geometry.unsigned_area() < pair[1].geometry.unsigned_area());
+            assert!(pair[1].geometry.contains(&pair[0].geometry));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn invalid_isochrone_bands_are_rejected() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("isochrones-invalid", Path::new("tests/grid.osm"))?;
+    let graph = Graph::new()?;
+    let start = Point::new(-73.998, 40.7015);
+
+    for bands in [vec![], vec![300.0, -100.0], vec![0.0], vec![f64::NAN]] {
+        let e = graph
+            .calculate_isochrones(start, TraversalMetric::Distance, &bands, None)
+            .unwrap_err();
+        let status = e.downcast_ref::<RoutingError>().map(RoutingError::status);
+        assert_eq!(status, Some(400), "{bands:?}");
+    }
+    Ok(())
+}
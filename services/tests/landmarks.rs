@@ -0,0 +1,44 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::db;
+use rusty_router::graph::{Graph, Landmarks, MobileCostModel};
+
+#[test]
+fn landmarks_guide_matching_requests() -> Result<(), anyhow::Error> {
+    let _db = TestDb::new("landmarks")?;
+    let mut conn = db::get_conn()?;
+    db::init_landmark_tables(&conn)?;
+    let cost_model = MobileCostModel::speed().resolve();
+    Landmarks::build(&conn, "mobile-speed", cost_model.clone())?.save(&mut conn)?;
+
+    let mut graph = Graph::new()?;
+    assert_eq!(graph.landmarks.len(), 1);
+    let points = [Point::new(-73.999, 40.7001), Point::new(-73.999, 40.7019)];
+    // the clients always send a heuristic weight, which mustn't turn landmarks off
+    let (guided, _, meta) = graph.calculate_route(
+        &points,
+        false,
+        Some(cost_model.clone()),
+        Some(0.75),
+        None,
+        None,
+    )?;
+    assert_eq!(serde_json::to_value(&meta)?["heuristic"], "landmarks");
+
+    // a model without landmarks falls back on the weighted straight-line distance
+    let (_, _, meta) = graph.calculate_route(&points, false, None, Some(0.75), None, None)?;
+    assert_eq!(serde_json::to_value(&meta)?["heuristic"], "haversine");
+
+    graph.landmarks.clear();
+    let (plain, _, meta) =
+        graph.calculate_route(&points, false, Some(cost_model), Some(0.75), None, None)?;
+    assert_eq!(serde_json::to_value(&meta)?["heuristic"], "haversine");
+    assert_eq!(
+        guided.iter().map(|s| s.to.id).collect::<Vec<_>>(),
+        plain.iter().map(|s| s.to.id).collect::<Vec<_>>()
+    );
+    assert_eq!(guided.last().unwrap().cost, plain.last().unwrap().cost);
+    Ok(())
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::graph::{Graph, LENGTH_TOLERANCE, MAX_LOOP_OVERLAP};
+use rusty_router::osm::{Distance, NodeId};
+use std::collections::HashSet;
+use std::path::Path;
+
+#[test]
+fn loops_return_to_the_start() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("loops", Path::new("tests/grid.osm"))?;
+    let graph = Graph::new()?;
+
+    // from the east end of Row 1 St, which is one-way, so loops differ in how far they ride
+    // against traffic
+    let (start, target) = (Point::new(-73.996, 40.702), 800);
+    let loops = graph.calculate_loops(start, target, 5, Some(common::cost_model("")?))?;
+    assert!(loops.len() > 1);
+
+    for pair in loops.windows(2) {
+        assert!(pair[0].avg_cost_factor <= pair[1].avg_cost_factor);
+    }
+
+    for candidate in &loops {
+        let (first, last) = (&candidate.route[0], candidate.route.last().unwrap());
+        assert_eq!(first.from.geometry, last.to.geometry);
+        assert_eq!(candidate.distance, last.distance_so_far);
+        assert!(
+            (candidate.distance - target).abs() as f64 <= target as f64 * LENGTH_TOLERANCE,
+            "{}",
+            candidate.distance
+        );
+
+        let weighted: f32 = candidate
+            .route
+            .iter()
+            .map(|s| s.cost_factor * s.length as f32)
+            .sum();
+        let avg_cost_factor = weighted / candidate.distance as f32;
+        assert!((candidate.avg_cost_factor - avg_cost_factor).abs() < 1e-4);
+
+        // each leg may only ride back over what the legs before it rode so far
+        let mut ridden: HashSet<(NodeId, NodeId)> = HashSet::new();
+        let mut reused: Distance = 0;
+        for leg in 0..=candidate.route.last().unwrap().leg {
+            let segments: Vec<_> = candidate.route.iter().filter(|s| s.leg == leg).collect();
+            for segment in &segments {
+                let (from, to) = (segment.from.id, segment.to.id);
+                if ridden.contains(&(from, to)) || ridden.contains(&(to, from)) {
+                    reused += segment.length;
+                }
+            }
+            ridden.extend(segments.iter().map(|s| (s.from.id, s.to.id)));
+        }
+        let overlap = reused as f32 / candidate.distance as f32;
+        assert!(overlap <= MAX_LOOP_OVERLAP, "{overlap}");
+        assert!((candidate.overlap - overlap).abs() < 1e-6);
+    }
+    Ok(())
+}
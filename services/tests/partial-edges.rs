@@ -0,0 +1,65 @@
+mod common;
+
+use common::TestDb;
+use geo::{Coord, Point};
+use rusty_router::db;
+use rusty_router::graph::{Graph, END_NODE_ID, START_NODE_ID};
+use std::path::Path;
+
+#[test]
+fn points_along_one_segment_route_directly() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("partial-edges", Path::new("tests/grid.osm"))?;
+    // Col 0 Ave climbs 12m over the 112m block north of its midpoint
+    db::get_conn()?.execute_batch(
+        "UPDATE Segments SET elevation_gain = 12 WHERE n1 = 300 AND n2 = 110;
+         UPDATE Segments SET elevation_loss = 12 WHERE n1 = 110 AND n2 = 300;",
+    )?;
+    let graph = Graph::new()?;
+
+    // from a fifth of the way along that block to four fifths, and back, neither of which
+    // should send the rider off to a corner
+    let (lower, upper) = (Point::new(-74.0, 40.7012), Point::new(-74.0, 40.7018));
+    let mut lengths = vec![];
+    for (start, end, way, gain, loss) in [(lower, upper, 2000, 7, 0), (upper, lower, -2000, 0, 7)] {
+        let (route, _, _) = graph.calculate_route(&[start, end], false, None, None, None, None)?;
+        assert_eq!(route.len(), 1);
+        let segment = &route[0];
+        assert_eq!(
+            (segment.from.id, segment.to.id),
+            (START_NODE_ID, END_NODE_ID)
+        );
+        assert_eq!(segment.way, way);
+        assert_eq!(segment.geometry.start, Coord::from(start));
+        assert_eq!(segment.geometry.end, Coord::from(end));
+        // three fifths of the block (give or take rounding), and of its climb
+        assert!((66..=68).contains(&segment.length), "{}", segment.length);
+        assert_eq!(segment.distance_so_far, segment.length);
+        lengths.push(segment.length);
+        assert_eq!(
+            (segment.elevation_gain, segment.elevation_loss),
+            (gain, loss)
+        );
+    }
+    assert_eq!(lengths[0], lengths[1]);
+    Ok(())
+}
+
+#[test]
+fn routes_start_and_end_partway_along_segments() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("partial-edges-ends", Path::new("tests/grid.osm"))?;
+    let graph = Graph::new()?;
+
+    // from a fifth of the way up Col 0 Ave's block to partway along Row 2 St's
+    let (start, end) = (Point::new(-74.0, 40.7012), Point::new(-73.9995, 40.704));
+    let (route, _, _) = graph.calculate_route(&[start, end], false, None, None, None, None)?;
+    let (first, last) = (route.first().unwrap(), route.last().unwrap());
+
+    // the route sets off north from where the rider stands, rather than back down to the corner
+    assert_eq!((first.from.id, first.to.id), (START_NODE_ID, 110));
+    assert_eq!(first.geometry.start, Coord::from(start));
+    assert_eq!(first.length, 90);
+    assert_eq!((last.from.id, last.to.id), (120, END_NODE_ID));
+    assert_eq!(last.geometry.end, Coord::from(end));
+    assert_eq!(last.length, 43);
+    Ok(())
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use rusty_router::graph::{MobileCostModel, ProfileRegistry};
+use rusty_router::osm::Lts;
+
+#[test]
+fn bundled_profiles() -> Result<(), anyhow::Error> {
+    let registry = ProfileRegistry::load()?;
+    for name in ["commuter", "kid-friendly", "fast", "hill-averse"] {
+        assert_eq!(registry.get(name)?.name, name);
+    }
+    assert_eq!(registry.get("fast@1")?.id(), "fast@1");
+    assert_eq!(
+        registry.get("kid-friendly")?.cost_model.max_lts(),
+        Some(Lts::Lts2)
+    );
+
+    // the listing says what each profile is, but not how it's tuned
+    let listing = serde_json::to_value(registry.profiles())?;
+    assert_eq!(listing.as_array().map(Vec::len), Some(4));
+    assert!(listing[0]["description"].is_string());
+    assert!(listing[0].get("cost_model").is_none());
+    Ok(())
+}
+
+#[test]
+fn profile_versions() -> Result<(), anyhow::Error> {
+    let registry = ProfileRegistry::from_json(&format!(
+        r#"[
+            {{"name": "commuter", "version": 2, "heuristic_weight": 0.5,
+             "mobile_cost_model": {{"priority": 0.6, "hill_penalty": 1, "salmon_penalty": 1}}}},
+            {{"name": "commuter", "version": 1, "cost_model": {}}}
+        ]"#,
+        common::cost_model_json("")
+    ))?;
+    // a bare name asks for the latest version
+    let latest = registry.get("commuter")?;
+    assert_eq!((latest.version, latest.heuristic_weight), (2, Some(0.5)));
+    let mobile: MobileCostModel =
+        serde_json::from_str(r#"{"priority": 0.6, "hill_penalty": 1, "salmon_penalty": 1}"#)?;
+    assert_eq!(latest.cost_model, mobile.resolve());
+
+    let pinned = registry.get("commuter@1")?;
+    assert_eq!((pinned.version, pinned.heuristic_weight), (1, None));
+
+    for unknown in ["commuter@3", "commuter@latest", "tourist"] {
+        let error = registry.get(unknown).unwrap_err();
+        assert_eq!(error.code(), "invalid_request");
+    }
+    Ok(())
+}
+
+#[test]
+fn invalid_profiles() {
+    let mobile = r#"{"priority": 0.5, "hill_penalty": 0, "salmon_penalty": 1}"#;
+    for json in [
+        // listed twice
+        format!(
+            r#"[{{"name": "fast", "version": 1, "mobile_cost_model": {mobile}}},
+                {{"name": "fast", "version": 1, "mobile_cost_model": {mobile}}}]"#
+        ),
+        // no cost model at all
+        r#"[{"name": "fast", "version": 1}]"#.to_owned(),
+        // a name requests couldn't ask for
+        format!(r#"[{{"name": "fast@2", "version": 1, "mobile_cost_model": {mobile}}}]"#),
+        // a cost model no request would be allowed to send
+        r#"[{"name": "fast", "version": 1, "mobile_cost_model": {"priority": 0.5, "hill_penalty": 0,
+            "salmon_penalty": 1, "rules": [{"if": {}, "multiply_by": -1}]}}]"#
+            .to_owned(),
+        // a misspelt field
+        format!(r#"[{{"name": "fast", "version": 1, "mobile_costmodel": {mobile}}}]"#),
+    ] {
+        assert!(ProfileRegistry::from_json(&json).is_err(), "{json}");
+    }
+}
@@ -0,0 +1,188 @@
+mod common;
+
+use chrono::NaiveDateTime;
+use common::TestDb;
+use geo::Point;
+use rusty_router::db::{self, Element, OSMMapper};
+use rusty_router::graph::Graph;
+use rusty_router::osm::{OpeningHours, Restriction, TurnRestriction};
+use std::path::Path;
+
+fn at(time: &str) -> NaiveDateTime {
+    // 2024-06-07 is a Friday
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M").unwrap()
+}
+
+#[test]
+fn opening_hours_with_weekdays_and_overnight_spans() -> Result<(), anyhow::Error> {
+    let hours = OpeningHours::parse("Mo-Fr 06:00-01:00; Sa,Su 08:00-22:00")?;
+
+    assert!(hours.is_open(at("2024-06-07 12:00")));
+    assert!(!hours.is_open(at("2024-06-07 05:59")));
+    // Friday's hours run past midnight into Saturday...
+    assert!(hours.is_open(at("2024-06-08 00:30")));
+    // ...until they close, and Saturday's own hours begin
+    assert!(!hours.is_open(at("2024-06-08 01:30")));
+    assert!(hours.is_open(at("2024-06-08 08:00")));
+    assert!(!hours.is_open(at("2024-06-09 22:00")));
+    Ok(())
+}
+
+#[test]
+fn opening_hours_later_rules_override_earlier() -> Result<(), anyhow::Error> {
+    let hours = OpeningHours::parse("24/7; Apr-Oct Su off")?;
+
+    assert!(hours.is_open(at("2024-06-07 03:00")));
+    assert!(!hours.is_open(at("2024-06-09 12:00")));
+    assert!(hours.is_open(at("2024-12-08 12:00")));
+    Ok(())
+}
+
+#[test]
+fn conditional_access_restrictions() -> Result<(), anyhow::Error> {
+    let restrictions = Restriction::parse("bicycle:conditional", "no @ (Mo-Fr 07:00-10:00)")?;
+    assert!(Restriction::is_closed(
+        &restrictions,
+        at("2024-06-07 08:00")
+    ));
+    assert!(!Restriction::is_closed(
+        &restrictions,
+        at("2024-06-08 08:00")
+    ));
+
+    // a Way opened only on a schedule is closed the rest of the time, as its base value says
+    let mut restrictions = vec![Restriction::parse_base("access", "no")?];
+    restrictions.extend(Restriction::parse(
+        "access:conditional",
+        "yes @ (Sa,Su); no @ (PH)",
+    )?);
+    assert!(Restriction::is_closed(
+        &restrictions,
+        at("2024-06-07 12:00")
+    ));
+    assert!(!Restriction::is_closed(
+        &restrictions,
+        at("2024-06-08 12:00")
+    ));
+
+    assert!(Restriction::parse("access:conditional", "no @ wet").is_err());
+    Ok(())
+}
+
+#[test]
+fn way_restrictions_by_tag_precedence() -> Result<(), anyhow::Error> {
+    let restrictions = |tags: &str| -> Result<_, serde_json::Error> {
+        let way: Element =
+            serde_json::from_str(&format!(r#"{{"type": "way", "id": 1, "tags": {tags}}}"#))?;
+        Ok(OSMMapper::from(&way).get_restrictions())
+    };
+    let (rush_hour, saturday) = (at("2024-06-07 08:00"), at("2024-06-08 08:00"));
+
+    // bikes are let through whenever other traffic is kept out
+    let excepted = restrictions(
+        r#"{"access:conditional": "no @ (Mo-Fr 07:00-10:00)",
+            "bicycle:conditional": "yes @ (Mo-Fr 07:00-10:00)"}"#,
+    )?;
+    assert!(!Restriction::is_closed(&excepted, rush_hour));
+    let designated = restrictions(
+        r#"{"bicycle": "designated", "access:conditional": "no @ (Mo-Fr 07:00-10:00)"}"#,
+    )?;
+    assert!(!Restriction::is_closed(&designated, rush_hour));
+    let closed = restrictions(r#"{"access:conditional": "no @ (Mo-Fr 07:00-10:00)"}"#)?;
+    assert!(Restriction::is_closed(&closed, rush_hour));
+    assert!(!Restriction::is_closed(&closed, saturday));
+
+    // outside a conditional opening, the base value decides, and is open unless it says otherwise
+    let weekends = restrictions(r#"{"access": "no", "access:conditional": "yes @ (Sa,Su)"}"#)?;
+    assert!(Restriction::is_closed(&weekends, rush_hour));
+    assert!(!Restriction::is_closed(&weekends, saturday));
+    let weekends = restrictions(r#"{"access:conditional": "yes @ (Sa,Su)"}"#)?;
+    assert!(!Restriction::is_closed(&weekends, rush_hour));
+
+    // opening hours only decide once neither access tag does
+    let park = restrictions(
+        r#"{"opening_hours": "Sa,Su 09:00-18:00", "bicycle:conditional": "yes @ (Mo-Fr 07:00-10:00)"}"#,
+    )?;
+    assert!(!Restriction::is_closed(&park, rush_hour));
+    assert!(Restriction::is_closed(&park, saturday));
+    assert!(!Restriction::is_closed(&park, at("2024-06-08 12:00")));
+    Ok(())
+}
+
+#[test]
+fn turn_restriction_relations() -> Result<(), anyhow::Error> {
+    let relation = |tags: &str| -> Result<Element, serde_json::Error> {
+        serde_json::from_str(&format!(
+            r#"{{"type": "relation", "id": 1, "tags": {tags}, "members": [
+                {{"type": "way", "ref": 10, "role": "from"}},
+                {{"type": "node", "ref": 100, "role": "via"}},
+                {{"type": "way", "ref": 20, "role": "to"}},
+                {{"type": "way", "ref": 30, "role": "to"}}
+            ]}}"#
+        ))
+    };
+
+    let restrictions = relation(r#"{"type": "restriction", "restriction": "no_left_turn"}"#)?
+        .get_turn_restrictions();
+    assert_eq!(restrictions.len(), 2);
+    assert!(!TurnRestriction::is_allowed(&restrictions, 10, -20));
+    assert!(TurnRestriction::is_allowed(&restrictions, 10, 40));
+    assert!(TurnRestriction::is_allowed(&restrictions, 40, 20));
+
+    let excepted = relation(
+        r#"{"type": "restriction", "restriction": "no_left_turn", "except": "psv;bicycle"}"#,
+    )?;
+    assert!(excepted.get_turn_restrictions().is_empty());
+
+    let only = relation(r#"{"type": "restriction", "restriction:bicycle": "only_straight_on"}"#)?
+        .get_turn_restrictions();
+    assert!(TurnRestriction::is_allowed(&only, 10, 20));
+    assert!(!TurnRestriction::is_allowed(&only, 10, 40));
+
+    // a U-turn ban leaves riding straight through along the same Way alone
+    let u_turn = [TurnRestriction {
+        from: 10,
+        via: 100,
+        to: 10,
+        only: false,
+    }];
+    assert!(!TurnRestriction::is_allowed(&u_turn, 10, -10));
+    assert!(TurnRestriction::is_allowed(&u_turn, 10, 10));
+    Ok(())
+}
+
+#[test]
+fn routes_avoid_ways_closed_on_arrival() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("restrictions", Path::new("tests/grid.osm"))?;
+    // Row 0 St closes for the weekday morning rush, from 08:00
+    db::get_conn()?.execute(
+        "INSERT INTO WayRestrictions (way, tag, open, hours) VALUES (1000, 1, 0, 'Mo-Fr 08:00-10:00')",
+        [],
+    )?;
+    let graph = Graph::new()?;
+
+    // from Col 0 Ave over to Col 1 Ave, which is shortest along Row 0 St and a little longer
+    // along Row 1 St. Row 0 St is reached about 25s after setting off.
+    let points = [Point::new(-74.0, 40.7009), Point::new(-73.998, 40.7009)];
+    let departing = |time: &str| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S");
+    for (departure, closed) in [
+        ("2024-06-07 08:30:00", true),
+        ("2024-06-07 07:58:00", false),
+        // setting off while it's still open, but reaching it once it's closed
+        ("2024-06-07 07:59:45", true),
+        // Saturday
+        ("2024-06-08 08:30:00", false),
+    ] {
+        let (route, _, _) = graph.calculate_route(
+            &points,
+            false,
+            None,
+            None,
+            None,
+            Some(departing(departure)?),
+        )?;
+        let rides_row_0 = route.iter().any(|s| s.way.abs() == 1000);
+        assert_eq!(rides_row_0, !closed, "departing {departure}");
+    }
+    Ok(())
+}
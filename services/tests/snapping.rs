@@ -1,16 +1,35 @@
 use geo::Point;
 use rusty_router::graph::{GraphRepository, SqliteGraphRepository};
+use rusty_router::osm::Road;
 
 #[test]
 fn get_corner_coords_in_lot() -> Result<(), anyhow::Error> {
     let db = SqliteGraphRepository::new()?;
-    db.get_snapped_neighbors(Point::new(-73.9791875, 40.690155), None)?;
+    db.get_snap_candidates(Point::new(-73.9791875, 40.690155), None)?;
     Ok(())
 }
 
 #[test]
 fn get_corner_coords_on_road() -> Result<(), anyhow::Error> {
     let db = SqliteGraphRepository::new()?;
-    db.get_snapped_neighbors(Point::new(-73.9790797, 40.6898084), None)?;
+    db.get_snap_candidates(Point::new(-73.9790797, 40.6898084), None)?;
+    Ok(())
+}
+
+#[test]
+fn snap_candidates_are_ranked_and_filtered() -> Result<(), anyhow::Error> {
+    let db = SqliteGraphRepository::new()?;
+    let center = Point::new(-73.9790797, 40.6898084);
+
+    let candidates = db.get_snap_candidates(center, None)?;
+    assert!(candidates
+        .windows(2)
+        .all(|pair| pair[0].distance <= pair[1].distance));
+    assert!(candidates
+        .iter()
+        .all(|c| (0.0..=1.0).contains(&c.fraction) && c.offset <= c.edge.distance));
+
+    let local = db.get_snap_candidates(center, Some(&[Road::Local]))?;
+    assert!(local.iter().all(|c| c.labels.1 == Road::Local));
     Ok(())
 }
@@ -0,0 +1,86 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::graph::{
+    write_snapshot, Graph, GraphRepository, InMemoryGraphRepository, SnapshotGraphRepository,
+    SqliteGraphRepository,
+};
+
+#[test]
+fn snapshot_matches_in_memory_graph() -> Result<(), anyhow::Error> {
+    let mut test_db = TestDb::new("snapshot")?;
+    let graph_path = test_db.scratch_file("graph");
+
+    let sqlite = SqliteGraphRepository::new()?;
+    let in_memory = InMemoryGraphRepository::new()?;
+    let summary = write_snapshot(&in_memory, &graph_path)?;
+    assert_eq!(summary.nodes, 3);
+    assert_eq!(summary.edges, 4);
+
+    // the snapshot stands alone
+    std::env::remove_var("DB_PATH");
+    let snapshot = SnapshotGraphRepository::open(&graph_path)?;
+
+    // the in-memory spatial indexes find just what the R*Tree does
+    for (lon, lat, radius) in [
+        (-73.999, 40.7015, 0.0002),
+        (-73.998, 40.7, 0.001),
+        (-73.9, 40.8, 0.001),
+    ] {
+        let center = Point::new(lon, lat);
+        let mut expected = sqlite.get_way_nodes_near(center, radius)?;
+        expected.sort_by_key(|(node, way)| (*way, node.id));
+        for repository in [&in_memory as &dyn GraphRepository, &snapshot] {
+            let mut found = repository.get_way_nodes_near(center, radius)?;
+            found.sort_by_key(|(node, way)| (*way, node.id));
+            assert_eq!(found, expected);
+        }
+    }
+
+    for id in [1, 2, 3, 4] {
+        assert_eq!(snapshot.get_neighbors(id)?, in_memory.get_neighbors(id)?);
+        assert_eq!(
+            snapshot.get_incoming_neighbors_with_labels(id)?,
+            in_memory.get_incoming_neighbors_with_labels(id)?
+        );
+        for stops in [vec![], vec![2]] {
+            assert_eq!(
+                snapshot.get_compacted_neighbors_with_labels(id, &stops)?,
+                in_memory.get_compacted_neighbors_with_labels(id, &stops)?
+            );
+            assert_eq!(
+                snapshot.get_compacted_incoming_neighbors_with_labels(id, &stops)?,
+                in_memory.get_compacted_incoming_neighbors_with_labels(id, &stops)?
+            );
+        }
+    }
+    for shape in 0..summary.shapes as u32 {
+        assert_eq!(snapshot.get_shape(shape)?, in_memory.get_shape(shape)?);
+    }
+    assert_eq!(snapshot.get_way_labels(10)?, in_memory.get_way_labels(10)?);
+    assert_eq!(
+        snapshot.get_way_names(&[10, -10, 11])?,
+        in_memory.get_way_names(&[10, -10, 11])?
+    );
+    let mut named = snapshot.find_ways_named("BOWERY")?;
+    named.sort();
+    let mut expected = in_memory.find_ways_named("BOWERY")?;
+    expected.sort();
+    assert!(!named.is_empty());
+    assert_eq!(named, expected);
+    assert!(!snapshot.is_turn_allowed(10, 2, -10)?);
+    assert!(snapshot.is_turn_allowed(10, 2, 10)?);
+
+    std::env::set_var("GRAPH_PATH", &graph_path);
+    let graph = Graph::new()?;
+    assert!(graph.hierarchies.is_empty());
+    let points = [Point::new(-73.999, 40.7001), Point::new(-73.999, 40.7019)];
+    let (route, _, _) = graph.calculate_route(&points, false, None, None, None, None)?;
+    assert!(!route.is_empty());
+
+    // a snapshot of another format is turned away rather than misread
+    std::fs::write(&graph_path, b"RRGRAPH\0\x00\x00\x00\x00")?;
+    assert!(SnapshotGraphRepository::open(&graph_path).is_err());
+    Ok(())
+}
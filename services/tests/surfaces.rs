@@ -0,0 +1,78 @@
+mod common;
+
+use rusty_router::db::{Element, OSMMapper};
+use rusty_router::graph::{CostModel, MobileCostModel};
+use rusty_router::osm::{BikeNetwork, Cycleway, Lts, Road, Surface};
+
+fn surface_of(tags: &str) -> Result<Surface, anyhow::Error> {
+    let element: Element =
+        serde_json::from_str(&format!(r#"{{"type": "way", "id": 1, "tags": {tags}}}"#))?;
+    Ok(OSMMapper::from(&element).get_surface_label())
+}
+
+#[test]
+fn surface_labels() -> Result<(), anyhow::Error> {
+    assert_eq!(surface_of(r#"{"highway": "residential"}"#)?, Surface::Paved);
+    assert_eq!(
+        surface_of(r#"{"highway": "residential", "surface": "sett"}"#)?,
+        Surface::Cobbled
+    );
+    assert_eq!(
+        surface_of(r#"{"highway": "path", "surface": "fine_gravel"}"#)?,
+        Surface::Rough
+    );
+    // smoothness says how it rides, whatever it's made of
+    assert_eq!(
+        surface_of(r#"{"highway": "residential", "surface": "asphalt", "smoothness": "bad"}"#)?,
+        Surface::Cobbled
+    );
+    assert_eq!(surface_of(r#"{"highway": "track"}"#)?, Surface::Unpaved);
+    assert_eq!(
+        surface_of(r#"{"highway": "track", "tracktype": "grade1"}"#)?,
+        Surface::Paved
+    );
+    Ok(())
+}
+
+#[test]
+fn rough_surfaces_cost_more() -> Result<(), anyhow::Error> {
+    let labels = |surface| {
+        (
+            Cycleway::No,
+            Road::Local,
+            false,
+            BikeNetwork::None,
+            surface,
+            Lts::Lts1,
+        )
+    };
+    for cost_model in [CostModel::default(), MobileCostModel::comfort().resolve()] {
+        let costs: Vec<_> = [
+            Surface::Paved,
+            Surface::Rough,
+            Surface::Cobbled,
+            Surface::Unpaved,
+        ]
+        .map(|surface| cost_model.calculate_cost(&labels(surface)))
+        .into();
+        assert!(costs.windows(2).all(|pair| pair[0] < pair[1]), "{costs:?}");
+    }
+
+    // models from before surfaces were weighed are unchanged by them
+    let cost_model = common::cost_model("")?;
+    assert_eq!(
+        cost_model.calculate_cost(&labels(Surface::Paved)),
+        cost_model.calculate_cost(&labels(Surface::Unpaved))
+    );
+
+    // the most tolerant riders don't mind the surface at all
+    let tolerant: MobileCostModel = serde_json::from_str(
+        r#"{"priority": 1.0, "hill_penalty": 0, "salmon_penalty": 1, "surface_tolerance": 2}"#,
+    )?;
+    let tolerant = tolerant.resolve();
+    assert_eq!(
+        tolerant.calculate_cost(&labels(Surface::Paved)),
+        tolerant.calculate_cost(&labels(Surface::Cobbled))
+    );
+    Ok(())
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use rusty_router::db::{Element, OSMMapper};
+use rusty_router::graph::{CostModel, MobileCostModel};
+use rusty_router::osm::{BikeNetwork, Cycleway, Lts, Road, Surface};
+
+/// the LTS of riding forward and in reverse along a Way with these tags
+fn lts_of(tags: &str) -> Result<(Lts, Lts), anyhow::Error> {
+    let element: Element =
+        serde_json::from_str(&format!(r#"{{"type": "way", "id": 1, "tags": {tags}}}"#))?;
+    let mapper = OSMMapper::from(&element);
+    let road = mapper.get_road_label();
+    let (forward, reverse, _) = mapper.get_cycleways_and_directionality();
+    Ok((
+        mapper.get_lts(road, forward, true),
+        mapper.get_lts(road, reverse, false),
+    ))
+}
+
+#[test]
+fn mixed_traffic_lts() -> Result<(), anyhow::Error> {
+    assert_eq!(
+        lts_of(r#"{"highway": "residential"}"#)?,
+        (Lts::Lts1, Lts::Lts1)
+    );
+    assert_eq!(
+        lts_of(r#"{"highway": "tertiary", "maxspeed": "25 mph"}"#)?,
+        (Lts::Lts2, Lts::Lts2)
+    );
+    assert_eq!(
+        lts_of(r#"{"highway": "residential", "maxspeed": "30 mph", "lanes": "4"}"#)?,
+        (Lts::Lts4, Lts::Lts4)
+    );
+    // untagged arterials are taken to be wide and fast
+    assert_eq!(lts_of(r#"{"highway": "primary"}"#)?, (Lts::Lts4, Lts::Lts4));
+    // speed limits without a unit are in km/h
+    assert_eq!(
+        lts_of(r#"{"highway": "residential", "maxspeed": "40"}"#)?,
+        (Lts::Lts1, Lts::Lts1)
+    );
+    Ok(())
+}
+
+#[test]
+fn bike_lane_lts() -> Result<(), anyhow::Error> {
+    // separated bikeways are LTS 1 whatever the street beside them
+    assert_eq!(
+        lts_of(r#"{"highway": "primary", "lanes": "6", "cycleway:both": "track"}"#)?,
+        (Lts::Lts1, Lts::Lts1)
+    );
+    assert_eq!(
+        lts_of(r#"{"highway": "secondary", "maxspeed": "25 mph", "cycleway:both": "lane"}"#)?,
+        (Lts::Lts1, Lts::Lts1)
+    );
+    // a bike lane alongside parked cars is more stressful than one without
+    assert_eq!(
+        lts_of(
+            r#"{"highway": "secondary", "maxspeed": "30 mph", "lanes": "2", "cycleway:both": "lane"}"#
+        )?,
+        (Lts::Lts2, Lts::Lts2)
+    );
+    assert_eq!(
+        lts_of(
+            r#"{"highway": "secondary", "maxspeed": "30 mph", "lanes": "2", "cycleway:both": "lane",
+                "parking:both": "no"}"#
+        )?,
+        (Lts::Lts1, Lts::Lts1)
+    );
+    // only the direction with the lane gets its benefit
+    assert_eq!(
+        lts_of(
+            r#"{"highway": "primary", "maxspeed": "30 mph", "lanes": "4", "lanes:forward": "2",
+                "lanes:backward": "2", "cycleway:right": "lane", "cycleway:left": "no"}"#
+        )?,
+        (Lts::Lts3, Lts::Lts4)
+    );
+    Ok(())
+}
+
+#[test]
+fn lts_caps_and_weighs_routes() -> Result<(), anyhow::Error> {
+    let labels = |lts| {
+        (
+            Cycleway::No,
+            Road::Local,
+            false,
+            BikeNetwork::None,
+            Surface::Paved,
+            lts,
+        )
+    };
+
+    // by default, routes ride any street at no extra cost for its stress
+    let cost_model = CostModel::default();
+    assert!(cost_model.allows(&labels(Lts::Lts4)));
+    assert_eq!(
+        cost_model.calculate_cost(&labels(Lts::Lts1)),
+        cost_model.calculate_cost(&labels(Lts::Lts4))
+    );
+
+    let cost_model = common::cost_model(
+        r#""lts_coefficient": 0.5, "max_lts": 2,
+            "lts_weights": {"1": 0.0, "2": 1.0, "3": 2.0, "4": 4.0}"#,
+    )?;
+    cost_model.validate()?;
+    assert_eq!(cost_model.max_lts(), Some(Lts::Lts2));
+    assert!(cost_model.allows(&labels(Lts::Lts2)));
+    assert!(!cost_model.allows(&labels(Lts::Lts3)));
+    assert!(
+        (cost_model.calculate_cost(&labels(Lts::Lts2))
+            - cost_model.calculate_cost(&labels(Lts::Lts1))
+            - 0.5)
+            .abs()
+            < 1e-6
+    );
+
+    let mobile: MobileCostModel = serde_json::from_str(
+        r#"{"priority": 0.5, "hill_penalty": 0, "salmon_penalty": 1, "max_lts": 1}"#,
+    )?;
+    assert_eq!(mobile.resolve().max_lts(), Some(Lts::Lts1));
+
+    // there is no LTS 5
+    assert!(serde_json::from_str::<MobileCostModel>(
+        r#"{"priority": 0.5, "hill_penalty": 0, "salmon_penalty": 1, "max_lts": 5}"#,
+    )
+    .is_err());
+    Ok(())
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::graph::{Graph, MobileCostModel};
+use std::path::Path;
+
+#[test]
+fn turn_penalty_avoids_left_turns() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("turn-costs", Path::new("tests/grid.osm"))?;
+    let graph = Graph::new()?;
+
+    // from Col 0 Ave over to Col 1 Ave, a little nearer Row 0 St than Row 1 St. Riding south
+    // around the block is shorter, but turns left twice; north around it turns right twice.
+    let points = [Point::new(-74.0, 40.7009), Point::new(-73.998, 40.7009)];
+    let mobile_cost_model = |turn_penalty: u8| -> Result<MobileCostModel, serde_json::Error> {
+        serde_json::from_str(&format!(
+            r#"{{"priority": 0.5, "hill_penalty": 1, "salmon_penalty": 1, "turn_penalty": {turn_penalty}}}"#
+        ))
+    };
+
+    let (lefts, _, _) = graph.calculate_route(
+        &points,
+        false,
+        Some(mobile_cost_model(0)?.resolve()),
+        None,
+        None,
+        None,
+    )?;
+    let nodes: Vec<_> = lefts.iter().map(|s| s.to.id).collect();
+    assert!(nodes.contains(&100) && nodes.contains(&101), "{nodes:?}");
+
+    let (rights, _, _) = graph.calculate_route(
+        &points,
+        false,
+        Some(mobile_cost_model(2)?.resolve()),
+        None,
+        None,
+        None,
+    )?;
+    let nodes: Vec<_> = rights.iter().map(|s| s.to.id).collect();
+    assert!(nodes.contains(&110) && nodes.contains(&111), "{nodes:?}");
+    // it's longer, but the rights are worth it
+    assert!(rights.last().unwrap().distance_so_far > lefts.last().unwrap().distance_so_far);
+    Ok(())
+}
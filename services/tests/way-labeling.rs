@@ -1,10 +1,12 @@
 use rusty_router::{
     graph::{GraphRepository, SqliteGraphRepository},
-    osm::{Cycleway, WayLabels},
+    osm::{Cycleway, Salmoning, WayLabels},
     osm::{Road, WayId},
 };
 
-fn assert_helper(expected: WayLabels, actual: WayLabels, header: WayId) {
+/// compares the labels drawn from the Way's own tags, leaving out bike route membership
+fn assert_helper(expected: (Cycleway, Road, Salmoning), labels: WayLabels, header: WayId) {
+    let actual = (labels.0, labels.1, labels.2);
     assert!(
         expected == actual,
         "{header} | expected: {:?}, actual: {:?}",
@@ -0,0 +1,58 @@
+mod common;
+
+use common::TestDb;
+use geo::Point;
+use rusty_router::graph::{Graph, END_NODE_ID, START_NODE_ID};
+use std::collections::HashSet;
+use std::path::Path;
+
+#[test]
+fn via_points_stitch_legs_together() -> Result<(), anyhow::Error> {
+    let _db = TestDb::from_extract("waypoints", Path::new("tests/grid.osm"))?;
+    let graph = Graph::new()?;
+
+    // up Col 0 Ave, through a via point partway along Row 2 St's first block, and on east along it
+    let points = [
+        Point::new(-74.0, 40.7005),
+        Point::new(-73.9995, 40.704),
+        Point::new(-73.9965, 40.704),
+    ];
+    let (route, _, _) = graph.calculate_route(&points, false, None, None, None, None)?;
+
+    let legs: Vec<usize> = route.iter().map(|s| s.leg).collect();
+    assert!(legs.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!((legs[0], legs[legs.len() - 1]), (0, 1));
+
+    // the first leg arrives at the via point, and the second sets off from it along the same
+    // block, without backing up to a corner or riding any part of the block twice
+    let via = legs.iter().position(|&leg| leg == 1).unwrap();
+    let (arrival, departure) = (&route[via - 1], &route[via]);
+    assert_eq!(
+        (arrival.to.id, departure.from.id),
+        (END_NODE_ID, START_NODE_ID)
+    );
+    assert_eq!(arrival.to.geometry, departure.from.geometry);
+    assert_eq!(arrival.way, departure.way);
+    assert_ne!(arrival.from.id, departure.to.id);
+
+    let mut ridden = HashSet::new();
+    let mut distance = 0;
+    for (i, segment) in route.iter().enumerate() {
+        if i > 0 {
+            assert_eq!(segment.from.geometry, route[i - 1].to.geometry);
+            assert!(segment.cost_so_far >= route[i - 1].cost_so_far);
+        }
+        if segment.from.id != START_NODE_ID && segment.to.id != END_NODE_ID {
+            assert!(ridden.insert((segment.from.id, segment.to.id)));
+        }
+        distance += segment.length;
+        assert_eq!(segment.distance_so_far, distance);
+    }
+
+    // the via point lies along one of the shortest ways across the grid, so stopping there adds
+    // no distance, as it would if either half of its block were ridden twice
+    let (direct, _, _) =
+        graph.calculate_route(&[points[0], points[2]], false, None, None, None, None)?;
+    assert_eq!(distance, direct.last().unwrap().distance_so_far);
+    Ok(())
+}